@@ -0,0 +1,342 @@
+mod session;
+
+use std::{fmt::Display, future::Future, pin::Pin, process::Stdio, time::Duration};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    process::Command,
+};
+
+use self::session::Session;
+
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+type ServerInput = Box<dyn AsyncWrite + Unpin + Send>;
+type ServerOutput = Box<dyn AsyncRead + Unpin + Send>;
+
+/// A launched server under test, `exit_code` resolves once it has exited.
+pub struct ServerProcess {
+    pub input: ServerInput,
+    pub output: ServerOutput,
+    pub exit_code: Pin<Box<dyn Future<Output = Option<i32>> + Send>>,
+}
+
+impl ServerProcess {
+    pub fn spawn(command: &mut Command) -> std::io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let input = child.stdin.take().expect("stdin should be piped");
+        let output = child.stdout.take().expect("stdout should be piped");
+
+        Ok(Self {
+            input: Box::new(input),
+            output: Box::new(output),
+            exit_code: Box::pin(
+                async move { child.wait().await.ok().and_then(|status| status.code()) },
+            ),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub outcome: Result<(), String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub results: Vec<CheckResult>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|result| result.outcome.is_ok())
+    }
+
+    fn record(&mut self, name: &'static str, outcome: Result<(), String>) {
+        self.results.push(CheckResult { name, outcome })
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for result in &self.results {
+            match &result.outcome {
+                Ok(()) => writeln!(f, "PASS  {}", result.name)?,
+                Err(reason) => writeln!(f, "FAIL  {}: {}", result.name, reason)?,
+            }
+        }
+        let passed_count = self
+            .results
+            .iter()
+            .filter(|result| result.outcome.is_ok())
+            .count();
+        write!(f, "{}/{} checks passed", passed_count, self.results.len())
+    }
+}
+
+/// Runs the base protocol checks, launching the server twice since one of them has it exit
+/// without a prior shutdown. `timeout` bounds every wait for the server.
+pub async fn check_conformance(
+    mut launch: impl FnMut() -> std::io::Result<ServerProcess>,
+    timeout: Duration,
+) -> std::io::Result<Report> {
+    let mut report = Report::default();
+    check_lifecycle(launch()?, timeout, &mut report).await;
+    check_exit_without_shutdown(launch()?, timeout, &mut report).await;
+    Ok(report)
+}
+
+async fn check_lifecycle(server: ServerProcess, timeout: Duration, report: &mut Report) {
+    let mut session = Session::new(server.input, server.output, timeout);
+
+    report.record(
+        "server-not-initialized",
+        expect_error_code(
+            session
+                .request("workspace/symbol", json!({ "query": "" }))
+                .await,
+            SERVER_NOT_INITIALIZED,
+        ),
+    );
+
+    let initialize_outcome = initialize(&mut session).await;
+    let initialized = initialize_outcome.is_ok();
+    report.record("initialize", initialize_outcome);
+    if !initialized {
+        return;
+    }
+
+    report.record(
+        "method-not-found",
+        expect_error_code(
+            session
+                .request("spique/conformanceUnknownMethod", json!({}))
+                .await,
+            METHOD_NOT_FOUND,
+        ),
+    );
+
+    report.record(
+        "dollar-notifications",
+        check_dollar_notification(&mut session, timeout).await,
+    );
+    report.record("cancellation", check_cancellation(&mut session).await);
+
+    let shutdown_outcome = match session.request("shutdown", Value::Null).await {
+        Ok(Ok(Value::Null)) => Ok(()),
+        Ok(Ok(result)) => Err(format!("expected a null result, got {}", result)),
+        Ok(Err(error)) => Err(format!("shutdown failed: {}", error)),
+        Err(err) => Err(err),
+    };
+    report.record("shutdown", shutdown_outcome);
+
+    let exit_outcome = match session.send_notification("exit", Value::Null).await {
+        Ok(()) => expect_exit_code(server.exit_code, 0, timeout).await,
+        Err(err) => Err(err),
+    };
+    report.record("exit-after-shutdown", exit_outcome);
+
+    report.record(
+        "framing",
+        match session.decode_errors.is_empty() {
+            true => Ok(()),
+            false => Err(session.decode_errors.join(", ")),
+        },
+    );
+    report.record(
+        "response-ids",
+        match session.violations.is_empty() {
+            true => Ok(()),
+            false => Err(session.violations.join(", ")),
+        },
+    );
+}
+
+async fn check_exit_without_shutdown(
+    server: ServerProcess,
+    timeout: Duration,
+    report: &mut Report,
+) {
+    let mut session = Session::new(server.input, server.output, timeout);
+    let outcome = match initialize(&mut session).await {
+        Ok(()) => match session.send_notification("exit", Value::Null).await {
+            Ok(()) => expect_exit_code(server.exit_code, 1, timeout).await,
+            Err(err) => Err(err),
+        },
+        Err(err) => Err(err),
+    };
+    report.record("exit-without-shutdown", outcome);
+}
+
+async fn initialize(session: &mut Session) -> Result<(), String> {
+    let params = json!({
+        "processId": null,
+        "rootUri": null,
+        "capabilities": {},
+    });
+
+    match session.request("initialize", params).await? {
+        Ok(result) if result.get("capabilities").is_some() => {
+            session.send_notification("initialized", json!({})).await
+        }
+        Ok(result) => Err(format!("initialize result lacks capabilities: {}", result)),
+        Err(error) => Err(format!("initialize failed: {}", error)),
+    }
+}
+
+/// Notifications starting with `$/` may be ignored, but must never be answered.
+async fn check_dollar_notification(session: &mut Session, timeout: Duration) -> Result<(), String> {
+    session
+        .send_notification("$/spiqueConformanceUnknown", json!({}))
+        .await?;
+
+    let quiet_period = timeout.min(Duration::from_millis(500));
+    let responses = session
+        .receive_for(quiet_period)
+        .await?
+        .into_iter()
+        .filter(|message| message.is_response())
+        .count();
+
+    match responses {
+        0 => Ok(()),
+        _ => Err("server responded to a notification".to_owned()),
+    }
+}
+
+/// A cancelled request must still receive exactly one response, duplicates are reported by the
+/// session itself.
+async fn check_cancellation(session: &mut Session) -> Result<(), String> {
+    let request_id = session
+        .send_request("workspace/symbol", json!({ "query": "" }))
+        .await?;
+    session
+        .send_notification("$/cancelRequest", json!({ "id": request_id }))
+        .await?;
+    session.response(&request_id).await.map(|_| ())
+}
+
+fn expect_error_code(
+    response: Result<Result<Value, Value>, String>,
+    expected_code: i64,
+) -> Result<(), String> {
+    match response? {
+        Err(error) if error["code"] == expected_code => Ok(()),
+        Err(error) => Err(format!(
+            "expected error code {}, got {}",
+            expected_code, error["code"]
+        )),
+        Ok(result) => Err(format!(
+            "expected error code {}, got result {}",
+            expected_code, result
+        )),
+    }
+}
+
+async fn expect_exit_code(
+    exit_code: impl Future<Output = Option<i32>>,
+    expected_code: i32,
+    timeout: Duration,
+) -> Result<(), String> {
+    match tokio::time::timeout(timeout, exit_code).await {
+        Ok(Some(code)) if code == expected_code => Ok(()),
+        Ok(Some(code)) => Err(format!(
+            "expected exit code {}, got {}",
+            expected_code, code
+        )),
+        Ok(None) => Err("server was terminated by a signal".to_owned()),
+        Err(_) => Err("server did not exit in time".to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{FutureExt, SinkExt, StreamExt};
+    use spique_core::messages::{codec::LanguageServerCodec, groups::raw::RawMessage};
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    use super::*;
+
+    /// In-process server following the base protocol, optionally answering requests before
+    /// `initialize`.
+    fn mock_server(answers_uninitialized: bool) -> std::io::Result<ServerProcess> {
+        let (server_input, input) = tokio::io::duplex(4096);
+        let (output, server_output) = tokio::io::duplex(4096);
+        let (exit_code_tx, exit_code_rx) = futures::channel::oneshot::channel();
+
+        tokio::spawn(async move {
+            let mut framed_input =
+                FramedRead::new(server_input, LanguageServerCodec::<RawMessage>::default());
+            let mut framed_output =
+                FramedWrite::new(server_output, LanguageServerCodec::<RawMessage>::default());
+            let mut initialized = false;
+            let mut shut_down = false;
+
+            while let Some(Ok(message)) = framed_input.next().await {
+                let method = message.method().unwrap_or_default();
+                let Some(request_id) = message.id().cloned() else {
+                    if method == "exit" {
+                        let _ = exit_code_tx.send(if shut_down { 0 } else { 1 });
+                        return;
+                    }
+                    continue;
+                };
+
+                let result = match method {
+                    "initialize" => {
+                        initialized = true;
+                        Ok(json!({ "capabilities": {} }))
+                    }
+                    _ if !initialized && !answers_uninitialized => {
+                        Err(json!({ "code": SERVER_NOT_INITIALIZED, "message": "uninitialized" }))
+                    }
+                    "shutdown" => {
+                        shut_down = true;
+                        Ok(Value::Null)
+                    }
+                    "workspace/symbol" => Ok(json!([])),
+                    _ => Err(json!({ "code": METHOD_NOT_FOUND, "message": "unknown" })),
+                };
+                let response = RawMessage::response(request_id, result);
+                framed_output.send(response).await.unwrap();
+            }
+        });
+
+        Ok(ServerProcess {
+            input: Box::new(input),
+            output: Box::new(output),
+            exit_code: Box::pin(exit_code_rx.map(Result::ok)),
+        })
+    }
+
+    #[tokio::test]
+    async fn passes_conforming_server() {
+        let report = check_conformance(|| mock_server(false), Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(report.passed(), "{}", report)
+    }
+
+    #[tokio::test]
+    async fn fails_server_answering_before_initialize() {
+        let report = check_conformance(|| mock_server(true), Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        let failed_checks = report
+            .results
+            .iter()
+            .filter(|result| result.outcome.is_err())
+            .map(|result| result.name)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["server-not-initialized"], failed_checks)
+    }
+}
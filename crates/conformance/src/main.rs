@@ -0,0 +1,44 @@
+use std::{process::ExitCode, time::Duration};
+
+use spique_conformance::{check_conformance, ServerProcess};
+use tokio::process::Command;
+
+const USAGE: &str = "usage: spique-conformance [--timeout <seconds>] <server> [server args...]";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    let mut timeout = Duration::from_secs(5);
+
+    if args.peek().is_some_and(|arg| arg == "--timeout") {
+        args.next();
+        match args.next().and_then(|seconds| seconds.parse().ok()) {
+            Some(seconds) => timeout = Duration::from_secs_f64(seconds),
+            None => {
+                eprintln!("{}", USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let Some(program) = args.next() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let server_args = args.collect::<Vec<_>>();
+
+    let launch = || ServerProcess::spawn(Command::new(&program).args(&server_args));
+    match check_conformance(launch, timeout).await {
+        Ok(report) => {
+            println!("{}", report);
+            match report.passed() {
+                true => ExitCode::SUCCESS,
+                false => ExitCode::FAILURE,
+            }
+        }
+        Err(err) => {
+            eprintln!("unable to launch {}: {}", program, err);
+            ExitCode::from(2)
+        }
+    }
+}
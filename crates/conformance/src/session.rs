@@ -0,0 +1,152 @@
+use std::{collections::HashSet, time::Duration};
+
+use futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use spique_core::messages::{codec::LanguageServerCodec, groups::raw::RawMessage};
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+use crate::{ServerInput, ServerOutput};
+
+/// Client end of a conversation with the server under test, keeping track of base protocol
+/// violations as messages are received.
+pub(crate) struct Session {
+    framed_input: FramedWrite<ServerInput, LanguageServerCodec<RawMessage>>,
+    framed_output: FramedRead<ServerOutput, LanguageServerCodec<RawMessage>>,
+    timeout: Duration,
+    next_request_id: i64,
+    pending_request_ids: HashSet<Value>,
+    answered_request_ids: HashSet<Value>,
+    pub violations: Vec<String>,
+    pub decode_errors: Vec<String>,
+}
+
+impl Session {
+    pub fn new(server_input: ServerInput, server_output: ServerOutput, timeout: Duration) -> Self {
+        Self {
+            framed_input: FramedWrite::new(server_input, LanguageServerCodec::default()),
+            framed_output: FramedRead::new(server_output, LanguageServerCodec::default()),
+            timeout,
+            next_request_id: 0,
+            pending_request_ids: HashSet::new(),
+            answered_request_ids: HashSet::new(),
+            violations: Vec::new(),
+            decode_errors: Vec::new(),
+        }
+    }
+
+    pub async fn send_request(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        self.next_request_id += 1;
+        let request_id = Value::from(self.next_request_id);
+        self.pending_request_ids.insert(request_id.clone());
+        self.send(RawMessage::request(
+            request_id.clone(),
+            method,
+            Some(params),
+        ))
+        .await?;
+        Ok(request_id)
+    }
+
+    pub async fn send_notification(&mut self, method: &str, params: Value) -> Result<(), String> {
+        self.send(RawMessage::notification(method, params)).await
+    }
+
+    /// Sends the request and awaits its response, returning the result or error object.
+    pub async fn request(
+        &mut self,
+        method: &str,
+        params: Value,
+    ) -> Result<Result<Value, Value>, String> {
+        let request_id = self.send_request(method, params).await?;
+        self.response(&request_id).await
+    }
+
+    pub async fn response(&mut self, request_id: &Value) -> Result<Result<Value, Value>, String> {
+        loop {
+            match self.receive(self.timeout).await? {
+                Some(message) if message.is_response() && message.id() == Some(request_id) => {
+                    return Ok(message.into_result())
+                }
+                Some(_) => continue,
+                None => return Err(format!("no response to request {} in time", request_id)),
+            }
+        }
+    }
+
+    /// Collects whatever the server sends within the period.
+    pub async fn receive_for(&mut self, period: Duration) -> Result<Vec<RawMessage>, String> {
+        let deadline = tokio::time::Instant::now() + period;
+        let mut messages = Vec::new();
+        loop {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            match self.receive(remaining).await? {
+                Some(message) => messages.push(message),
+                None => return Ok(messages),
+            }
+        }
+    }
+
+    /// Answers server requests with a `null` result so that the server isn't left waiting, and
+    /// records responses that don't belong to any request.
+    async fn receive(&mut self, timeout: Duration) -> Result<Option<RawMessage>, String> {
+        let message = match tokio::time::timeout(timeout, self.framed_output.next()).await {
+            Err(_) => return Ok(None),
+            Ok(None) => return Err("server closed its output".to_owned()),
+            Ok(Some(Err(err))) => {
+                self.decode_errors.push(err.to_string());
+                return Err(format!("malformed frame from server: {}", err));
+            }
+            Ok(Some(Ok(message))) => message,
+        };
+
+        if message.is_request() {
+            let request_id = message.id().cloned().unwrap_or_default();
+            self.send(RawMessage::response(request_id, Ok(Value::Null)))
+                .await?;
+        } else if message.is_response() {
+            let response_id = message.id().cloned().unwrap_or_default();
+            if self.pending_request_ids.remove(&response_id) {
+                self.answered_request_ids.insert(response_id);
+            } else if self.answered_request_ids.contains(&response_id) {
+                self.violations
+                    .push(format!("request {} answered more than once", response_id));
+            } else {
+                self.violations
+                    .push(format!("response to unknown request id {}", response_id));
+            }
+        }
+
+        Ok(Some(message))
+    }
+
+    async fn send(&mut self, message: RawMessage) -> Result<(), String> {
+        self.framed_input
+            .send(message)
+            .await
+            .map_err(|err| format!("unable to write to server: {:?}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_malformed_frames() {
+        let (_server_input, input) = tokio::io::duplex(4096);
+        let (output, mut server_output) = tokio::io::duplex(4096);
+        let mut session = Session::new(Box::new(input), Box::new(output), Duration::from_secs(1));
+
+        let request_id = session.send_request("shutdown", Value::Null).await.unwrap();
+        server_output
+            .write_all(b"Content-Length: 5\r\n\r\n{oops")
+            .await
+            .unwrap();
+
+        let err = session.response(&request_id).await.unwrap_err();
+        assert!(err.starts_with("malformed frame"), "{}", err);
+        assert_eq!(1, session.decode_errors.len());
+    }
+}
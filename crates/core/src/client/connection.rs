@@ -0,0 +1,106 @@
+use futures::{channel::mpsc::UnboundedReceiver, SinkExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+use crate::{
+    messages::{
+        codec::{Frames, LanguageServerCodec},
+        groups::AllMessages,
+    },
+    outbox::PendingResponses,
+};
+
+use super::handler::ClientHandler;
+
+/// Drives the connection to the language server, must be running for any client requests to
+/// resolve.
+pub struct ClientConnection<I: AsyncRead + Unpin, O: AsyncWrite + Unpin, H: ClientHandler> {
+    framed_input: Frames<FramedRead<I, LanguageServerCodec<AllMessages>>>,
+    framed_output: FramedWrite<O, LanguageServerCodec<AllMessages>>,
+    outgoing_rx: UnboundedReceiver<AllMessages>,
+    pending_responses: PendingResponses,
+    handler: H,
+}
+
+impl<I: AsyncRead + Unpin, O: AsyncWrite + Unpin, H: ClientHandler> ClientConnection<I, O, H> {
+    pub(super) fn new(
+        server_output: I,
+        server_input: O,
+        outgoing_rx: UnboundedReceiver<AllMessages>,
+        pending_responses: PendingResponses,
+        handler: H,
+    ) -> Self {
+        Self {
+            framed_input: Frames::new(FramedRead::new(
+                server_output,
+                LanguageServerCodec::default(),
+            )),
+            framed_output: FramedWrite::new(server_input, LanguageServerCodec::default()),
+            outgoing_rx,
+            pending_responses,
+            handler,
+        }
+    }
+
+    /// Runs until either the server closes its output or every [`LanguageClient`] handle has
+    /// been dropped.
+    ///
+    /// [`LanguageClient`]: super::LanguageClient
+    pub async fn run(mut self) {
+        loop {
+            tokio::select! {
+                decode_attempt = self.framed_input.next() => match decode_attempt {
+                    Some(Ok(message)) => {
+                        if !self.handle_incoming(message).await {
+                            break;
+                        }
+                    }
+                    Some(Err(err)) => tracing::warn!(%err, "Unable to decode server message."),
+                    None => break,
+                },
+                outgoing_message = self.outgoing_rx.next() => match outgoing_message {
+                    Some(message) => {
+                        if !self.write(message).await {
+                            break;
+                        }
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        // Closed first for requests sent from now on to fail rather than wait for a response.
+        self.outgoing_rx.close();
+        self.pending_responses.clear()
+    }
+
+    async fn handle_incoming(&mut self, message: AllMessages) -> bool {
+        tracing::debug!(?message, "Received message from server.");
+        match message {
+            AllMessages::Requests(request) => {
+                let response = self.handler.handle_request(request);
+                self.write(AllMessages::UntypedResponse(response)).await
+            }
+            AllMessages::UntypedResponse(response) => {
+                if let Err(response) = self.pending_responses.resolve(response) {
+                    tracing::warn!(?response, "Received response to unknown request.")
+                }
+                true
+            }
+            AllMessages::Notifications(notification) => {
+                self.handler.handle_notification(notification);
+                true
+            }
+        }
+    }
+
+    async fn write(&mut self, message: AllMessages) -> bool {
+        match self.framed_output.send(message).await {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::error!(?err, "Unable to write to server input.");
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use crate::messages::{
+    core::{
+        response::{
+            response_error::{ReservedResponseErrorCodes, ResponseError, ResponseErrorCode},
+            UntypedResponseMessage,
+        },
+        LspRequest,
+    },
+    groups::{notifications::AllNotifications, requests::AllRequests},
+};
+
+/// Handles the messages a language server initiates.
+pub trait ClientHandler {
+    /// Server requests such as `workspace/configuration`. Defaults to a `MethodNotFound` error
+    /// response.
+    fn handle_request(&mut self, request: AllRequests) -> UntypedResponseMessage {
+        method_not_found(&request)
+    }
+
+    fn handle_notification(&mut self, notification: AllNotifications) {
+        tracing::debug!(?notification, "Ignoring server notification.")
+    }
+}
+
+impl ClientHandler for () {}
+
+pub fn method_not_found(request: &AllRequests) -> UntypedResponseMessage {
+    UntypedResponseMessage {
+        id: request.request_id().clone().into(),
+        kind: Err(ResponseError {
+            code: ResponseErrorCode::Reserved(ReservedResponseErrorCodes::MethodNotFound),
+            message: "request not handled by client".to_owned(),
+            data: None,
+        }),
+    }
+}
@@ -0,0 +1,247 @@
+mod connection;
+mod handler;
+
+pub use connection::ClientConnection;
+pub use handler::{method_not_found, ClientHandler};
+
+use std::process::Stdio;
+
+use lsp_types::{
+    notification::{Exit, Initialized, Notification},
+    request::{Initialize, Request, Shutdown},
+    InitializeParams, InitializeResult, InitializedParams,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpStream, ToSocketAddrs},
+    process::{Child, ChildStdin, ChildStdout, Command},
+};
+
+use crate::{
+    messages::{
+        core::{notification::NotificationMessage, request::RequestMessage},
+        groups::{
+            notifications::{AllNotifications, AllServerNotifications},
+            requests::{AllRequests, AllServerRequests},
+        },
+    },
+    outbox::{Outbox, RequestError},
+};
+
+/// Client side counterpart of the [`Service`](crate::service::Service), for driving language
+/// servers from test harnesses and batch tools.
+#[derive(Clone)]
+pub struct LanguageClient {
+    outbox: Outbox,
+}
+
+impl LanguageClient {
+    pub fn new<I, O, H>(
+        server_output: I,
+        server_input: O,
+        handler: H,
+    ) -> (Self, ClientConnection<I, O, H>)
+    where
+        I: AsyncRead + Unpin,
+        O: AsyncWrite + Unpin,
+        H: ClientHandler,
+    {
+        let (outbox, outgoing_rx, pending_responses) = Outbox::new(None);
+        let connection = ClientConnection::new(
+            server_output,
+            server_input,
+            outgoing_rx,
+            pending_responses,
+            handler,
+        );
+
+        (Self { outbox }, connection)
+    }
+
+    /// Spawns the language server with piped stdio, the child is returned so that it may be
+    /// awaited after the exit notification.
+    pub fn spawn<H: ClientHandler>(
+        command: &mut Command,
+        handler: H,
+    ) -> std::io::Result<(Self, ClientConnection<ChildStdout, ChildStdin, H>, Child)> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let server_input = child.stdin.take().expect("stdin should be piped");
+        let server_output = child.stdout.take().expect("stdout should be piped");
+        let (client, connection) = Self::new(server_output, server_input, handler);
+
+        Ok((client, connection, child))
+    }
+
+    pub async fn connect_tcp<H: ClientHandler>(
+        addr: impl ToSocketAddrs,
+        handler: H,
+    ) -> std::io::Result<(
+        Self,
+        ClientConnection<tokio::net::tcp::OwnedReadHalf, tokio::net::tcp::OwnedWriteHalf, H>,
+    )> {
+        let (read_half, write_half) = TcpStream::connect(addr).await?.into_split();
+        Ok(Self::new(read_half, write_half, handler))
+    }
+
+    #[cfg(unix)]
+    pub async fn connect_unix<H: ClientHandler>(
+        path: impl AsRef<std::path::Path>,
+        handler: H,
+    ) -> std::io::Result<(
+        Self,
+        ClientConnection<tokio::net::unix::OwnedReadHalf, tokio::net::unix::OwnedWriteHalf, H>,
+    )> {
+        let (read_half, write_half) = tokio::net::UnixStream::connect(path).await?.into_split();
+        Ok(Self::new(read_half, write_half, handler))
+    }
+
+    pub async fn request<R: Request>(
+        &self,
+        params: Option<R::Params>,
+    ) -> Result<R::Result, RequestError>
+    where
+        RequestMessage<R>: Into<AllServerRequests> + Into<AllRequests>,
+    {
+        self.outbox.send_request::<R>(params).await
+    }
+
+    pub fn notify<N: Notification>(&self, params: Option<N::Params>)
+    where
+        NotificationMessage<N>: Into<AllServerNotifications> + Into<AllNotifications>,
+    {
+        self.outbox.send_notification::<N>(params)
+    }
+
+    /// Sends the `initialize` request followed by the `initialized` notification.
+    pub async fn initialize(
+        &self,
+        initialize_params: InitializeParams,
+    ) -> Result<InitializeResult, RequestError> {
+        let initialize_result = self.request::<Initialize>(Some(initialize_params)).await?;
+        self.notify::<Initialized>(Some(InitializedParams {}));
+        Ok(initialize_result)
+    }
+
+    /// Sends the `shutdown` request followed by the `exit` notification.
+    pub async fn shutdown(&self) -> Result<(), RequestError> {
+        self.request::<Shutdown>(None).await?;
+        self.notify::<Exit>(None);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use lsp_types::{request::WorkspaceConfiguration, ConfigurationParams};
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    use crate::{
+        messages::{
+            codec::LanguageServerCodec,
+            core::{response::UntypedResponseMessage, LspRequest},
+            groups::{requests::AllClientRequests, AllMessages},
+        },
+        service::{filter::tests::FilterMock, server::tests::shutdown_backend_mock, Service},
+    };
+
+    use super::*;
+
+    struct ConfigurationHandlerMock;
+
+    impl ClientHandler for ConfigurationHandlerMock {
+        fn handle_request(&mut self, request: AllRequests) -> UntypedResponseMessage {
+            match request {
+                AllRequests::Client(AllClientRequests::Configuration(request)) => {
+                    UntypedResponseMessage {
+                        id: request.id.into(),
+                        kind: Ok(serde_json::json!([{ "enabled": true }])),
+                    }
+                }
+                request => method_not_found(&request),
+            }
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn shuts_down_service() {
+        let (service_input, client_output) = tokio::io::duplex(1024);
+        let (client_input, service_output) = tokio::io::duplex(1024);
+        let service_handle = tokio::spawn(
+            Service::<FilterMock, _, _>::new(service_input, service_output)
+                .run(shutdown_backend_mock),
+        );
+
+        let (client, connection) = LanguageClient::new(client_input, client_output, ());
+        let connection_handle = tokio::spawn(connection.run());
+
+        client.shutdown().await.unwrap();
+        drop(client);
+        connection_handle.await.unwrap();
+        service_handle.await.unwrap();
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn fails_requests_after_connection_closed() {
+        let (_server_input, client_output) = tokio::io::duplex(1024);
+        let (client_input, server_output) = tokio::io::duplex(1024);
+        drop(server_output);
+
+        let (client, connection) = LanguageClient::new(client_input, client_output, ());
+        connection.run().await;
+
+        assert!(matches!(
+            client.request::<Shutdown>(None).await,
+            Err(RequestError::ConnectionClosed)
+        ));
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn responds_to_server_requests() {
+        let (server_input, client_output) = tokio::io::duplex(1024);
+        let (client_input, server_output) = tokio::io::duplex(1024);
+
+        let (_client, connection) =
+            LanguageClient::new(client_input, client_output, ConfigurationHandlerMock);
+        tokio::spawn(connection.run());
+
+        let configuration_request = RequestMessage::<WorkspaceConfiguration> {
+            id: lsp_types::NumberOrString::Number(1).into(),
+            params: Some(ConfigurationParams { items: vec![] }),
+        };
+        let request_id = configuration_request.request_id().clone();
+
+        FramedWrite::new(server_output, LanguageServerCodec::<AllMessages>::default())
+            .send(AllMessages::Requests(configuration_request.into()))
+            .await
+            .unwrap();
+
+        let Some(Ok(AllMessages::UntypedResponse(response))) =
+            FramedRead::new(server_input, LanguageServerCodec::<AllMessages>::default())
+                .next()
+                .await
+        else {
+            panic!("expected a response from the client")
+        };
+        assert_eq!(response.id, request_id.into());
+        assert!(response.kind.is_ok())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn fails_requests_when_server_exits() {
+        let (client, connection, mut child) =
+            LanguageClient::spawn(&mut Command::new("true"), ()).unwrap();
+        let connection_handle = tokio::spawn(connection.run());
+
+        assert!(matches!(
+            client.request::<Shutdown>(None).await,
+            Err(RequestError::ConnectionClosed)
+        ));
+        connection_handle.await.unwrap();
+        child.wait().await.unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+use lsp_types::{Position, PositionEncodingKind};
+use ropey::{Rope, RopeSlice};
+
+use super::DocumentError;
+
+/// What the characters of a [`Position`] count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PositionEncoding {
+    Utf8,
+    /// Mandatory for clients to support, and used unless another encoding was negotiated.
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    pub fn from_kind(kind: &PositionEncodingKind) -> Option<Self> {
+        [Self::Utf8, Self::Utf16, Self::Utf32]
+            .into_iter()
+            .find(|encoding| encoding.kind() == *kind)
+    }
+
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            PositionEncoding::Utf8 => PositionEncodingKind::UTF8,
+            PositionEncoding::Utf16 => PositionEncodingKind::UTF16,
+            PositionEncoding::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+
+    fn char_to_units(self, line: RopeSlice, char_index: usize) -> usize {
+        match self {
+            PositionEncoding::Utf8 => line.char_to_byte(char_index),
+            PositionEncoding::Utf16 => line.char_to_utf16_cu(char_index),
+            PositionEncoding::Utf32 => char_index,
+        }
+    }
+
+    /// Units within a character round down to its start.
+    fn units_to_char(self, line: RopeSlice, units: usize) -> usize {
+        match self {
+            PositionEncoding::Utf8 => line.byte_to_char(units),
+            PositionEncoding::Utf16 => line.utf16_cu_to_char(units),
+            PositionEncoding::Utf32 => units,
+        }
+    }
+}
+
+/// Characters past the end of the line are clamped to it, as the specification requires.
+pub(crate) fn position_to_char(
+    text: &Rope,
+    position: Position,
+    encoding: PositionEncoding,
+) -> Result<usize, DocumentError> {
+    let line_index = position.line as usize;
+    if line_index >= text.len_lines() {
+        return Err(DocumentError::InvalidPosition(position));
+    }
+
+    let line = text.line(line_index);
+    let line_length = encoding.char_to_units(line, line.len_chars() - line_ending_length(line));
+    let character = encoding.units_to_char(line, line_length.min(position.character as usize));
+    Ok(text.line_to_char(line_index) + character)
+}
+
+pub(crate) fn char_to_position(
+    text: &Rope,
+    char_index: usize,
+    encoding: PositionEncoding,
+) -> Position {
+    let line_index = text.char_to_line(char_index);
+    let line_start = text.line_to_char(line_index);
+    let character = encoding.char_to_units(text.line(line_index), char_index - line_start);
+    Position::new(line_index as u32, character as u32)
+}
+
+fn line_ending_length(line: RopeSlice) -> usize {
+    let mut chars = line.chars_at(line.len_chars());
+    match (chars.prev(), chars.prev()) {
+        (Some('\n'), Some('\r')) => 2,
+        (Some('\n' | '\r'), _) => 1,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_positions_in_each_encoding() {
+        let text = Rope::from_str("a\r\nä🦀b\n");
+        let b_index = 5;
+
+        for (encoding, character) in [
+            (PositionEncoding::Utf8, 6),
+            (PositionEncoding::Utf16, 3),
+            (PositionEncoding::Utf32, 2),
+        ] {
+            let position = Position::new(1, character);
+            assert_eq!(Ok(b_index), position_to_char(&text, position, encoding));
+            assert_eq!(position, char_to_position(&text, b_index, encoding));
+        }
+    }
+
+    #[test]
+    fn clamps_characters_to_line_end() {
+        let text = Rope::from_str("ab\r\n");
+        assert_eq!(
+            Ok(2),
+            position_to_char(&text, Position::new(0, 9), PositionEncoding::Utf16)
+        );
+        assert_eq!(
+            Err(DocumentError::InvalidPosition(Position::new(2, 0))),
+            position_to_char(&text, Position::new(2, 0), PositionEncoding::Utf16)
+        );
+    }
+
+    #[test]
+    fn maps_encoding_kinds() {
+        for encoding in [
+            PositionEncoding::Utf8,
+            PositionEncoding::Utf16,
+            PositionEncoding::Utf32,
+        ] {
+            assert_eq!(
+                Some(encoding),
+                PositionEncoding::from_kind(&encoding.kind())
+            );
+        }
+        assert_eq!(
+            None,
+            PositionEncoding::from_kind(&PositionEncodingKind::new("utf-7"))
+        );
+    }
+}
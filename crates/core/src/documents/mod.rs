@@ -0,0 +1,36 @@
+mod encoding;
+mod notebook;
+mod notebook_store;
+mod store;
+mod text;
+
+pub use encoding::PositionEncoding;
+pub use notebook::{ConcatenatedNotebook, NotebookCell, NotebookDocument};
+pub use notebook_store::NotebookStore;
+pub use store::DocumentStore;
+pub use text::TextDocument;
+
+use derive_more::Display;
+use lsp_types::{Position, Range, Url};
+
+#[derive(Debug, Display, PartialEq)]
+pub enum DocumentError {
+    #[display(fmt = "document {} is not open", _0)]
+    NotOpen(Url),
+    #[display(fmt = "version {} received after version {}", received, current)]
+    OutOfOrderVersion { current: i32, received: i32 },
+    #[display(
+        fmt = "position {}:{} is outside of the document",
+        "_0.line",
+        "_0.character"
+    )]
+    InvalidPosition(Position),
+    #[display(fmt = "offset {} is outside of the document", _0)]
+    InvalidOffset(usize),
+    #[display(fmt = "range end precedes its start")]
+    InvalidRange(Range),
+    #[display(fmt = "cells {}..{} are outside of the notebook", start, end)]
+    InvalidCells { start: usize, end: usize },
+    #[display(fmt = "no text document opened for cell {}", _0)]
+    MissingCellDocument(Url),
+}
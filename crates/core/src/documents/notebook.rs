@@ -0,0 +1,553 @@
+use std::collections::HashMap;
+
+use lsp_types::{
+    ExecutionSummary, LSPObject, Notebook, NotebookCellKind, NotebookCellSelector,
+    NotebookDocumentChangeEvent, NotebookDocumentFilter, NotebookSelector, TextDocumentItem, Url,
+};
+use ropey::Rope;
+
+use crate::glob::Glob;
+
+use super::{DocumentError, PositionEncoding, TextDocument};
+
+const CELL_SEPARATOR: &str = "\n";
+
+#[derive(Debug, Clone)]
+pub struct NotebookCell {
+    kind: NotebookCellKind,
+    metadata: Option<LSPObject>,
+    execution_summary: Option<ExecutionSummary>,
+    document: TextDocument,
+}
+
+impl NotebookCell {
+    fn new(cell: lsp_types::NotebookCell, document: TextDocument) -> Self {
+        Self {
+            kind: cell.kind,
+            metadata: cell.metadata,
+            execution_summary: cell.execution_summary,
+            document,
+        }
+    }
+
+    pub fn kind(&self) -> &NotebookCellKind {
+        &self.kind
+    }
+
+    pub fn metadata(&self) -> Option<&LSPObject> {
+        self.metadata.as_ref()
+    }
+
+    pub fn execution_summary(&self) -> Option<&ExecutionSummary> {
+        self.execution_summary.as_ref()
+    }
+
+    pub fn document(&self) -> &TextDocument {
+        &self.document
+    }
+}
+
+/// An open notebook along with the text documents of its cells. Cloning it is cheap, like
+/// cloning a [`TextDocument`].
+#[derive(Debug, Clone)]
+pub struct NotebookDocument {
+    uri: Url,
+    notebook_type: String,
+    version: i32,
+    metadata: Option<LSPObject>,
+    cells: Vec<NotebookCell>,
+    /// Languages of the cells synced by the notebook selector, all of them if `None`.
+    synced_languages: Option<Vec<String>>,
+    position_encoding: PositionEncoding,
+}
+
+impl NotebookDocument {
+    /// Every cell needs its text document among `cell_text_documents`.
+    pub fn new(
+        notebook: lsp_types::NotebookDocument,
+        cell_text_documents: Vec<TextDocumentItem>,
+        position_encoding: PositionEncoding,
+    ) -> Result<Self, DocumentError> {
+        let mut cell_text_documents = cell_text_documents
+            .into_iter()
+            .map(|item| (item.uri.clone(), item))
+            .collect::<HashMap<_, _>>();
+        let cells = notebook
+            .cells
+            .into_iter()
+            .map(|cell| {
+                let item = cell_text_documents
+                    .remove(&cell.document)
+                    .ok_or_else(|| DocumentError::MissingCellDocument(cell.document.clone()))?;
+                Ok(NotebookCell::new(
+                    cell,
+                    TextDocument::new(item, position_encoding),
+                ))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            uri: notebook.uri,
+            notebook_type: notebook.notebook_type,
+            version: notebook.version,
+            metadata: notebook.metadata,
+            cells,
+            synced_languages: None,
+            position_encoding,
+        })
+    }
+
+    pub(crate) fn with_synced_languages(mut self, synced_languages: Option<Vec<String>>) -> Self {
+        self.synced_languages = synced_languages;
+        self
+    }
+
+    pub fn uri(&self) -> &Url {
+        &self.uri
+    }
+
+    pub fn notebook_type(&self) -> &str {
+        &self.notebook_type
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn metadata(&self) -> Option<&LSPObject> {
+        self.metadata.as_ref()
+    }
+
+    pub fn cells(&self) -> &[NotebookCell] {
+        &self.cells
+    }
+
+    /// Looks the cell up by the uri of its text document.
+    pub fn cell(&self, uri: &Url) -> Option<&NotebookCell> {
+        self.cells.iter().find(|cell| cell.document.uri() == uri)
+    }
+
+    /// Whether the language of the cell is one synced by the notebook selector.
+    pub fn is_synced(&self, cell: &NotebookCell) -> bool {
+        self.synced_languages.as_ref().is_none_or(|languages| {
+            languages
+                .iter()
+                .any(|language| language == "*" || language == cell.document.language_id())
+        })
+    }
+
+    /// Applies the change as a whole, leaving the notebook untouched if any part of it fails.
+    ///
+    /// Cells moved by a structural change keep their text document, while new cells need theirs
+    /// among the ones opened by the change.
+    pub fn apply_change(
+        &mut self,
+        version: i32,
+        change: &NotebookDocumentChangeEvent,
+    ) -> Result<(), DocumentError> {
+        if version <= self.version {
+            return Err(DocumentError::OutOfOrderVersion {
+                current: self.version,
+                received: version,
+            });
+        }
+
+        let mut notebook = self.clone();
+        if let Some(metadata) = &change.metadata {
+            notebook.metadata = Some(metadata.clone())
+        }
+
+        if let Some(cell_change) = &change.cells {
+            if let Some(structure) = &cell_change.structure {
+                let start = structure.array.start as usize;
+                let end = start + structure.array.delete_count as usize;
+                if end > notebook.cells.len() {
+                    return Err(DocumentError::InvalidCells { start, end });
+                }
+
+                let mut opened_documents = structure
+                    .did_open
+                    .iter()
+                    .flatten()
+                    .map(|item| (item.uri.clone(), item))
+                    .collect::<HashMap<_, _>>();
+                let mut deleted_documents = notebook
+                    .cells
+                    .drain(start..end)
+                    .map(|cell| (cell.document.uri().clone(), cell.document))
+                    .collect::<HashMap<_, _>>();
+                let inserted_cells = structure
+                    .array
+                    .cells
+                    .iter()
+                    .flatten()
+                    .map(|cell| {
+                        let document = match opened_documents.remove(&cell.document) {
+                            Some(item) => TextDocument::new(item.clone(), self.position_encoding),
+                            None => deleted_documents.remove(&cell.document).ok_or_else(|| {
+                                DocumentError::MissingCellDocument(cell.document.clone())
+                            })?,
+                        };
+                        Ok(NotebookCell::new(cell.clone(), document))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                notebook.cells.splice(start..start, inserted_cells);
+            }
+
+            for data in cell_change.data.iter().flatten() {
+                let cell = notebook.cell_mut(&data.document)?;
+                cell.kind = data.kind.clone();
+                cell.metadata = data.metadata.clone();
+                cell.execution_summary = data.execution_summary.clone();
+            }
+
+            for text_content in cell_change.text_content.iter().flatten() {
+                notebook
+                    .cell_mut(&text_content.document.uri)?
+                    .document
+                    .apply_changes(text_content.document.version, &text_content.changes)?;
+            }
+        }
+
+        notebook.version = version;
+        *self = notebook;
+        Ok(())
+    }
+
+    fn cell_mut(&mut self, uri: &Url) -> Result<&mut NotebookCell, DocumentError> {
+        self.cells
+            .iter_mut()
+            .find(|cell| cell.document.uri() == uri)
+            .ok_or_else(|| DocumentError::NotOpen(uri.clone()))
+    }
+
+    /// Joins the text of the synced code cells into a single virtual document, separating the
+    /// cells by a newline.
+    pub fn concatenate(&self) -> ConcatenatedNotebook {
+        let mut text = Rope::new();
+        let mut cells = Vec::new();
+        let code_cells = self
+            .cells
+            .iter()
+            .filter(|cell| cell.kind == NotebookCellKind::Code && self.is_synced(cell));
+        for (index, cell) in code_cells.enumerate() {
+            if index > 0 {
+                text.append(Rope::from_str(CELL_SEPARATOR));
+            }
+            let cell_text = cell.document.text().clone();
+            cells.push(ConcatenatedCell {
+                uri: cell.document.uri().clone(),
+                start: text.len_bytes(),
+                length: cell_text.len_bytes(),
+            });
+            text.append(cell_text);
+        }
+
+        ConcatenatedNotebook { text, cells }
+    }
+}
+
+/// The cells of a notebook as a single document, mapping offsets back to the cells.
+#[derive(Debug, Clone)]
+pub struct ConcatenatedNotebook {
+    text: Rope,
+    cells: Vec<ConcatenatedCell>,
+}
+
+#[derive(Debug, Clone)]
+struct ConcatenatedCell {
+    uri: Url,
+    start: usize,
+    length: usize,
+}
+
+impl ConcatenatedNotebook {
+    pub fn text(&self) -> &Rope {
+        &self.text
+    }
+
+    /// The uri of the cell holding the byte offset, along with the byte offset into the cell.
+    /// Offsets of cell separators belong to the end of the cell preceding them.
+    pub fn cell_offset(&self, offset: usize) -> Option<(&Url, usize)> {
+        let index = self
+            .cells
+            .partition_point(|cell| cell.start <= offset)
+            .checked_sub(1)?;
+        let cell = &self.cells[index];
+        (offset <= cell.start + cell.length).then(|| (&cell.uri, offset - cell.start))
+    }
+
+    /// The byte offset into the concatenated text of the byte offset into the cell.
+    pub fn offset(&self, cell_uri: &Url, cell_offset: usize) -> Option<usize> {
+        self.cells
+            .iter()
+            .find(|cell| cell.uri == *cell_uri && cell_offset <= cell.length)
+            .map(|cell| cell.start + cell_offset)
+    }
+}
+
+/// The languages of the cells synced for the notebook by the first selector matching it, with
+/// `Some(None)` meaning every language and `None` that no selector matches.
+pub(crate) fn synced_languages(
+    selectors: &[NotebookSelector],
+    uri: &Url,
+    notebook_type: &str,
+) -> Option<Option<Vec<String>>> {
+    let languages = |cells: &[NotebookCellSelector]| {
+        cells
+            .iter()
+            .map(|cell| cell.language.clone())
+            .collect::<Vec<_>>()
+    };
+
+    selectors.iter().find_map(|selector| match selector {
+        NotebookSelector::ByNotebook { notebook, cells } => {
+            notebook_matches(notebook, uri, notebook_type).then(|| cells.as_deref().map(languages))
+        }
+        NotebookSelector::ByCells { notebook, cells } => notebook
+            .as_ref()
+            .is_none_or(|notebook| notebook_matches(notebook, uri, notebook_type))
+            .then(|| Some(languages(cells))),
+    })
+}
+
+fn notebook_matches(notebook: &Notebook, uri: &Url, notebook_type: &str) -> bool {
+    let type_matches = |filter_type: &str| filter_type == "*" || filter_type == notebook_type;
+    let (filter_type, scheme, pattern) = match notebook {
+        Notebook::String(filter_type) => return type_matches(filter_type),
+        Notebook::NotebookDocumentFilter(NotebookDocumentFilter::ByType {
+            notebook_type,
+            scheme,
+            pattern,
+        }) => (Some(notebook_type), scheme.as_ref(), pattern.as_ref()),
+        Notebook::NotebookDocumentFilter(NotebookDocumentFilter::ByScheme {
+            notebook_type,
+            scheme,
+            pattern,
+        }) => (notebook_type.as_ref(), Some(scheme), pattern.as_ref()),
+        Notebook::NotebookDocumentFilter(NotebookDocumentFilter::ByPattern {
+            notebook_type,
+            scheme,
+            pattern,
+        }) => (notebook_type.as_ref(), scheme.as_ref(), Some(pattern)),
+    };
+
+    filter_type.is_none_or(|filter_type| type_matches(filter_type))
+        && scheme.is_none_or(|scheme| scheme == uri.scheme())
+        && pattern
+            .is_none_or(|pattern| Glob::new(pattern).is_ok_and(|glob| glob.is_match(uri.path())))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use lsp_types::{
+        NotebookCellArrayChange, NotebookDocumentCellChange, NotebookDocumentCellChangeStructure,
+        NotebookDocumentChangeTextContent, VersionedTextDocumentIdentifier,
+    };
+
+    use crate::documents::text::tests::change_mock;
+
+    use super::*;
+
+    pub fn cell_uri(index: usize) -> Url {
+        Url::parse(&format!("vscode-notebook-cell:/a.ipynb#{}", index)).unwrap()
+    }
+
+    pub fn cell_mock(index: usize, kind: NotebookCellKind) -> lsp_types::NotebookCell {
+        lsp_types::NotebookCell {
+            kind,
+            document: cell_uri(index),
+            metadata: None,
+            execution_summary: None,
+        }
+    }
+
+    pub fn cell_text_document_mock(
+        index: usize,
+        language_id: &str,
+        text: &str,
+    ) -> TextDocumentItem {
+        TextDocumentItem {
+            uri: cell_uri(index),
+            language_id: language_id.to_string(),
+            version: 0,
+            text: text.to_string(),
+        }
+    }
+
+    /// A code cell `a = 1`, a markup cell and a code cell `b = 2`.
+    pub fn notebook_mock() -> (lsp_types::NotebookDocument, Vec<TextDocumentItem>) {
+        let notebook = lsp_types::NotebookDocument {
+            uri: Url::parse("file:///a.ipynb").unwrap(),
+            notebook_type: "jupyter-notebook".to_string(),
+            version: 0,
+            metadata: None,
+            cells: vec![
+                cell_mock(0, NotebookCellKind::Code),
+                cell_mock(1, NotebookCellKind::Markup),
+                cell_mock(2, NotebookCellKind::Code),
+            ],
+        };
+        let cell_text_documents = vec![
+            cell_text_document_mock(0, "python", "a = 1"),
+            cell_text_document_mock(1, "markdown", "# A"),
+            cell_text_document_mock(2, "python", "b = 2"),
+        ];
+        (notebook, cell_text_documents)
+    }
+
+    fn notebook_document_mock() -> NotebookDocument {
+        let (notebook, cell_text_documents) = notebook_mock();
+        NotebookDocument::new(notebook, cell_text_documents, PositionEncoding::Utf16).unwrap()
+    }
+
+    fn cell_texts(notebook: &NotebookDocument) -> Vec<String> {
+        notebook
+            .cells()
+            .iter()
+            .map(|cell| cell.document().text().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn applies_structure_and_text_changes() {
+        let mut notebook = notebook_document_mock();
+        // Swaps the first two cells.
+        let change = NotebookDocumentChangeEvent {
+            metadata: None,
+            cells: Some(NotebookDocumentCellChange {
+                structure: Some(NotebookDocumentCellChangeStructure {
+                    array: NotebookCellArrayChange {
+                        start: 0,
+                        delete_count: 2,
+                        cells: Some(vec![
+                            cell_mock(1, NotebookCellKind::Markup),
+                            cell_mock(0, NotebookCellKind::Code),
+                        ]),
+                    },
+                    did_open: None,
+                    did_close: None,
+                }),
+                data: None,
+                text_content: None,
+            }),
+        };
+        notebook.apply_change(1, &change).unwrap();
+
+        // Replaces the last cell with two new ones, editing the first of them.
+        let change = NotebookDocumentChangeEvent {
+            metadata: None,
+            cells: Some(NotebookDocumentCellChange {
+                structure: Some(NotebookDocumentCellChangeStructure {
+                    array: NotebookCellArrayChange {
+                        start: 2,
+                        delete_count: 1,
+                        cells: Some(vec![
+                            cell_mock(3, NotebookCellKind::Code),
+                            cell_mock(4, NotebookCellKind::Code),
+                        ]),
+                    },
+                    did_open: Some(vec![
+                        cell_text_document_mock(3, "python", "c = 3"),
+                        cell_text_document_mock(4, "python", "d = 4"),
+                    ]),
+                    did_close: Some(vec![lsp_types::TextDocumentIdentifier { uri: cell_uri(2) }]),
+                }),
+                data: None,
+                text_content: Some(vec![NotebookDocumentChangeTextContent {
+                    document: VersionedTextDocumentIdentifier {
+                        uri: cell_uri(3),
+                        version: 1,
+                    },
+                    changes: vec![change_mock((0, 4), (0, 5), "33")],
+                }]),
+            }),
+        };
+        notebook.apply_change(2, &change).unwrap();
+
+        assert_eq!(
+            vec!["# A", "a = 1", "c = 33", "d = 4"],
+            cell_texts(&notebook)
+        );
+        assert_eq!(2, notebook.version());
+    }
+
+    #[test]
+    fn rejects_changes_atomically() {
+        let mut notebook = notebook_document_mock();
+        let change = NotebookDocumentChangeEvent {
+            metadata: Some(LSPObject::default()),
+            cells: Some(NotebookDocumentCellChange {
+                structure: Some(NotebookDocumentCellChangeStructure {
+                    array: NotebookCellArrayChange {
+                        start: 0,
+                        delete_count: 1,
+                        cells: Some(vec![cell_mock(5, NotebookCellKind::Code)]),
+                    },
+                    did_open: None,
+                    did_close: None,
+                }),
+                data: None,
+                text_content: None,
+            }),
+        };
+
+        assert_eq!(
+            Err(DocumentError::MissingCellDocument(cell_uri(5))),
+            notebook.apply_change(1, &change)
+        );
+        assert_eq!(3, notebook.cells().len());
+        assert!(notebook.metadata().is_none());
+        assert_eq!(0, notebook.version());
+    }
+
+    #[test]
+    fn maps_concatenated_offsets_to_cells() {
+        let concatenated = notebook_document_mock().concatenate();
+        assert_eq!("a = 1\nb = 2", concatenated.text().to_string());
+
+        assert_eq!(Some((&cell_uri(0), 5)), concatenated.cell_offset(5));
+        assert_eq!(Some((&cell_uri(2), 0)), concatenated.cell_offset(6));
+        assert_eq!(Some((&cell_uri(2), 5)), concatenated.cell_offset(11));
+        assert_eq!(None, concatenated.cell_offset(12));
+        assert_eq!(Some(10), concatenated.offset(&cell_uri(2), 4));
+        assert_eq!(None, concatenated.offset(&cell_uri(1), 0));
+    }
+
+    #[test]
+    fn matches_notebook_selectors() {
+        let uri = Url::parse("file:///notebooks/a.ipynb").unwrap();
+        let by_pattern = NotebookSelector::ByNotebook {
+            notebook: Notebook::NotebookDocumentFilter(NotebookDocumentFilter::ByPattern {
+                notebook_type: None,
+                scheme: Some("file".to_string()),
+                pattern: "**/*.ipynb".to_string(),
+            }),
+            cells: None,
+        };
+        let by_cells = NotebookSelector::ByCells {
+            notebook: Some(Notebook::String("jupyter-notebook".to_string())),
+            cells: vec![NotebookCellSelector {
+                language: "python".to_string(),
+            }],
+        };
+
+        assert_eq!(
+            Some(None),
+            synced_languages(std::slice::from_ref(&by_pattern), &uri, "jupyter-notebook")
+        );
+        assert_eq!(
+            Some(Some(vec!["python".to_string()])),
+            synced_languages(std::slice::from_ref(&by_cells), &uri, "jupyter-notebook")
+        );
+        assert_eq!(None, synced_languages(&[by_cells], &uri, "interactive"));
+        assert_eq!(
+            None,
+            synced_languages(
+                &[by_pattern],
+                &Url::parse("untitled:a.ipynb").unwrap(),
+                "jupyter-notebook"
+            )
+        );
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use lsp_types::{NotebookDocumentChangeEvent, NotebookSelector, TextDocumentItem, Url};
+
+use crate::{
+    messages::groups::{
+        notifications::{AllNotifications, AllServerNotifications},
+        AllMessages,
+    },
+    service::{
+        encoding::announced_position_encoding, filter::MessageHook, initialize::InitializeTracker,
+    },
+};
+
+use super::{
+    notebook::synced_languages, DocumentError, NotebookDocument, PositionEncoding, TextDocument,
+};
+
+const NOTEBOOKS_POISONED: &str = "notebooks lock poisoned";
+const NOTEBOOK_DOCUMENT_SYNC_KEY: &str = "notebookDocumentSync";
+const NOTEBOOK_SELECTOR_KEY: &str = "notebookSelector";
+
+/// The notebooks opened by the client, kept in sync with it when added to a
+/// [`Service`](crate::service::Service) with
+/// [`with_notebook_store`](crate::service::Service::with_notebook_store).
+///
+/// Only notebooks matching the `notebookDocumentSync` selectors announced in the `initialize`
+/// result are tracked, every notebook if none were announced.
+#[derive(Clone, Default)]
+pub struct NotebookStore {
+    state: Arc<RwLock<NotebookStoreState>>,
+    initialize_tracker: InitializeTracker,
+}
+
+#[derive(Default)]
+struct NotebookStoreState {
+    notebooks: HashMap<Url, NotebookDocument>,
+    selectors: Option<Vec<NotebookSelector>>,
+    position_encoding: PositionEncoding,
+}
+
+impl NotebookStore {
+    /// A snapshot of the notebook, unaffected by later changes.
+    pub fn get(&self, uri: &Url) -> Option<NotebookDocument> {
+        self.state
+            .read()
+            .expect(NOTEBOOKS_POISONED)
+            .notebooks
+            .get(uri)
+            .cloned()
+    }
+
+    pub fn uris(&self) -> Vec<Url> {
+        self.state
+            .read()
+            .expect(NOTEBOOKS_POISONED)
+            .notebooks
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// A snapshot of the notebook holding the cell with the text document.
+    pub fn notebook_of_cell(&self, cell_uri: &Url) -> Option<NotebookDocument> {
+        self.state
+            .read()
+            .expect(NOTEBOOKS_POISONED)
+            .notebooks
+            .values()
+            .find(|notebook| notebook.cell(cell_uri).is_some())
+            .cloned()
+    }
+
+    /// A snapshot of the text document of the cell.
+    pub fn cell_document(&self, cell_uri: &Url) -> Option<TextDocument> {
+        self.notebook_of_cell(cell_uri)
+            .and_then(|notebook| notebook.cell(cell_uri).map(|cell| cell.document().clone()))
+    }
+
+    /// Applies to notebooks opened afterwards.
+    pub fn set_position_encoding(&self, position_encoding: PositionEncoding) {
+        self.state
+            .write()
+            .expect(NOTEBOOKS_POISONED)
+            .position_encoding = position_encoding
+    }
+
+    /// Applies to notebooks opened afterwards, `None` letting every notebook through.
+    pub fn set_selectors(&self, selectors: Option<Vec<NotebookSelector>>) {
+        self.state.write().expect(NOTEBOOKS_POISONED).selectors = selectors
+    }
+
+    /// Replaces any notebook already open with the same uri. Returns whether the notebook
+    /// matched the selectors.
+    pub fn open(
+        &self,
+        notebook: lsp_types::NotebookDocument,
+        cell_text_documents: Vec<TextDocumentItem>,
+    ) -> Result<bool, DocumentError> {
+        let mut state = self.state.write().expect(NOTEBOOKS_POISONED);
+        let synced_languages = match &state.selectors {
+            Some(selectors) => {
+                match synced_languages(selectors, &notebook.uri, &notebook.notebook_type) {
+                    Some(synced_languages) => synced_languages,
+                    None => return Ok(false),
+                }
+            }
+            None => None,
+        };
+
+        let notebook =
+            NotebookDocument::new(notebook, cell_text_documents, state.position_encoding)?
+                .with_synced_languages(synced_languages);
+        state.notebooks.insert(notebook.uri().clone(), notebook);
+        Ok(true)
+    }
+
+    pub fn change(
+        &self,
+        uri: &Url,
+        version: i32,
+        change: &NotebookDocumentChangeEvent,
+    ) -> Result<(), DocumentError> {
+        self.state
+            .write()
+            .expect(NOTEBOOKS_POISONED)
+            .notebooks
+            .get_mut(uri)
+            .ok_or_else(|| DocumentError::NotOpen(uri.clone()))?
+            .apply_change(version, change)
+    }
+
+    pub fn close(&self, uri: &Url) {
+        self.state
+            .write()
+            .expect(NOTEBOOKS_POISONED)
+            .notebooks
+            .remove(uri);
+    }
+}
+
+impl MessageHook for NotebookStore {
+    fn inspect_incoming(&mut self, message: &AllMessages) {
+        self.initialize_tracker.inspect_incoming(message);
+        let AllMessages::Notifications(AllNotifications::Server(notification)) = message else {
+            return;
+        };
+
+        let result = match notification {
+            AllServerNotifications::DidOpenNotebook(notification) => {
+                let Some(params) = &notification.params else {
+                    return;
+                };
+                self.open(
+                    params.notebook_document.clone(),
+                    params.cell_text_documents.clone(),
+                )
+                .map(|_| ())
+            }
+            AllServerNotifications::DidChangeNotebook(notification) => {
+                let Some(params) = &notification.params else {
+                    return;
+                };
+                let notebook = &params.notebook_document;
+                let is_tracked = self
+                    .state
+                    .read()
+                    .expect(NOTEBOOKS_POISONED)
+                    .notebooks
+                    .contains_key(&notebook.uri);
+                // Notebooks not matching the selectors are left out on purpose.
+                if !is_tracked {
+                    return;
+                }
+                self.change(&notebook.uri, notebook.version, &params.change)
+            }
+            AllServerNotifications::DidCloseNotebookDocument(notification) => {
+                if let Some(params) = &notification.params {
+                    self.close(&params.notebook_document.uri)
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        if let Err(err) = result {
+            tracing::warn!(%err, "Unable to apply notebook changes.")
+        }
+    }
+
+    fn amend_outgoing(&mut self, message: &mut AllMessages) {
+        let Some(capabilities) = self.initialize_tracker.capabilities_mut(message) else {
+            return;
+        };
+
+        self.set_position_encoding(announced_position_encoding(capabilities));
+        let selectors = capabilities
+            .get(NOTEBOOK_DOCUMENT_SYNC_KEY)
+            .and_then(|sync| sync.get(NOTEBOOK_SELECTOR_KEY))
+            .and_then(|selectors| serde_json::from_value(selectors.clone()).ok());
+        self.set_selectors(selectors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{
+        notification::{DidCloseNotebookDocument, DidOpenNotebookDocument},
+        ClientCapabilities, DidCloseNotebookDocumentParams, DidOpenNotebookDocumentParams,
+        NotebookDocumentIdentifier,
+    };
+    use serde_json::json;
+
+    use crate::{
+        documents::notebook::tests::{cell_uri, notebook_mock},
+        messages::{
+            core::notification::NotificationMessage, groups::requests::tests::initialize_mock,
+        },
+        service::initialize::tests::initialize_response_mock,
+    };
+
+    use super::*;
+
+    fn open_notebook(store: &mut NotebookStore) {
+        let (notebook, cell_text_documents) = notebook_mock();
+        store.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<DidOpenNotebookDocument> {
+                params: Some(DidOpenNotebookDocumentParams {
+                    notebook_document: notebook,
+                    cell_text_documents,
+                }),
+            }
+            .into(),
+        ));
+    }
+
+    fn announce_capabilities(store: &mut NotebookStore, capabilities: serde_json::Value) {
+        store.inspect_incoming(&initialize_mock(ClientCapabilities::default()));
+        store.amend_outgoing(&mut initialize_response_mock(
+            json!({ "capabilities": capabilities }),
+        ));
+    }
+
+    #[test]
+    fn tracks_notebooks_from_notifications() {
+        let mut store = NotebookStore::default();
+        open_notebook(&mut store);
+
+        let notebook = store.notebook_of_cell(&cell_uri(1)).unwrap();
+        assert_eq!(3, notebook.cells().len());
+        assert_eq!(
+            "# A",
+            store
+                .cell_document(&cell_uri(1))
+                .unwrap()
+                .text()
+                .to_string()
+        );
+
+        store.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<DidCloseNotebookDocument> {
+                params: Some(DidCloseNotebookDocumentParams {
+                    notebook_document: NotebookDocumentIdentifier {
+                        uri: notebook.uri().clone(),
+                    },
+                    cell_text_documents: Vec::new(),
+                }),
+            }
+            .into(),
+        ));
+        assert!(store.uris().is_empty());
+    }
+
+    #[test]
+    fn respects_announced_selectors() {
+        let mut store = NotebookStore::default();
+        announce_capabilities(
+            &mut store,
+            json!({
+                "notebookDocumentSync": {
+                    "notebookSelector": [{ "notebook": "interactive" }]
+                }
+            }),
+        );
+        open_notebook(&mut store);
+        assert!(store.uris().is_empty());
+
+        announce_capabilities(
+            &mut store,
+            json!({
+                "notebookDocumentSync": {
+                    "notebookSelector": [{ "cells": [{ "language": "python" }] }]
+                }
+            }),
+        );
+        open_notebook(&mut store);
+        let notebook = store.uris().pop().and_then(|uri| store.get(&uri)).unwrap();
+        assert!(!notebook.is_synced(&notebook.cells()[1]));
+        assert_eq!("a = 1\nb = 2", notebook.concatenate().text().to_string());
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use lsp_types::{TextDocumentContentChangeEvent, TextDocumentItem, Url};
+
+use crate::{
+    messages::groups::{
+        notifications::{AllNotifications, AllServerNotifications},
+        AllMessages,
+    },
+    service::{
+        encoding::announced_position_encoding, filter::MessageHook, initialize::InitializeTracker,
+    },
+};
+
+use super::{DocumentError, PositionEncoding, TextDocument};
+
+const DOCUMENTS_POISONED: &str = "documents lock poisoned";
+
+/// The text documents opened by the client, kept in sync with it when added to a
+/// [`Service`](crate::service::Service) with
+/// [`with_document_store`](crate::service::Service::with_document_store).
+///
+/// Documents are updated before the backend receives the notification changing them, and use
+/// the position encoding announced in the `initialize` result.
+#[derive(Clone, Default)]
+pub struct DocumentStore {
+    state: Arc<RwLock<StoreState>>,
+    initialize_tracker: InitializeTracker,
+}
+
+#[derive(Default)]
+struct StoreState {
+    documents: HashMap<Url, TextDocument>,
+    position_encoding: PositionEncoding,
+}
+
+impl DocumentStore {
+    /// A snapshot of the document, unaffected by later changes.
+    pub fn get(&self, uri: &Url) -> Option<TextDocument> {
+        self.state
+            .read()
+            .expect(DOCUMENTS_POISONED)
+            .documents
+            .get(uri)
+            .cloned()
+    }
+
+    pub fn uris(&self) -> Vec<Url> {
+        self.state
+            .read()
+            .expect(DOCUMENTS_POISONED)
+            .documents
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    pub fn position_encoding(&self) -> PositionEncoding {
+        self.state
+            .read()
+            .expect(DOCUMENTS_POISONED)
+            .position_encoding
+    }
+
+    /// Applies to documents opened afterwards.
+    pub fn set_position_encoding(&self, position_encoding: PositionEncoding) {
+        self.state
+            .write()
+            .expect(DOCUMENTS_POISONED)
+            .position_encoding = position_encoding
+    }
+
+    /// Replaces any document already open with the same uri.
+    pub fn open(&self, item: TextDocumentItem) {
+        let mut state = self.state.write().expect(DOCUMENTS_POISONED);
+        let document = TextDocument::new(item, state.position_encoding);
+        state.documents.insert(document.uri().clone(), document);
+    }
+
+    pub fn change(
+        &self,
+        uri: &Url,
+        version: i32,
+        changes: &[TextDocumentContentChangeEvent],
+    ) -> Result<(), DocumentError> {
+        self.state
+            .write()
+            .expect(DOCUMENTS_POISONED)
+            .documents
+            .get_mut(uri)
+            .ok_or_else(|| DocumentError::NotOpen(uri.clone()))?
+            .apply_changes(version, changes)
+    }
+
+    pub fn close(&self, uri: &Url) {
+        self.state
+            .write()
+            .expect(DOCUMENTS_POISONED)
+            .documents
+            .remove(uri);
+    }
+}
+
+impl MessageHook for DocumentStore {
+    fn inspect_incoming(&mut self, message: &AllMessages) {
+        self.initialize_tracker.inspect_incoming(message);
+        let AllMessages::Notifications(AllNotifications::Server(notification)) = message else {
+            return;
+        };
+
+        match notification {
+            AllServerNotifications::DidOpenText(notification) => {
+                if let Some(params) = &notification.params {
+                    self.open(params.text_document.clone())
+                }
+            }
+            AllServerNotifications::DidChangeText(notification) => {
+                let Some(params) = &notification.params else {
+                    return;
+                };
+                if let Err(err) = self.change(
+                    &params.text_document.uri,
+                    params.text_document.version,
+                    &params.content_changes,
+                ) {
+                    tracing::warn!(%err, "Unable to apply document changes.")
+                }
+            }
+            AllServerNotifications::DidCloseTextDocument(notification) => {
+                if let Some(params) = &notification.params {
+                    self.close(&params.text_document.uri)
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn amend_outgoing(&mut self, message: &mut AllMessages) {
+        if let Some(capabilities) = self.initialize_tracker.capabilities_mut(message) {
+            self.set_position_encoding(announced_position_encoding(capabilities))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{
+        notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument},
+        ClientCapabilities, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+        DidOpenTextDocumentParams, TextDocumentIdentifier, VersionedTextDocumentIdentifier,
+    };
+
+    use crate::{
+        documents::text::tests::{change_mock, text_document_item_mock},
+        messages::{
+            core::notification::NotificationMessage, groups::requests::tests::initialize_mock,
+        },
+        service::initialize::tests::initialize_response_mock,
+    };
+
+    use super::*;
+
+    #[test]
+    fn tracks_documents_from_notifications() {
+        let mut store = DocumentStore::default();
+        let item = text_document_item_mock("a");
+        let uri = item.uri.clone();
+
+        store.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<DidOpenTextDocument> {
+                params: Some(DidOpenTextDocumentParams {
+                    text_document: item,
+                }),
+            }
+            .into(),
+        ));
+        let snapshot = store.get(&uri).unwrap();
+
+        store.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<DidChangeTextDocument> {
+                params: Some(DidChangeTextDocumentParams {
+                    text_document: VersionedTextDocumentIdentifier {
+                        uri: uri.clone(),
+                        version: 1,
+                    },
+                    content_changes: vec![change_mock((0, 1), (0, 1), "b")],
+                }),
+            }
+            .into(),
+        ));
+        assert_eq!("ab", store.get(&uri).unwrap().text().to_string());
+        assert_eq!("a", snapshot.text().to_string());
+
+        store.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<DidCloseTextDocument> {
+                params: Some(DidCloseTextDocumentParams {
+                    text_document: TextDocumentIdentifier { uri: uri.clone() },
+                }),
+            }
+            .into(),
+        ));
+        assert!(store.get(&uri).is_none());
+    }
+
+    #[test]
+    fn rejects_changes_to_unopened_documents() {
+        let store = DocumentStore::default();
+        let uri = Url::parse("file:///a.rs").unwrap();
+        assert_eq!(
+            Err(DocumentError::NotOpen(uri.clone())),
+            store.change(&uri, 1, &[])
+        );
+    }
+
+    #[test]
+    fn follows_announced_position_encoding() {
+        let mut store = DocumentStore::default();
+        store.inspect_incoming(&initialize_mock(ClientCapabilities::default()));
+        store.amend_outgoing(&mut initialize_response_mock(serde_json::json!({
+            "capabilities": { "positionEncoding": "utf-8" }
+        })));
+
+        let item = text_document_item_mock("a");
+        let uri = item.uri.clone();
+        store.open(item);
+        assert_eq!(
+            PositionEncoding::Utf8,
+            store.get(&uri).unwrap().position_encoding()
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use lsp_types::{Position, Range, TextDocumentContentChangeEvent, TextDocumentItem, Url};
+use ropey::Rope;
+
+use super::{
+    encoding::{char_to_position, position_to_char},
+    DocumentError, PositionEncoding,
+};
+
+/// An open text document. Cloning it is cheap, as the text is shared until either clone changes.
+#[derive(Debug, Clone)]
+pub struct TextDocument {
+    uri: Url,
+    language_id: String,
+    version: i32,
+    text: Rope,
+    position_encoding: PositionEncoding,
+}
+
+impl TextDocument {
+    /// Positions of the document, including those of its changes, are in `position_encoding`.
+    pub fn new(item: TextDocumentItem, position_encoding: PositionEncoding) -> Self {
+        Self {
+            uri: item.uri,
+            language_id: item.language_id,
+            version: item.version,
+            text: Rope::from_str(&item.text),
+            position_encoding,
+        }
+    }
+
+    pub fn uri(&self) -> &Url {
+        &self.uri
+    }
+
+    pub fn language_id(&self) -> &str {
+        &self.language_id
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn text(&self) -> &Rope {
+        &self.text
+    }
+
+    pub fn position_encoding(&self) -> PositionEncoding {
+        self.position_encoding
+    }
+
+    /// The byte offset of the position into the text.
+    pub fn offset(&self, position: Position) -> Result<usize, DocumentError> {
+        position_to_char(&self.text, position, self.position_encoding)
+            .map(|char_index| self.text.char_to_byte(char_index))
+    }
+
+    /// The position of the byte offset, rounded down to the start of its character.
+    pub fn position(&self, offset: usize) -> Result<Position, DocumentError> {
+        if offset > self.text.len_bytes() {
+            return Err(DocumentError::InvalidOffset(offset));
+        }
+        let char_index = self.text.byte_to_char(offset);
+        Ok(char_to_position(
+            &self.text,
+            char_index,
+            self.position_encoding,
+        ))
+    }
+
+    pub fn offsets(&self, range: Range) -> Result<std::ops::Range<usize>, DocumentError> {
+        Ok(self.offset(range.start)?..self.offset(range.end)?)
+    }
+
+    pub fn range(&self, offsets: std::ops::Range<usize>) -> Result<Range, DocumentError> {
+        Ok(Range::new(
+            self.position(offsets.start)?,
+            self.position(offsets.end)?,
+        ))
+    }
+
+    /// Applies the changes in order, leaving the document untouched if any of them fails or the
+    /// version doesn't succeed the current one.
+    pub fn apply_changes(
+        &mut self,
+        version: i32,
+        changes: &[TextDocumentContentChangeEvent],
+    ) -> Result<(), DocumentError> {
+        if version <= self.version {
+            return Err(DocumentError::OutOfOrderVersion {
+                current: self.version,
+                received: version,
+            });
+        }
+
+        let mut text = self.text.clone();
+        for change in changes {
+            match change.range {
+                Some(range) => {
+                    let start = position_to_char(&text, range.start, self.position_encoding)?;
+                    let end = position_to_char(&text, range.end, self.position_encoding)?;
+                    if end < start {
+                        return Err(DocumentError::InvalidRange(range));
+                    }
+                    text.remove(start..end);
+                    text.insert(start, &change.text);
+                }
+                None => text = Rope::from_str(&change.text),
+            }
+        }
+
+        self.text = text;
+        self.version = version;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub fn text_document_item_mock(text: &str) -> TextDocumentItem {
+        TextDocumentItem {
+            uri: Url::parse("file:///a.rs").unwrap(),
+            language_id: "rust".to_string(),
+            version: 0,
+            text: text.to_string(),
+        }
+    }
+
+    pub fn change_mock(
+        start: (u32, u32),
+        end: (u32, u32),
+        text: &str,
+    ) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range::new(
+                Position::new(start.0, start.1),
+                Position::new(end.0, end.1),
+            )),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn applies_incremental_changes() {
+        let mut document = TextDocument::new(
+            text_document_item_mock("fn a() {}\r\nfn b() {}\n"),
+            PositionEncoding::Utf16,
+        );
+        document
+            .apply_changes(
+                1,
+                &[
+                    change_mock((0, 3), (0, 4), "main"),
+                    change_mock((1, 0), (2, 0), ""),
+                    // Clamped to the end of the line.
+                    change_mock((0, 12), (0, 99), " // 🦀"),
+                ],
+            )
+            .unwrap();
+
+        assert_eq!("fn main() {} // 🦀\r\n", document.text().to_string());
+        assert_eq!(1, document.version());
+    }
+
+    #[test]
+    fn applies_changes_in_position_encoding() {
+        let mut document =
+            TextDocument::new(text_document_item_mock("🦀a"), PositionEncoding::Utf8);
+        document
+            .apply_changes(1, &[change_mock((0, 4), (0, 5), "b")])
+            .unwrap();
+        assert_eq!("🦀b", document.text().to_string());
+    }
+
+    #[test]
+    fn converts_byte_offsets() {
+        let document =
+            TextDocument::new(text_document_item_mock("a\n🦀b"), PositionEncoding::Utf16);
+        let range = Range::new(Position::new(1, 0), Position::new(1, 2));
+
+        assert_eq!(Ok(2..6), document.offsets(range));
+        assert_eq!(Ok(range), document.range(2..6));
+        // Within the crab.
+        assert_eq!(Ok(Position::new(1, 0)), document.position(3));
+        assert_eq!(Err(DocumentError::InvalidOffset(8)), document.position(8));
+    }
+
+    #[test]
+    fn replaces_full_text() {
+        let mut document = TextDocument::new(text_document_item_mock("a"), PositionEncoding::Utf16);
+        let change = TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "b".to_string(),
+        };
+        document.apply_changes(1, &[change]).unwrap();
+        assert_eq!("b", document.text().to_string());
+    }
+
+    #[test]
+    fn rejects_changes_atomically() {
+        let mut document =
+            TextDocument::new(text_document_item_mock("a\n"), PositionEncoding::Utf16);
+        let snapshot = document.clone();
+
+        assert_eq!(
+            Err(DocumentError::OutOfOrderVersion {
+                current: 0,
+                received: 0
+            }),
+            document.apply_changes(0, &[change_mock((0, 0), (0, 0), "b")])
+        );
+        assert_eq!(
+            Err(DocumentError::InvalidPosition(Position::new(2, 0))),
+            document.apply_changes(
+                1,
+                &[
+                    change_mock((0, 0), (0, 0), "b"),
+                    change_mock((2, 0), (2, 0), "c")
+                ]
+            )
+        );
+        assert_eq!("a\n", document.text().to_string());
+        assert_eq!(0, document.version());
+
+        document
+            .apply_changes(1, &[change_mock((1, 0), (1, 0), "b")])
+            .unwrap();
+        assert_eq!("a\n", snapshot.text().to_string());
+    }
+}
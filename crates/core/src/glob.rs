@@ -0,0 +1,321 @@
+use derive_more::Display;
+
+/// A glob pattern in the syntax of the language server protocol:
+///
+/// - `*` matches zero or more characters within a path segment.
+/// - `?` matches a single character within a path segment.
+/// - `**` matches any number of path segments, including none.
+/// - `{a,b}` matches either alternative, which may themselves contain patterns.
+/// - `[a-z]` matches a character in the range, `[!a-z]` one outside of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glob {
+    pattern: String,
+    alternatives: Vec<Vec<Token>>,
+}
+
+#[derive(Debug, Display, PartialEq)]
+pub enum GlobError {
+    #[display(fmt = "unclosed character class at {}", _0)]
+    UnclosedClass(usize),
+    #[display(fmt = "unclosed alternatives at {}", _0)]
+    UnclosedAlternatives(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(char),
+    AnyChar,
+    AnyChars,
+    /// `**` not followed by a separator, matching across segments.
+    AnyPath,
+    /// `**/`, matching no segments or any number of them.
+    AnySegments,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self, GlobError> {
+        let alternatives = expand_alternatives(pattern)?
+            .iter()
+            .map(|alternative| tokenize(alternative))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            pattern: pattern.to_string(),
+            alternatives,
+        })
+    }
+
+    /// Matches the text literally when used as part of a pattern, by putting each metacharacter
+    /// in a character class of its own.
+    pub fn escape(text: &str) -> String {
+        text.chars()
+            .map(|char| match char {
+                '*' | '?' | '[' | ']' | '{' | '}' => format!("[{}]", char),
+                char => char.to_string(),
+            })
+            .collect()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Matches the whole path, with segments separated by `/`.
+    pub fn is_match(&self, path: &str) -> bool {
+        let path = path.chars().collect::<Vec<_>>();
+        self.alternatives
+            .iter()
+            .any(|tokens| match_tokens(tokens, &path))
+    }
+}
+
+impl std::fmt::Display for Glob {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+/// Expands the outermost `{a,b}` groups into separate patterns, recursing into the alternatives.
+fn expand_alternatives(pattern: &str) -> Result<Vec<String>, GlobError> {
+    let chars = pattern.char_indices().collect::<Vec<_>>();
+    let in_class = class_mask(&chars);
+    let Some(open_position) = chars
+        .iter()
+        .enumerate()
+        .position(|(position, (_, char))| *char == '{' && !in_class[position])
+    else {
+        return Ok(vec![pattern.to_string()]);
+    };
+
+    let mut depth = 0;
+    let mut separators = Vec::new();
+    let mut close_position = None;
+    for (position, (_, char)) in chars.iter().enumerate().skip(open_position) {
+        if in_class[position] {
+            continue;
+        }
+        match char {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close_position = Some(position);
+                    break;
+                }
+            }
+            ',' if depth == 1 => separators.push(position),
+            _ => (),
+        }
+    }
+    let close_position =
+        close_position.ok_or(GlobError::UnclosedAlternatives(chars[open_position].0))?;
+
+    let byte_index = |position: usize| chars[position].0;
+    let prefix = &pattern[..byte_index(open_position)];
+    let suffix = &pattern[byte_index(close_position) + 1..];
+    let bounds = std::iter::once(open_position)
+        .chain(separators)
+        .chain(std::iter::once(close_position))
+        .collect::<Vec<_>>();
+
+    let mut expanded = Vec::new();
+    for bound in bounds.windows(2) {
+        let alternative = &pattern[byte_index(bound[0]) + 1..byte_index(bound[1])];
+        expanded.extend(expand_alternatives(&format!(
+            "{}{}{}",
+            prefix, alternative, suffix
+        ))?);
+    }
+    Ok(expanded)
+}
+
+/// Whether each of the chars is part of a `[...]` class, in which braces and commas are literal.
+fn class_mask(chars: &[(usize, char)]) -> Vec<bool> {
+    let mut in_class = vec![false; chars.len()];
+    let mut class_start = None;
+    for (position, (_, char)) in chars.iter().enumerate() {
+        match class_start {
+            None if *char == '[' => class_start = Some(position),
+            None => continue,
+            Some(start) => {
+                // A leading `]` is part of the class rather than closing it, as when tokenizing.
+                let first_position = match chars.get(start + 1) {
+                    Some((_, '!')) => start + 2,
+                    _ => start + 1,
+                };
+                if *char == ']' && position > first_position {
+                    class_start = None
+                }
+            }
+        }
+        in_class[position] = true;
+    }
+    in_class
+}
+
+fn tokenize(pattern: &str) -> Result<Vec<Token>, GlobError> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.char_indices().peekable();
+    while let Some((index, char)) = chars.next() {
+        let token = match char {
+            '?' => Token::AnyChar,
+            '*' if chars.next_if(|(_, char)| *char == '*').is_some() => {
+                match chars.next_if(|(_, char)| *char == '/') {
+                    Some(_) => Token::AnySegments,
+                    None => Token::AnyPath,
+                }
+            }
+            '*' => Token::AnyChars,
+            '[' => {
+                let negated = chars.next_if(|(_, char)| *char == '!').is_some();
+                let mut ranges = Vec::new();
+                loop {
+                    let (_, start) = chars.next().ok_or(GlobError::UnclosedClass(index))?;
+                    // A leading `]` is part of the class rather than closing it.
+                    if start == ']' && !ranges.is_empty() {
+                        break;
+                    }
+                    let end = match chars.peek() {
+                        Some((_, '-')) => {
+                            chars.next();
+                            match chars.next() {
+                                Some((_, ']')) => {
+                                    ranges.push((start, start));
+                                    ranges.push(('-', '-'));
+                                    break;
+                                }
+                                Some((_, end)) => end,
+                                None => return Err(GlobError::UnclosedClass(index)),
+                            }
+                        }
+                        _ => start,
+                    };
+                    ranges.push((start, end));
+                }
+                Token::Class { negated, ranges }
+            }
+            char => Token::Literal(char),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn match_tokens(tokens: &[Token], path: &[char]) -> bool {
+    let Some((token, remaining_tokens)) = tokens.split_first() else {
+        return path.is_empty();
+    };
+
+    match token {
+        Token::Literal(literal) => {
+            path.first() == Some(literal) && match_tokens(remaining_tokens, &path[1..])
+        }
+        Token::AnyChar => {
+            path.first().is_some_and(|char| *char != '/')
+                && match_tokens(remaining_tokens, &path[1..])
+        }
+        Token::Class { negated, ranges } => path.first().is_some_and(|char| {
+            *char != '/'
+                && ranges
+                    .iter()
+                    .any(|(start, end)| (*start..=*end).contains(char))
+                    != *negated
+                && match_tokens(remaining_tokens, &path[1..])
+        }),
+        Token::AnyChars => {
+            let segment_length = path.iter().take_while(|char| **char != '/').count();
+            (0..=segment_length).any(|length| match_tokens(remaining_tokens, &path[length..]))
+        }
+        Token::AnyPath => {
+            (0..=path.len()).any(|length| match_tokens(remaining_tokens, &path[length..]))
+        }
+        Token::AnySegments => {
+            match_tokens(remaining_tokens, path)
+                || path
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, char)| **char == '/')
+                    .any(|(index, _)| match_tokens(remaining_tokens, &path[index + 1..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_matches(pattern: &str, matching: &[&str], not_matching: &[&str]) {
+        let glob = Glob::new(pattern).unwrap();
+        for path in matching {
+            assert!(glob.is_match(path), "{} should match {}", pattern, path)
+        }
+        for path in not_matching {
+            assert!(!glob.is_match(path), "{} shouldn't match {}", pattern, path)
+        }
+    }
+
+    #[test]
+    fn matches_within_segments() {
+        assert_matches("*.rs", &["lib.rs", ".rs"], &["src/lib.rs", "lib.rsx"]);
+        assert_matches("src/?.rs", &["src/a.rs"], &["src/ab.rs", "src//.rs"]);
+    }
+
+    #[test]
+    fn matches_across_segments() {
+        assert_matches(
+            "**/*.rs",
+            &["lib.rs", "src/lib.rs", "crates/core/src/lib.rs"],
+            &["lib.toml"],
+        );
+        assert_matches(
+            "src/**/mod.rs",
+            &["src/mod.rs", "src/a/b/mod.rs"],
+            &["mod.rs"],
+        );
+        assert_matches("target/**", &["target/debug/build"], &["src/target"]);
+    }
+
+    #[test]
+    fn matches_escaped_text_literally() {
+        let escaped = Glob::escape("/a[1]/{b,c}*?");
+        assert_matches(
+            &format!("{}/*.rs", escaped),
+            &["/a[1]/{b,c}*?/lib.rs"],
+            &["/a1/b*?/lib.rs", "/a[1]/{b,c}xy/lib.rs"],
+        );
+    }
+
+    #[test]
+    fn matches_alternatives() {
+        assert_matches(
+            "**/*.{rs,toml}",
+            &["Cargo.toml", "src/lib.rs"],
+            &["README.md"],
+        );
+        assert_matches(
+            "{src,{tests,benches}/*}.rs",
+            &["src.rs", "tests/a.rs"],
+            &["tests.rs"],
+        );
+    }
+
+    #[test]
+    fn matches_character_classes() {
+        assert_matches("file[0-9].rs", &["file1.rs"], &["filea.rs", "file10.rs"]);
+        assert_matches("file[!0-9].rs", &["filea.rs"], &["file1.rs", "file/.rs"]);
+        assert_matches("[]a-].rs", &["].rs", "a.rs", "-.rs"], &["b.rs"]);
+    }
+
+    #[test]
+    fn rejects_unclosed_patterns() {
+        assert_eq!(Err(GlobError::UnclosedClass(4)), Glob::new("file[0-9.rs"));
+        assert_eq!(
+            Err(GlobError::UnclosedAlternatives(2)),
+            Glob::new("**{rs,toml")
+        );
+    }
+}
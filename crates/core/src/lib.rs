@@ -1,7 +1,12 @@
 #![allow(clippy::declare_interior_mutable_const)]
 #![allow(clippy::borrow_interior_mutable_const)]
-// TEMP:
-#![allow(dead_code)]
 
+pub mod client;
+pub mod documents;
+pub mod glob;
 pub mod messages;
+pub mod outbox;
+pub mod proxy;
 pub mod service;
+pub mod session;
+pub mod workspace;
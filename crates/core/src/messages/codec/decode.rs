@@ -22,12 +22,28 @@ impl<M: MessageGroup> Decoder for LanguageServerCodec<M> {
     type Error = DecodeError;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode_frame(src)? {
+            Some(content) => Ok(Some(serde_json::de::from_slice::<M>(&content)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<M: MessageGroup> LanguageServerCodec<M> {
+    /// Splits the content of the next frame off `src` without deserializing it, for components
+    /// forwarding frames as they arrived.
+    pub fn decode_frame(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, DecodeError> {
         match self.known_content_length {
             None => {
                 let mut headers_buffer = [httparse::EMPTY_HEADER; 2];
-                match httparse::parse_headers(src, &mut headers_buffer)? {
-                    httparse::Status::Partial => Ok(None),
-                    httparse::Status::Complete((parsed_src_index, headers)) => {
+                match httparse::parse_headers(src, &mut headers_buffer) {
+                    Err(err) => {
+                        // Nothing in the buffer can be trusted to be a frame boundary anymore.
+                        src.clear();
+                        Err(err.into())
+                    }
+                    Ok(httparse::Status::Partial) => Ok(None),
+                    Ok(httparse::Status::Complete((parsed_src_index, headers))) => {
                         match JsonRpcHeaders::try_from(headers) {
                             Ok(json_rpc_headers) => {
                                 let content_length = json_rpc_headers.content_length;
@@ -40,11 +56,14 @@ impl<M: MessageGroup> Decoder for LanguageServerCodec<M> {
                                     src.reserve(missing_capacity)
                                 }
 
-                                self.decode(src)
+                                self.decode_frame(src)
                             }
                             Err(err) => match err {
                                 HeadersParseError::MissingContentLength => Ok(None),
-                                _ => Err(DecodeError::HeadersParseError(err)),
+                                _ => {
+                                    src.advance(parsed_src_index);
+                                    Err(DecodeError::HeadersParseError(err))
+                                }
                             },
                         }
                     }
@@ -55,15 +74,11 @@ impl<M: MessageGroup> Decoder for LanguageServerCodec<M> {
                     return Ok(None);
                 }
 
-                if src.len() > content_length {
-                    // TODO: handle situation when buffer contains more than content length?
-                    unimplemented!()
-                }
-
-                let message = serde_json::de::from_slice::<M>(src)?;
-                src.clear();
+                // Consume the content before deserializing so that an invalid message doesn't
+                // leave the codec stuck on it, and so that any following messages remain intact.
+                let content = src.split_to(content_length);
                 self.known_content_length = None;
-                Ok(Some(message))
+                Ok(Some(content))
             }
         }
     }
@@ -77,7 +92,7 @@ mod tests {
         groups::{tests::MESSAGE_MOCK, AllMessages},
         payload::{
             headers::{CONTENT_TYPE_HEADER_NAME, JSON_RPC_CONTENT_TYPE},
-            tests::PAYLOAD_STR_MOCK,
+            tests::{INVALID_PAYLOAD_STR_MOCK, PAYLOAD_STR_MOCK},
         },
     };
 
@@ -132,13 +147,47 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
-    fn to_many_bytes_are_unimplemented() {
-        let mut message_bytes = BytesMut::from(
-            format!("{}\r\nsomething", JsonRpcHeaders { content_length: 1 }).as_str(),
+    fn decodes_consecutive_messages_in_one_buffer() {
+        let mut message_bytes = BytesMut::new();
+        message_bytes.put(PAYLOAD_STR_MOCK.as_bytes());
+        message_bytes.put(PAYLOAD_STR_MOCK.as_bytes());
+
+        let mut codec = LanguageServerCodec::<AllMessages>::default();
+        assert_eq!(
+            MESSAGE_MOCK,
+            codec.decode(&mut message_bytes).unwrap().unwrap()
         );
+        assert_eq!(
+            MESSAGE_MOCK,
+            codec.decode(&mut message_bytes).unwrap().unwrap()
+        );
+        assert!(message_bytes.is_empty())
+    }
+
+    #[test]
+    fn recovers_after_invalid_content() {
+        let mut message_bytes = BytesMut::from(INVALID_PAYLOAD_STR_MOCK.as_str());
+        message_bytes.put(PAYLOAD_STR_MOCK.as_bytes());
 
         let mut codec = LanguageServerCodec::<AllMessages>::default();
-        let _ = codec.decode(&mut message_bytes);
+        assert!(matches!(
+            codec.decode(&mut message_bytes),
+            Err(DecodeError::Deserialize(_))
+        ));
+        assert_eq!(
+            MESSAGE_MOCK,
+            codec.decode(&mut message_bytes).unwrap().unwrap()
+        );
+    }
+
+    #[test]
+    fn decodes_frames_regardless_of_content() {
+        let mut message_bytes = BytesMut::from(INVALID_PAYLOAD_STR_MOCK.as_str());
+
+        let mut codec = LanguageServerCodec::<AllMessages>::default();
+        assert_eq!(
+            br#"{"name":10}"#,
+            &codec.decode_frame(&mut message_bytes).unwrap().unwrap()[..]
+        );
     }
 }
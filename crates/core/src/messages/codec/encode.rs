@@ -4,7 +4,10 @@ use bytes::{BufMut, BytesMut};
 use derive_more::From;
 use tokio_util::codec::Encoder;
 
-use crate::messages::{groups::MessageGroup, payload::Payload};
+use crate::messages::{
+    groups::MessageGroup,
+    payload::{headers::JsonRpcHeaders, Payload},
+};
 
 use super::LanguageServerCodec;
 
@@ -30,6 +33,20 @@ impl<M: MessageGroup> Encoder<M> for LanguageServerCodec<M> {
     }
 }
 
+impl<M: MessageGroup> LanguageServerCodec<M> {
+    /// Frames `content` as is, the counterpart of [`LanguageServerCodec::decode_frame`].
+    pub fn encode_frame(&mut self, content: &[u8], dst: &mut BytesMut) {
+        let headers = JsonRpcHeaders {
+            content_length: content.len(),
+        }
+        .to_string();
+        dst.reserve(headers.len() + 2 + content.len());
+        dst.put(headers.as_bytes());
+        dst.put(&b"\r\n"[..]);
+        dst.put(content);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::messages::groups::{tests::MESSAGE_MOCK, AllMessages};
@@ -49,4 +66,17 @@ mod tests {
             std::str::from_utf8(&payload_buffer).unwrap()
         )
     }
+
+    #[test]
+    fn encodes_frame_as_is() {
+        let mut language_server_codec = LanguageServerCodec::<AllMessages>::default();
+        let mut payload_buffer = BytesMut::new();
+        let content = Payload::new(&MESSAGE_MOCK).body().to_owned();
+        language_server_codec.encode_frame(content.as_bytes(), &mut payload_buffer);
+
+        assert_eq!(
+            &Payload::new(&MESSAGE_MOCK).to_string(),
+            std::str::from_utf8(&payload_buffer).unwrap()
+        )
+    }
 }
@@ -0,0 +1,82 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{ready, Stream};
+
+/// Decoded frames of a [`FramedRead`](tokio_util::codec::FramedRead), ending only once its
+/// input does.
+///
+/// A framed read yields a single `None` after each decode error before going on with the
+/// following frames, which shouldn't be mistaken for the end of the input.
+pub struct Frames<S> {
+    stream: S,
+    errored: bool,
+}
+
+impl<S> Frames<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            errored: false,
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+}
+
+impl<S, T, E> Stream for Frames<S>
+where
+    S: Stream<Item = Result<T, E>> + Unpin,
+{
+    type Item = Result<T, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match ready!(Pin::new(&mut self.stream).poll_next(cx)) {
+                Some(frame) => {
+                    self.errored = frame.is_err();
+                    return Poll::Ready(Some(frame));
+                }
+                None if self.errored => self.errored = false,
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::codec::FramedRead;
+
+    use crate::messages::{
+        codec::LanguageServerCodec,
+        groups::AllMessages,
+        payload::tests::{INVALID_PAYLOAD_STR_MOCK, PAYLOAD_STR_MOCK},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn goes_on_after_decode_errors() {
+        let (mut writer, reader) = tokio::io::duplex(1024);
+        writer
+            .write_all(format!("{}{}", *INVALID_PAYLOAD_STR_MOCK, *PAYLOAD_STR_MOCK).as_bytes())
+            .await
+            .unwrap();
+        drop(writer);
+
+        let mut frames = Frames::new(FramedRead::new(
+            reader,
+            LanguageServerCodec::<AllMessages>::default(),
+        ));
+        assert!(frames.next().await.unwrap().is_err());
+        assert!(frames.next().await.unwrap().is_ok());
+        assert!(frames.next().await.is_none());
+    }
+}
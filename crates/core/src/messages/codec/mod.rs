@@ -1,8 +1,10 @@
 mod decode;
 mod encode;
+mod frames;
 
 pub use decode::DecodeError;
 pub use encode::EncodeError;
+pub use frames::Frames;
 
 use std::marker::PhantomData;
 
@@ -76,7 +76,7 @@ impl<R: Request> TryFrom<UntypedResponseMessage> for ResponseMessage<R> {
         Ok(ResponseMessage::<R> {
             id: untyped.id,
             kind: match untyped.kind {
-                Ok(value) => serde_json::from_value(value)?,
+                Ok(value) => Ok(serde_json::from_value(value)?),
                 Err(err) => Err(err),
             },
         })
@@ -109,6 +109,7 @@ impl<'de> Deserialize<'de> for UntypedResponseMessage {
             #[serde(rename = "jsonrpc")]
             _jsonrpc: Version,
             id: ResponseId,
+            #[serde(flatten)]
             kind: ResultDom,
         }
 
@@ -171,6 +172,30 @@ pub mod tests {
                 .unwrap()
         )
     }
+
+    #[test]
+    fn deserializes_error_response_message() {
+        let response = serde_json::from_value::<UntypedResponseMessage>(json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "error": { "code": -32601, "message": "Method not found" }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            ResponseMessage::<Shutdown> {
+                id: ResponseId::NumberOrString(NumberOrString::Number(0)),
+                kind: Err(ResponseError {
+                    code: response_error::ResponseErrorCode::Reserved(
+                        response_error::ReservedResponseErrorCodes::MethodNotFound
+                    ),
+                    message: "Method not found".to_string(),
+                    data: None,
+                }),
+            },
+            response.try_into().unwrap()
+        )
+    }
 }
 
 pub mod response_error {
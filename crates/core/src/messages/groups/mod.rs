@@ -2,6 +2,7 @@
 #![allow(clippy::large_enum_variant)]
 
 pub mod notifications;
+pub mod raw;
 pub mod requests;
 pub mod responses;
 
@@ -1,8 +1,24 @@
-use lsp_types::notification::*;
+use lsp_types::{notification::*, ProgressToken};
 use serde::{Deserialize, Serialize};
 
 use crate::messages::core::notification::NotificationMessage;
 
+/// `$/progress` carrying partial results, whose value depends on the request they belong to and
+/// therefore isn't covered by [`Progress`].
+#[derive(Debug)]
+pub enum PartialResultProgress {}
+
+impl Notification for PartialResultProgress {
+    type Params = PartialResultParams;
+    const METHOD: &'static str = Progress::METHOD;
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PartialResultParams {
+    pub token: ProgressToken,
+    pub value: serde_json::Value,
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum AllNotifications {
@@ -22,6 +38,7 @@ pub enum AllClientNotifications {
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
 pub enum AllServerNotifications {
     Initialized(NotificationMessage<Initialized>),
     SetTrace(NotificationMessage<SetTrace>),
@@ -49,10 +66,98 @@ pub enum AllServerNotifications {
 pub enum AllImplementationNotifications {
     CancelRequest(NotificationMessage<Cancel>),
     Progress(NotificationMessage<Progress>),
+    // Anything not deserializing as work done progress.
+    PartialResult(NotificationMessage<PartialResultProgress>),
+}
+
+impl From<AllClientNotifications> for AllNotifications {
+    fn from(client_notification: AllClientNotifications) -> Self {
+        AllNotifications::Client(client_notification)
+    }
+}
+
+impl From<AllServerNotifications> for AllNotifications {
+    fn from(server_notification: AllServerNotifications) -> Self {
+        AllNotifications::Server(server_notification)
+    }
+}
+
+impl From<AllImplementationNotifications> for AllNotifications {
+    fn from(implementation_notification: AllImplementationNotifications) -> Self {
+        AllNotifications::ImplementationDependent(implementation_notification)
+    }
+}
+
+macro_rules! impl_from_notification_message {
+    ($group:ident, $all_notifications_variant:ident, [$($variant:ident($notification:ty)),* $(,)?]) => {
+        $(
+            impl From<NotificationMessage<$notification>> for $group {
+                fn from(notification: NotificationMessage<$notification>) -> Self {
+                    $group::$variant(notification)
+                }
+            }
+
+            impl From<NotificationMessage<$notification>> for AllNotifications {
+                fn from(notification: NotificationMessage<$notification>) -> Self {
+                    AllNotifications::$all_notifications_variant($group::$variant(notification))
+                }
+            }
+        )*
+    };
 }
 
+impl_from_notification_message!(
+    AllClientNotifications,
+    Client,
+    [
+        LogTrace(LogTrace),
+        LogMessage(LogMessage),
+        PublishDiagnostics(PublishDiagnostics),
+        ShowMessage(ShowMessage),
+        Telemetry(TelemetryEvent),
+    ]
+);
+
+impl_from_notification_message!(
+    AllServerNotifications,
+    Server,
+    [
+        Initialized(Initialized),
+        SetTrace(SetTrace),
+        Exit(Exit),
+        WillSaveTextDocument(WillSaveTextDocument),
+        WorkDoneProgressCancel(WorkDoneProgressCancel),
+        DidOpenText(DidOpenTextDocument),
+        DidChangeText(DidChangeTextDocument),
+        DidSaveTextDocument(DidSaveTextDocument),
+        DidCloseTextDocument(DidCloseTextDocument),
+        DidOpenNotebook(DidOpenNotebookDocument),
+        DidChangeNotebook(DidChangeNotebookDocument),
+        DidSaveNotebookDocument(DidSaveNotebookDocument),
+        DidCloseNotebookDocument(DidCloseNotebookDocument),
+        DidChangeNotification(DidChangeConfiguration),
+        DidChangeWorkspaceFolders(DidChangeWorkspaceFolders),
+        DidCreateFiles(DidCreateFiles),
+        DidRenameFiles(DidRenameFiles),
+        DidDeleteFiles(DidDeleteFiles),
+        DidChangeWatcheFiles(DidChangeWatchedFiles),
+    ]
+);
+
+impl_from_notification_message!(
+    AllImplementationNotifications,
+    ImplementationDependent,
+    [
+        CancelRequest(Cancel),
+        Progress(Progress),
+        PartialResult(PartialResultProgress),
+    ]
+);
+
 #[cfg(test)]
 pub mod tests {
+    use serde_json::json;
+
     use super::*;
 
     #[derive(Debug, PartialEq)]
@@ -82,4 +187,18 @@ pub mod tests {
             }
         }
     }
+
+    #[test]
+    fn deserializes_server_notifications_by_method() {
+        assert_eq!(
+            AllNotifications::Server(AllServerNotifications::Exit(NotificationMessage {
+                params: None
+            })),
+            serde_json::from_value::<AllNotifications>(json!({
+                "jsonrpc": "2.0",
+                "method": "exit"
+            }))
+            .unwrap()
+        )
+    }
 }
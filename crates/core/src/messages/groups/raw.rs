@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::MessageGroup;
+
+/// Any JSON-RPC message, left undecoded for components that only route messages and shouldn't
+/// reject methods unknown to [`AllMessages`](super::AllMessages).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RawMessage(pub Value);
+
+impl RawMessage {
+    pub fn id(&self) -> Option<&Value> {
+        self.0.get("id").filter(|id| !id.is_null())
+    }
+
+    /// The id serialized as JSON, so that `1` and `"1"` are told apart when matching responses
+    /// with their requests.
+    pub fn id_key(&self) -> Option<String> {
+        self.id().map(Value::to_string)
+    }
+
+    /// The id as shown in logs, with string ids left unquoted.
+    pub fn display_id(&self) -> Option<String> {
+        self.id().map(|id| match id {
+            Value::String(id) => id.clone(),
+            id => id.to_string(),
+        })
+    }
+
+    pub fn set_id(&mut self, id: Value) {
+        if let Some(message) = self.0.as_object_mut() {
+            message.insert("id".to_owned(), id);
+        }
+    }
+
+    pub fn method(&self) -> Option<&str> {
+        self.0.get("method").and_then(Value::as_str)
+    }
+
+    pub fn params(&self) -> Option<&Value> {
+        self.0.get("params")
+    }
+
+    pub fn params_mut(&mut self) -> Option<&mut Value> {
+        self.0.get_mut("params")
+    }
+
+    pub fn is_request(&self) -> bool {
+        self.method().is_some() && self.id().is_some()
+    }
+
+    pub fn is_notification(&self) -> bool {
+        self.method().is_some() && self.id().is_none()
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.method().is_none() && (self.0.get("result").is_some() || self.0.get("error").is_some())
+    }
+
+    pub fn request(id: Value, method: &str, params: Option<Value>) -> Self {
+        let mut message = serde_json::json!({ "jsonrpc": "2.0", "id": id, "method": method });
+        if let Some(params) = params {
+            message["params"] = params;
+        }
+        Self(message)
+    }
+
+    pub fn notification(method: &str, params: Value) -> Self {
+        Self(serde_json::json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    pub fn response(id: Value, result: Result<Value, Value>) -> Self {
+        Self(match result {
+            Ok(result) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => serde_json::json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+        })
+    }
+
+    /// The response result, or its error object.
+    pub fn into_result(self) -> Result<Value, Value> {
+        let Value::Object(mut message) = self.0 else {
+            return Ok(Value::Null);
+        };
+
+        match message.remove("error") {
+            Some(error) => Err(error),
+            None => Ok(message.remove("result").unwrap_or(Value::Null)),
+        }
+    }
+}
+
+impl MessageGroup for RawMessage {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_messages() {
+        let request = RawMessage::request(1.into(), "custom/request", None);
+        assert!(request.is_request() && !request.is_notification() && !request.is_response());
+
+        let notification = RawMessage::notification("custom/notification", Value::Null);
+        assert!(notification.is_notification() && !notification.is_request());
+
+        let response = RawMessage::response(1.into(), Ok(Value::Null));
+        assert!(response.is_response() && !response.is_request());
+        assert_eq!(Ok(Value::Null), response.into_result());
+    }
+
+    #[test]
+    fn keeps_id_types_apart() {
+        let number_id_request = RawMessage::request(1.into(), "custom/request", None);
+        let string_id_request = RawMessage::request("1".into(), "custom/request", None);
+        assert_ne!(number_id_request.id_key(), string_id_request.id_key());
+    }
+
+    #[test]
+    fn displays_string_ids_unquoted() {
+        let request = RawMessage::request("a1".into(), "custom/request", None);
+        assert_eq!(Some("a1".to_owned()), request.display_id());
+    }
+}
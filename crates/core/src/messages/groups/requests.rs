@@ -171,17 +171,145 @@ impl LspRequest for AllServerRequests {
     }
 }
 
+impl From<AllClientRequests> for AllRequests {
+    fn from(client_request: AllClientRequests) -> Self {
+        AllRequests::Client(client_request)
+    }
+}
+
+impl From<AllServerRequests> for AllRequests {
+    fn from(server_request: AllServerRequests) -> Self {
+        AllRequests::Server(server_request)
+    }
+}
+
+macro_rules! impl_from_request_message {
+    ($group:ident, $all_requests_variant:ident, [$($variant:ident($request:ty)),* $(,)?]) => {
+        $(
+            impl From<RequestMessage<$request>> for $group {
+                fn from(request: RequestMessage<$request>) -> Self {
+                    $group::$variant(request)
+                }
+            }
+
+            impl From<RequestMessage<$request>> for AllRequests {
+                fn from(request: RequestMessage<$request>) -> Self {
+                    AllRequests::$all_requests_variant($group::$variant(request))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_request_message!(
+    AllClientRequests,
+    Client,
+    [
+        RegisterCapability(RegisterCapability),
+        UnregisterCapability(UnregisterCapability),
+        InlayHintRefresh(InlayHintRefreshRequest),
+        SemanticTokensRefresh(SemanticTokensRefresh),
+        InlineValueRefresh(InlineValueRefreshRequest),
+        WorkspaceDiagnosticsRefresh(WorkspaceDiagnosticRefresh),
+        Configuration(WorkspaceConfiguration),
+        WorkspaceFolders(WorkspaceFoldersRequest),
+        ApplyWorkspaceEdit(ApplyWorkspaceEdit),
+        ShowMessageRequest(ShowMessageRequest),
+        ShowDocument(ShowDocument),
+        WorkDoneProgressCreate(WorkDoneProgressCreate),
+    ]
+);
+
+impl_from_request_message!(
+    AllServerRequests,
+    Server,
+    [
+        Initialize(Initialize),
+        Shutdown(Shutdown),
+        WillSaveWaitUntilTextDocument(WillSaveWaitUntil),
+        GotoDeclaration(GotoDeclaration),
+        GotoDefinition(GotoDefinition),
+        GotoTypeDefinition(GotoTypeDefinition),
+        GotoImplementation(GotoImplementation),
+        References(References),
+        CallHierarchyPrepare(CallHierarchyPrepare),
+        CallHierarchyIncoming(CallHierarchyIncomingCalls),
+        CallHierarchyOutgoing(CallHierarchyOutgoingCalls),
+        TypeHierarchyPrepare(TypeHierarchyPrepare),
+        TypeHierarchySuper(TypeHierarchySupertypes),
+        TypeHierarchySub(TypeHierarchySubtypes),
+        DocumentHighlights(DocumentHighlightRequest),
+        DocumentLink(DocumentLinkRequest),
+        DocumentLinkResolve(DocumentLinkResolve),
+        Hover(HoverRequest),
+        CodeLens(CodeLensRequest),
+        CodeLensResolve(CodeLensResolve),
+        CodeLensRefresh(CodeLensRefresh),
+        FoldingRange(FoldingRangeRequest),
+        SelectionRange(SelectionRangeRequest),
+        DocumentSymbols(DocumentSymbolRequest),
+        SemanticTokensFull(SemanticTokensFullRequest),
+        SemanticTokensFullDelta(SemanticTokensFullDeltaRequest),
+        SemanticTokensRange(SemanticTokensRangeRequest),
+        InlayHint(InlayHintRequest),
+        InlayHindResolve(InlayHintResolveRequest),
+        InlineValue(InlineValueRequest),
+        Moniker(MonikerRequest),
+        Completion(Completion),
+        ResolveCompletionItem(ResolveCompletionItem),
+        DocumentDiagnostics(DocumentDiagnosticRequest),
+        WorkspaceDiagnostics(WorkspaceDiagnosticRequest),
+        SignatureHelp(SignatureHelpRequest),
+        CodeAction(CodeActionRequest),
+        CodeActionResolve(CodeActionResolveRequest),
+        DocumentColor(DocumentColor),
+        ColorPresentation(ColorPresentationRequest),
+        DocumentFormatting(Formatting),
+        DocumentRangeFormatting(RangeFormatting),
+        DocumentOnTypeFormatting(OnTypeFormatting),
+        Rename(Rename),
+        PrepareRename(PrepareRenameRequest),
+        LinkedEditingRange(LinkedEditingRange),
+        WorkspaceSymbols(WorkspaceSymbolRequest),
+        WorkspaceSymbolsResolve(WorkspaceSymbolResolve),
+        WillCreateFiles(WillCreateFiles),
+        WillRenameFiles(WillRenameFiles),
+        WillDeleteFiles(WillDeleteFiles),
+        ExecuteCommand(ExecuteCommand),
+    ]
+);
+
 #[cfg(test)]
 pub mod tests {
+    use lsp_types::{ClientCapabilities, InitializeParams, NumberOrString};
+
     use crate::{
         messages::{
-            core::response::UntypedResponseMessage, groups::responses::tests::SomeResponsesMock,
+            core::response::UntypedResponseMessage,
+            groups::{responses::tests::SomeResponsesMock, AllMessages},
         },
         service::filter::{tests::FilterMock, MessageFilter, ResponseTypingFn},
     };
 
     use super::*;
 
+    pub fn initialize_params_mock(params: InitializeParams) -> AllMessages {
+        AllMessages::Requests(
+            RequestMessage::<Initialize> {
+                id: NumberOrString::Number(0).into(),
+                params: Some(params),
+            }
+            .into(),
+        )
+    }
+
+    pub fn initialize_mock(capabilities: ClientCapabilities) -> AllMessages {
+        initialize_params_mock(InitializeParams {
+            capabilities,
+            ..Default::default()
+        })
+    }
+
     #[derive(Debug, PartialEq)]
     pub enum SomeRequestsMock {
         ShutDown(RequestMessage<Shutdown>),
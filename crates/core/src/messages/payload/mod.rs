@@ -21,6 +21,10 @@ impl Payload {
             body,
         }
     }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
 }
 
 impl Display for Payload {
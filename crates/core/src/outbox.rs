@@ -0,0 +1,292 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use derive_more::{Display, From};
+use futures::channel::{
+    mpsc::{UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
+use lsp_types::{notification::Notification, request::Request, NumberOrString};
+
+use crate::messages::{
+    core::{
+        notification::NotificationMessage,
+        request::{RequestId, RequestMessage},
+        response::{response_error::ResponseError, ResponseId, UntypedResponseMessage},
+    },
+    groups::{notifications::AllNotifications, requests::AllRequests, AllMessages},
+};
+
+const PENDING_RESPONSES_POISONED: &str = "pending responses lock poisoned";
+
+#[derive(Debug, Display, From)]
+pub enum RequestError {
+    #[display(fmt = "connection closed before a response was received")]
+    ConnectionClosed,
+    #[display(fmt = "error response: {}", "_0.message")]
+    Response(ResponseError),
+    Deserialize(serde_json::Error),
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct PendingResponses(
+    Arc<Mutex<HashMap<RequestId, oneshot::Sender<UntypedResponseMessage>>>>,
+);
+
+impl PendingResponses {
+    fn insert(&self, request_id: RequestId) -> oneshot::Receiver<UntypedResponseMessage> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.0
+            .lock()
+            .expect(PENDING_RESPONSES_POISONED)
+            .insert(request_id, response_tx);
+        response_rx
+    }
+
+    fn remove(&self, request_id: &RequestId) {
+        self.0
+            .lock()
+            .expect(PENDING_RESPONSES_POISONED)
+            .remove(request_id);
+    }
+
+    /// Hands the response back if it doesn't belong to any pending request.
+    pub fn resolve(&self, response: UntypedResponseMessage) -> Result<(), UntypedResponseMessage> {
+        let ResponseId::NumberOrString(request_id) = &response.id else {
+            return Err(response);
+        };
+
+        let response_tx = self
+            .0
+            .lock()
+            .expect(PENDING_RESPONSES_POISONED)
+            .remove(&RequestId::from(request_id.clone()));
+
+        match response_tx {
+            Some(response_tx) => {
+                // Requester no longer caring about the response is fine.
+                let _ = response_tx.send(response);
+                Ok(())
+            }
+            None => Err(response),
+        }
+    }
+
+    /// Lets every pending request fail with [`RequestError::ConnectionClosed`].
+    pub fn clear(&self) {
+        self.0.lock().expect(PENDING_RESPONSES_POISONED).clear()
+    }
+}
+
+/// Sends requests and notifications to the other end of a connection, matching each request up
+/// with its response.
+#[derive(Clone)]
+pub struct Outbox {
+    outgoing_tx: UnboundedSender<AllMessages>,
+    pending_responses: PendingResponses,
+    next_request_id: Arc<AtomicI32>,
+    request_id_prefix: Option<&'static str>,
+}
+
+impl Outbox {
+    /// Request ids become strings starting with `request_id_prefix` when one is given, useful
+    /// for avoiding collisions with ids chosen elsewhere.
+    pub(crate) fn new(
+        request_id_prefix: Option<&'static str>,
+    ) -> (Self, UnboundedReceiver<AllMessages>, PendingResponses) {
+        let (outgoing_tx, outgoing_rx) = futures::channel::mpsc::unbounded();
+        let (outbox, pending_responses) = Self::with_sender(outgoing_tx, request_id_prefix);
+        (outbox, outgoing_rx, pending_responses)
+    }
+
+    /// Sends over an existing channel, such as one shared with other senders.
+    pub(crate) fn with_sender(
+        outgoing_tx: UnboundedSender<AllMessages>,
+        request_id_prefix: Option<&'static str>,
+    ) -> (Self, PendingResponses) {
+        let pending_responses = PendingResponses::default();
+
+        let outbox = Self {
+            outgoing_tx,
+            pending_responses: pending_responses.clone(),
+            next_request_id: Arc::new(AtomicI32::new(0)),
+            request_id_prefix,
+        };
+
+        (outbox, pending_responses)
+    }
+
+    pub fn send_notification<N: Notification>(&self, params: Option<N::Params>)
+    where
+        NotificationMessage<N>: Into<AllNotifications>,
+    {
+        let message = AllMessages::Notifications(NotificationMessage::<N> { params }.into());
+        if self.send(message).is_err() {
+            tracing::warn!(
+                method = N::METHOD,
+                "Connection closed, dropping notification."
+            )
+        }
+    }
+
+    pub async fn send_request<R: Request>(
+        &self,
+        params: Option<R::Params>,
+    ) -> Result<R::Result, RequestError>
+    where
+        RequestMessage<R>: Into<AllRequests>,
+    {
+        let request_id = self.next_request_id();
+        let response_rx = self.pending_responses.insert(request_id.clone());
+        let message = AllMessages::Requests(
+            RequestMessage::<R> {
+                id: request_id.clone(),
+                params,
+            }
+            .into(),
+        );
+        if let Err(err) = self.send(message) {
+            self.pending_responses.remove(&request_id);
+            return Err(err);
+        }
+
+        let response = response_rx
+            .await
+            .map_err(|_| RequestError::ConnectionClosed)?;
+
+        match response.kind {
+            Ok(value) => Ok(serde_json::from_value(value)?),
+            Err(response_error) => Err(response_error.into()),
+        }
+    }
+
+    /// Fails with [`RequestError::ConnectionClosed`] once the connection is no longer running.
+    pub fn send(&self, message: AllMessages) -> Result<(), RequestError> {
+        self.outgoing_tx
+            .unbounded_send(message)
+            .map_err(|_| RequestError::ConnectionClosed)
+    }
+
+    fn next_request_id(&self) -> RequestId {
+        let request_number = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        match self.request_id_prefix {
+            Some(prefix) => NumberOrString::String(format!("{}{}", prefix, request_number)),
+            None => NumberOrString::Number(request_number),
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{FutureExt, StreamExt};
+    use lsp_types::{notification::Exit, request::Shutdown};
+
+    use crate::messages::{
+        core::response::{
+            response_error::{ReservedResponseErrorCodes, ResponseErrorCode},
+            tests::SHUTDOWN_RESPONSE_MOCK,
+        },
+        groups::tests::MESSAGE_MOCK,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn resolves_request_with_response() {
+        let (outbox, mut outgoing_rx, pending_responses) = Outbox::new(None);
+
+        let request_task = tokio::spawn(async move { outbox.send_request::<Shutdown>(None).await });
+        assert_eq!(MESSAGE_MOCK, outgoing_rx.next().await.unwrap());
+        pending_responses
+            .resolve(SHUTDOWN_RESPONSE_MOCK.into())
+            .unwrap();
+
+        request_task.await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn fails_request_with_error_response() {
+        let (outbox, mut outgoing_rx, pending_responses) = Outbox::new(None);
+
+        let request_task = tokio::spawn(async move { outbox.send_request::<Shutdown>(None).await });
+        outgoing_rx.next().await.unwrap();
+        pending_responses
+            .resolve(UntypedResponseMessage {
+                id: SHUTDOWN_RESPONSE_MOCK.id,
+                kind: Err(ResponseError {
+                    code: ResponseErrorCode::Reserved(ReservedResponseErrorCodes::RequestFailed),
+                    message: "failed".to_owned(),
+                    data: None,
+                }),
+            })
+            .unwrap();
+
+        assert!(matches!(
+            request_task.await.unwrap(),
+            Err(RequestError::Response(_))
+        ))
+    }
+
+    #[tokio::test]
+    async fn fails_pending_requests_when_cleared() {
+        let (outbox, mut outgoing_rx, pending_responses) = Outbox::new(None);
+
+        let request_task = tokio::spawn(async move { outbox.send_request::<Shutdown>(None).await });
+        outgoing_rx.next().await.unwrap();
+        pending_responses.clear();
+
+        assert!(matches!(
+            request_task.await.unwrap(),
+            Err(RequestError::ConnectionClosed)
+        ))
+    }
+
+    #[tokio::test]
+    async fn fails_requests_sent_after_connection_closed() {
+        let (outbox, outgoing_rx, pending_responses) = Outbox::new(None);
+        drop(outgoing_rx);
+        pending_responses.clear();
+
+        assert!(matches!(
+            outbox.send_request::<Shutdown>(None).await,
+            Err(RequestError::ConnectionClosed)
+        ));
+        assert!(pending_responses
+            .0
+            .lock()
+            .expect(PENDING_RESPONSES_POISONED)
+            .is_empty());
+    }
+
+    #[test]
+    fn returns_unknown_responses() {
+        let (_outbox, _outgoing_rx, pending_responses) = Outbox::new(None);
+        assert!(pending_responses
+            .resolve(SHUTDOWN_RESPONSE_MOCK.into())
+            .is_err())
+    }
+
+    #[test]
+    fn prefixes_request_ids() {
+        let (outbox, mut outgoing_rx, _pending_responses) = Outbox::new(Some("prefix-"));
+        outbox.send_notification::<Exit>(None);
+        assert!(outbox
+            .send_request::<Shutdown>(None)
+            .now_or_never()
+            .is_none());
+
+        let _exit_notification = outgoing_rx.try_next().unwrap().unwrap();
+        assert_eq!(
+            Some(&RequestId::from(NumberOrString::String(
+                "prefix-0".to_owned()
+            ))),
+            outgoing_rx.try_next().unwrap().unwrap().request_id()
+        );
+    }
+}
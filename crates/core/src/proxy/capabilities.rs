@@ -0,0 +1,260 @@
+use serde_json::{Map, Value};
+
+const TEXT_DOCUMENT_SYNC_KEY: &str = "textDocumentSync";
+const POSITION_ENCODING_KEY: &str = "positionEncoding";
+const UTF16_ENCODING: &str = "utf-16";
+/// `TextDocumentSyncKind.None`.
+const NO_SYNC: u64 = 0;
+
+/// Combines the `ServerCapabilities` of every proxied server into those advertised to the editor.
+///
+/// Objects are merged key by key, lists such as trigger characters are united and boolean flags
+/// are enabled if any server enables them. Scalars that can't be combined are taken from the
+/// first server declaring them, except for those applying to every server alike: changes are
+/// synced in the least detailed kind any server asks for, since a server can always handle the
+/// full content of a document, and positions are only announced in an encoding the servers agree
+/// on.
+pub(super) fn merge_capabilities(capabilities: &[Value]) -> Value {
+    let mut merged = capabilities
+        .iter()
+        .fold(Value::Object(Map::new()), |merged, capabilities| {
+            merge_values(merged, capabilities)
+        });
+
+    if let Some(merged) = merged.as_object_mut() {
+        merge_sync_kinds(merged, capabilities);
+        merge_position_encodings(merged, capabilities);
+    }
+    merged
+}
+
+/// Leaves the servers to the UTF-16 positions every one of them supports, as positions are
+/// forwarded to them unconverted.
+pub(super) fn restrict_position_encodings(initialize_params: &mut Value) {
+    if let Some(general) = initialize_params
+        .pointer_mut("/capabilities/general")
+        .and_then(Value::as_object_mut)
+    {
+        general.remove("positionEncodings");
+    }
+}
+
+fn merge_sync_kinds(merged: &mut Map<String, Value>, capabilities: &[Value]) {
+    let Some(sync_kind) = capabilities
+        .iter()
+        .filter_map(
+            |capabilities| match capabilities.get(TEXT_DOCUMENT_SYNC_KEY)? {
+                Value::Number(sync_kind) => sync_kind.as_u64(),
+                sync_options => sync_options.get("change")?.as_u64(),
+            },
+        )
+        .filter(|sync_kind| *sync_kind != NO_SYNC)
+        .min()
+    else {
+        return;
+    };
+
+    match merged.get_mut(TEXT_DOCUMENT_SYNC_KEY) {
+        Some(Value::Object(sync_options)) => {
+            sync_options.insert("change".to_owned(), sync_kind.into());
+        }
+        Some(merged_sync_kind) => *merged_sync_kind = sync_kind.into(),
+        None => (),
+    }
+}
+
+fn merge_position_encodings(merged: &mut Map<String, Value>, capabilities: &[Value]) {
+    let mut encodings = capabilities
+        .iter()
+        .filter(|capabilities| !capabilities.is_null())
+        .map(|capabilities| {
+            capabilities
+                .get(POSITION_ENCODING_KEY)
+                .and_then(Value::as_str)
+                .unwrap_or(UTF16_ENCODING)
+        });
+
+    let Some(first_encoding) = encodings.next() else {
+        return;
+    };
+    if encodings.any(|encoding| encoding != first_encoding) {
+        tracing::warn!("Proxied servers disagree on the position encoding, announcing UTF-16.");
+        merged.remove(POSITION_ENCODING_KEY);
+    }
+}
+
+fn merge_values(merged: Value, other: &Value) -> Value {
+    match (merged, other) {
+        (Value::Object(mut merged), Value::Object(other)) => {
+            for (key, other_value) in other {
+                let merged_value = match merged.remove(key) {
+                    Some(merged_value) => merge_values(merged_value, other_value),
+                    None => other_value.clone(),
+                };
+                merged.insert(key.clone(), merged_value);
+            }
+            Value::Object(merged)
+        }
+        (Value::Array(mut merged), Value::Array(other)) => {
+            for other_value in other {
+                if !merged.contains(other_value) {
+                    merged.push(other_value.clone())
+                }
+            }
+            Value::Array(merged)
+        }
+        // Options objects imply support, and so take precedence over a plain `true`.
+        (Value::Bool(true), other @ Value::Object(_)) => other.clone(),
+        (Value::Bool(false) | Value::Null, other) => other.clone(),
+        (merged, _) => merged,
+    }
+}
+
+/// Server capability that has to be advertised for a request to be routed to the server, as a
+/// JSON pointer. Requests without one are assumed to be supported by every server.
+fn capability_pointer(method: &str) -> Option<&'static str> {
+    Some(match method {
+        "completionItem/resolve" => "/completionProvider/resolveProvider",
+        "codeAction/resolve" => "/codeActionProvider/resolveProvider",
+        "codeLens/resolve" => "/codeLensProvider/resolveProvider",
+        "documentLink/resolve" => "/documentLinkProvider/resolveProvider",
+        "inlayHint/resolve" => "/inlayHintProvider/resolveProvider",
+        "workspaceSymbol/resolve" => "/workspaceSymbolProvider/resolveProvider",
+        "textDocument/completion" => "/completionProvider",
+        "textDocument/hover" => "/hoverProvider",
+        "textDocument/signatureHelp" => "/signatureHelpProvider",
+        "textDocument/declaration" => "/declarationProvider",
+        "textDocument/definition" => "/definitionProvider",
+        "textDocument/typeDefinition" => "/typeDefinitionProvider",
+        "textDocument/implementation" => "/implementationProvider",
+        "textDocument/references" => "/referencesProvider",
+        "textDocument/documentHighlight" => "/documentHighlightProvider",
+        "textDocument/documentSymbol" => "/documentSymbolProvider",
+        "textDocument/codeAction" => "/codeActionProvider",
+        "textDocument/codeLens" => "/codeLensProvider",
+        "textDocument/documentLink" => "/documentLinkProvider",
+        "textDocument/documentColor" | "textDocument/colorPresentation" => "/colorProvider",
+        "textDocument/formatting" => "/documentFormattingProvider",
+        "textDocument/rangeFormatting" => "/documentRangeFormattingProvider",
+        "textDocument/onTypeFormatting" => "/documentOnTypeFormattingProvider",
+        "textDocument/rename" | "textDocument/prepareRename" => "/renameProvider",
+        "textDocument/foldingRange" => "/foldingRangeProvider",
+        "textDocument/selectionRange" => "/selectionRangeProvider",
+        "textDocument/linkedEditingRange" => "/linkedEditingRangeProvider",
+        "textDocument/moniker" => "/monikerProvider",
+        "textDocument/inlineValue" => "/inlineValueProvider",
+        "textDocument/inlayHint" => "/inlayHintProvider",
+        "textDocument/diagnostic" | "workspace/diagnostic" => "/diagnosticProvider",
+        "textDocument/semanticTokens/full"
+        | "textDocument/semanticTokens/full/delta"
+        | "textDocument/semanticTokens/range" => "/semanticTokensProvider",
+        "textDocument/prepareCallHierarchy"
+        | "callHierarchy/incomingCalls"
+        | "callHierarchy/outgoingCalls" => "/callHierarchyProvider",
+        "textDocument/prepareTypeHierarchy"
+        | "typeHierarchy/supertypes"
+        | "typeHierarchy/subtypes" => "/typeHierarchyProvider",
+        "workspace/symbol" => "/workspaceSymbolProvider",
+        "workspace/executeCommand" => "/executeCommandProvider",
+        _ => return None,
+    })
+}
+
+pub(super) fn supports(capabilities: &Value, method: &str) -> bool {
+    match capability_pointer(method) {
+        Some(pointer) => !matches!(
+            capabilities.pointer(pointer),
+            None | Some(Value::Null) | Some(Value::Bool(false))
+        ),
+        None => true,
+    }
+}
+
+pub(super) fn supports_command(capabilities: &Value, command: &str) -> bool {
+    capabilities
+        .pointer("/executeCommandProvider/commands")
+        .and_then(Value::as_array)
+        .is_some_and(|commands| commands.iter().any(|known| known == command))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn merges_capabilities() {
+        let language_server = json!({
+            "textDocumentSync": 2,
+            "hoverProvider": true,
+            "completionProvider": { "triggerCharacters": ["."] },
+            "codeActionProvider": true,
+        });
+        let linter = json!({
+            "textDocumentSync": 1,
+            "completionProvider": { "triggerCharacters": [".", ":"], "resolveProvider": true },
+            "codeActionProvider": { "codeActionKinds": ["quickfix"] },
+            "diagnosticProvider": { "interFileDependencies": false },
+        });
+
+        assert_eq!(
+            json!({
+                "textDocumentSync": 1,
+                "hoverProvider": true,
+                "completionProvider": { "triggerCharacters": [".", ":"], "resolveProvider": true },
+                "codeActionProvider": { "codeActionKinds": ["quickfix"] },
+                "diagnosticProvider": { "interFileDependencies": false },
+            }),
+            merge_capabilities(&[language_server, linter])
+        )
+    }
+
+    #[test]
+    fn syncs_least_detailed_change_kind() {
+        let incremental = json!({ "textDocumentSync": { "openClose": true, "change": 2 } });
+        let full = json!({ "textDocumentSync": 1 });
+        let unsynced = json!({ "textDocumentSync": 0 });
+
+        assert_eq!(
+            json!({ "textDocumentSync": { "openClose": true, "change": 1 } }),
+            merge_capabilities(&[incremental.clone(), full])
+        );
+        assert_eq!(
+            json!({ "textDocumentSync": 2 }),
+            merge_capabilities(&[unsynced, json!({ "textDocumentSync": 2 })])
+        );
+    }
+
+    #[test]
+    fn announces_agreed_position_encodings_only() {
+        let utf8 = json!({ "positionEncoding": "utf-8" });
+        assert_eq!(utf8, merge_capabilities(&[utf8.clone(), utf8.clone()]));
+        assert_eq!(json!({}), merge_capabilities(&[utf8, json!({})]));
+    }
+
+    #[test]
+    fn restricts_position_encodings() {
+        let mut initialize_params = json!({
+            "capabilities": { "general": { "positionEncodings": ["utf-8", "utf-16"] } }
+        });
+        restrict_position_encodings(&mut initialize_params);
+        assert_eq!(
+            json!({ "capabilities": { "general": {} } }),
+            initialize_params
+        );
+    }
+
+    #[test]
+    fn checks_support_by_capability() {
+        let capabilities = json!({ "hoverProvider": true, "renameProvider": false });
+        assert!(supports(&capabilities, "textDocument/hover"));
+        assert!(!supports(&capabilities, "textDocument/rename"));
+        assert!(!supports(&capabilities, "textDocument/completion"));
+        assert!(supports(&capabilities, "custom/request"));
+
+        let capabilities = json!({ "completionProvider": {} });
+        assert!(supports(&capabilities, "textDocument/completion"));
+        assert!(!supports(&capabilities, "completionItem/resolve"));
+    }
+}
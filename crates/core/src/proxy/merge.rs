@@ -0,0 +1,280 @@
+use serde_json::{Map, Value};
+
+const ORIGIN_KEY: &str = "spiqueProxyServer";
+
+/// Requests sent to every supporting server, their results are merged into one.
+pub(super) fn is_merged(method: &str) -> bool {
+    matches!(
+        method,
+        "textDocument/completion"
+            | "textDocument/diagnostic"
+            | "textDocument/codeAction"
+            | "textDocument/codeLens"
+            | "textDocument/documentLink"
+            | "textDocument/documentColor"
+            | "textDocument/documentHighlight"
+            | "textDocument/references"
+            | "textDocument/foldingRange"
+            | "textDocument/inlayHint"
+            | "textDocument/prepareCallHierarchy"
+            | "textDocument/prepareTypeHierarchy"
+            | "workspace/symbol"
+    )
+}
+
+/// Requests with items whose follow up requests must reach the server that produced them.
+fn produces_items(method: &str) -> bool {
+    matches!(
+        method,
+        "textDocument/completion"
+            | "textDocument/codeAction"
+            | "textDocument/codeLens"
+            | "textDocument/documentLink"
+            | "textDocument/inlayHint"
+            | "textDocument/prepareCallHierarchy"
+            | "textDocument/prepareTypeHierarchy"
+            | "workspace/symbol"
+    )
+}
+
+/// Follow up requests taking a previously produced item as their params.
+pub(super) fn resolves_item(method: &str) -> bool {
+    matches!(
+        method,
+        "completionItem/resolve"
+            | "codeAction/resolve"
+            | "codeLens/resolve"
+            | "documentLink/resolve"
+            | "inlayHint/resolve"
+            | "workspaceSymbol/resolve"
+    )
+}
+
+/// Follow up requests taking a previously produced item in `params.item`.
+pub(super) fn references_item(method: &str) -> bool {
+    matches!(
+        method,
+        "callHierarchy/incomingCalls"
+            | "callHierarchy/outgoingCalls"
+            | "typeHierarchy/supertypes"
+            | "typeHierarchy/subtypes"
+    )
+}
+
+/// Records the originating server in the item `data`, which clients send back unchanged in
+/// follow up requests.
+fn tag_origin(item: &mut Value, server_index: usize) {
+    let Value::Object(item) = item else {
+        return;
+    };
+
+    let mut tag = Map::new();
+    tag.insert(ORIGIN_KEY.to_owned(), server_index.into());
+    if let Some(data) = item.remove("data") {
+        tag.insert("data".to_owned(), data);
+    }
+    item.insert("data".to_owned(), Value::Object(tag));
+}
+
+/// Restores the item `data` to what the server originally sent, returning the server index.
+pub(super) fn untag_origin(item: &mut Value) -> Option<usize> {
+    let item = item.as_object_mut()?;
+    let tag = item.get_mut("data")?.as_object_mut()?;
+    let server_index = tag.get(ORIGIN_KEY)?.as_u64()? as usize;
+
+    match tag.remove("data") {
+        Some(data) => item.insert("data".to_owned(), data),
+        None => item.remove("data"),
+    };
+
+    Some(server_index)
+}
+
+pub(super) fn tag_result(method: &str, result: &mut Value, server_index: usize) {
+    if resolves_item(method) {
+        return tag_origin(result, server_index);
+    }
+
+    if !produces_items(method) {
+        return;
+    }
+
+    let items = match result {
+        Value::Array(items) => items,
+        Value::Object(completion_list) => match completion_list.get_mut("items") {
+            Some(Value::Array(items)) => items,
+            _ => return,
+        },
+        _ => return,
+    };
+
+    for item in items {
+        tag_origin(item, server_index)
+    }
+}
+
+/// Merges the successful results of a request, ordered by server.
+pub(super) fn merge_results(method: &str, results: Vec<Value>) -> Value {
+    let mut results = results.into_iter().filter(|result| !result.is_null());
+
+    match method {
+        "textDocument/completion" => merge_completions(results),
+        "textDocument/diagnostic" => merge_diagnostic_reports(results),
+        method if is_merged(method) => {
+            let items = results
+                .flat_map(|result| match result {
+                    Value::Array(items) => items,
+                    _ => Vec::new(),
+                })
+                .collect::<Vec<_>>();
+            match items.is_empty() {
+                true => Value::Null,
+                false => Value::Array(items),
+            }
+        }
+        _ => results.next().unwrap_or(Value::Null),
+    }
+}
+
+fn merge_completions(results: impl Iterator<Item = Value>) -> Value {
+    let mut is_incomplete = false;
+    let mut items = Vec::new();
+    let mut any_result = false;
+
+    for result in results {
+        any_result = true;
+        match result {
+            Value::Array(completion_items) => items.extend(completion_items),
+            Value::Object(mut completion_list) => {
+                is_incomplete |= completion_list
+                    .get("isIncomplete")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
+                if let Some(Value::Array(completion_items)) = completion_list.remove("items") {
+                    items.extend(completion_items)
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match any_result {
+        true => serde_json::json!({ "isIncomplete": is_incomplete, "items": items }),
+        false => Value::Null,
+    }
+}
+
+/// Result ids are dropped from the merged report since they are per server, making the editor
+/// always receive full reports.
+fn merge_diagnostic_reports(results: impl Iterator<Item = Value>) -> Value {
+    let items = results
+        .flat_map(
+            |mut report| match report.get_mut("items").map(Value::take) {
+                Some(Value::Array(items)) => items,
+                _ => Vec::new(),
+            },
+        )
+        .collect::<Vec<_>>();
+
+    serde_json::json!({ "kind": "full", "items": items })
+}
+
+/// Latest `textDocument/publishDiagnostics` of each server, so that one server publishing
+/// doesn't clear the diagnostics of another.
+pub(super) struct PublishedDiagnostics {
+    server_count: usize,
+    by_uri: std::collections::HashMap<String, Vec<Vec<Value>>>,
+}
+
+impl PublishedDiagnostics {
+    pub fn new(server_count: usize) -> Self {
+        Self {
+            server_count,
+            by_uri: Default::default(),
+        }
+    }
+
+    /// Replaces the diagnostics in the params with those of every server.
+    pub fn merge(&mut self, server_index: usize, params: &mut Value) {
+        let Some(uri) = params.get("uri").and_then(Value::as_str) else {
+            return;
+        };
+
+        let server_diagnostics = self
+            .by_uri
+            .entry(uri.to_owned())
+            .or_insert_with(|| vec![Vec::new(); self.server_count]);
+
+        server_diagnostics[server_index] = match params.get_mut("diagnostics").map(Value::take) {
+            Some(Value::Array(diagnostics)) => diagnostics,
+            _ => Vec::new(),
+        };
+
+        params["diagnostics"] = Value::Array(server_diagnostics.concat());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn merges_completion_results() {
+        let merged = merge_results(
+            "textDocument/completion",
+            vec![
+                json!([{ "label": "first" }]),
+                Value::Null,
+                json!({ "isIncomplete": true, "items": [{ "label": "second" }] }),
+            ],
+        );
+
+        assert_eq!(
+            json!({ "isIncomplete": true, "items": [{ "label": "first" }, { "label": "second" }] }),
+            merged
+        )
+    }
+
+    #[test]
+    fn takes_first_result_of_unmerged_requests() {
+        assert_eq!(
+            json!({ "contents": "second" }),
+            merge_results(
+                "textDocument/hover",
+                vec![Value::Null, json!({ "contents": "second" })]
+            )
+        )
+    }
+
+    #[test]
+    fn round_trips_origin_tags() {
+        let mut result = json!([{ "title": "fix", "data": 7 }, { "title": "refactor" }]);
+        tag_result("textDocument/codeAction", &mut result, 1);
+
+        let mut with_data = result[0].take();
+        assert_eq!(Some(1), untag_origin(&mut with_data));
+        assert_eq!(json!({ "title": "fix", "data": 7 }), with_data);
+
+        let mut without_data = result[1].take();
+        assert_eq!(Some(1), untag_origin(&mut without_data));
+        assert_eq!(json!({ "title": "refactor" }), without_data);
+    }
+
+    #[test]
+    fn keeps_diagnostics_of_every_server() {
+        let mut published_diagnostics = PublishedDiagnostics::new(2);
+        let mut publish = |server_index, message: &str| {
+            let mut params = json!({ "uri": "file:///a", "diagnostics": [{ "message": message }] });
+            published_diagnostics.merge(server_index, &mut params);
+            params["diagnostics"].clone()
+        };
+
+        publish(0, "type error");
+        assert_eq!(
+            json!([{ "message": "type error" }, { "message": "typo" }]),
+            publish(1, "typo")
+        )
+    }
+}
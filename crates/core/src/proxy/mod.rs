@@ -0,0 +1,341 @@
+mod capabilities;
+mod merge;
+mod router;
+
+use std::process::Stdio;
+
+use futures::{channel::mpsc::UnboundedSender, StreamExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    process::{Child, Command},
+};
+use tokio_util::codec::FramedRead;
+
+use crate::messages::{
+    codec::{Frames, LanguageServerCodec},
+    groups::raw::RawMessage,
+};
+
+use self::router::Router;
+
+type ServerInput = Box<dyn AsyncWrite + Unpin + Send>;
+type ServerOutput = Box<dyn AsyncRead + Unpin + Send>;
+
+enum ServerEvent {
+    Message(usize, RawMessage),
+    Closed(usize),
+}
+
+/// Presents several language servers to the editor as one.
+///
+/// `initialize` and `shutdown` reach every server, and the advertised capabilities are merged.
+/// Other requests go to the servers which advertised support for them, list-like results such
+/// as completions and code actions are gathered from all of them while the first server answers
+/// the rest. Notifications are broadcast.
+pub struct LspProxy<I: AsyncRead + Unpin, O: AsyncWrite + Unpin> {
+    editor_input: I,
+    editor_output: O,
+    servers: Vec<(ServerOutput, ServerInput)>,
+    children: Vec<Child>,
+}
+
+impl<I: AsyncRead + Unpin, O: AsyncWrite + Unpin> LspProxy<I, O> {
+    pub fn new(editor_input: I, editor_output: O) -> Self {
+        Self {
+            editor_input,
+            editor_output,
+            servers: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Servers are prioritized in the order they are added.
+    pub fn with_server(
+        mut self,
+        server_output: impl AsyncRead + Unpin + Send + 'static,
+        server_input: impl AsyncWrite + Unpin + Send + 'static,
+    ) -> Self {
+        self.servers
+            .push((Box::new(server_output), Box::new(server_input)));
+        self
+    }
+
+    /// Spawns the server with piped stdio, it is awaited once the proxy stops running.
+    pub fn with_spawned_server(mut self, command: &mut Command) -> std::io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let server_input = child.stdin.take().expect("stdin should be piped");
+        let server_output = child.stdout.take().expect("stdout should be piped");
+        self.children.push(child);
+
+        Ok(self.with_server(server_output, server_input))
+    }
+
+    /// Runs until the editor closes its output or every server has closed theirs.
+    pub async fn run(self) {
+        let (server_event_tx, mut server_event_rx) = futures::channel::mpsc::unbounded();
+        let mut server_inputs = Vec::with_capacity(self.servers.len());
+        for (server_index, (server_output, server_input)) in self.servers.into_iter().enumerate() {
+            tokio::spawn(read_server(
+                server_index,
+                server_output,
+                server_event_tx.clone(),
+            ));
+            server_inputs.push(server_input);
+        }
+        drop(server_event_tx);
+
+        let mut editor_input = Frames::new(FramedRead::new(
+            self.editor_input,
+            LanguageServerCodec::<RawMessage>::default(),
+        ));
+        let mut router = Router::new(self.editor_output, server_inputs);
+
+        loop {
+            tokio::select! {
+                decode_attempt = editor_input.next() => match decode_attempt {
+                    Some(Ok(message)) => router.handle_editor_message(message).await,
+                    Some(Err(err)) => tracing::warn!(%err, "Unable to decode editor message."),
+                    None => break,
+                },
+                server_event = server_event_rx.next() => match server_event {
+                    Some(ServerEvent::Message(server_index, message)) => {
+                        router.handle_server_message(server_index, message).await
+                    }
+                    Some(ServerEvent::Closed(server_index)) => {
+                        router.handle_server_closed(server_index).await
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        // Closes the server inputs.
+        drop(router);
+        for mut child in self.children {
+            if let Err(err) = child.wait().await {
+                tracing::error!(?err, "Unable to await proxied server.")
+            }
+        }
+    }
+}
+
+async fn read_server(
+    server_index: usize,
+    server_output: ServerOutput,
+    server_event_tx: UnboundedSender<ServerEvent>,
+) {
+    let mut framed_output = Frames::new(FramedRead::new(
+        server_output,
+        LanguageServerCodec::<RawMessage>::default(),
+    ));
+
+    while let Some(decode_attempt) = framed_output.next().await {
+        match decode_attempt {
+            Ok(message) => {
+                if server_event_tx
+                    .unbounded_send(ServerEvent::Message(server_index, message))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+            Err(err) => {
+                tracing::warn!(server_index, %err, "Unable to decode proxied server message.")
+            }
+        }
+    }
+
+    let _ = server_event_tx.unbounded_send(ServerEvent::Closed(server_index));
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::SinkExt;
+    use serde_json::{json, Value};
+    use tokio::io::DuplexStream;
+    use tokio_util::codec::FramedWrite;
+
+    use super::*;
+
+    type Editor = (
+        FramedRead<DuplexStream, LanguageServerCodec<RawMessage>>,
+        FramedWrite<DuplexStream, LanguageServerCodec<RawMessage>>,
+    );
+
+    /// Answers every request with `respond(method, params)` until its input closes.
+    fn server_mock(
+        respond: impl Fn(&str, Option<&Value>) -> Value + Send + 'static,
+    ) -> (DuplexStream, DuplexStream) {
+        let (server_input, proxy_output) = tokio::io::duplex(4096);
+        let (proxy_input, server_output) = tokio::io::duplex(4096);
+
+        tokio::spawn(async move {
+            let mut framed_input =
+                FramedRead::new(server_input, LanguageServerCodec::<RawMessage>::default());
+            let mut framed_output =
+                FramedWrite::new(server_output, LanguageServerCodec::<RawMessage>::default());
+
+            while let Some(Ok(message)) = framed_input.next().await {
+                if let (true, Some(id)) = (message.is_request(), message.id()) {
+                    let result = respond(message.method().unwrap(), message.params());
+                    let response = RawMessage::response(id.clone(), Ok(result));
+                    framed_output.send(response).await.unwrap()
+                }
+            }
+        });
+
+        (proxy_input, proxy_output)
+    }
+
+    fn spawn_proxy(servers: Vec<(DuplexStream, DuplexStream)>) -> Editor {
+        let (proxy_input, editor_output) = tokio::io::duplex(4096);
+        let (editor_input, proxy_output) = tokio::io::duplex(4096);
+
+        let proxy = servers.into_iter().fold(
+            LspProxy::new(proxy_input, proxy_output),
+            |proxy, (server_output, server_input)| proxy.with_server(server_output, server_input),
+        );
+        tokio::spawn(proxy.run());
+
+        (
+            FramedRead::new(editor_input, LanguageServerCodec::default()),
+            FramedWrite::new(editor_output, LanguageServerCodec::default()),
+        )
+    }
+
+    async fn request(editor: &mut Editor, id: i64, method: &str, params: Value) -> Value {
+        let request = RawMessage::request(id.into(), method, Some(params));
+        editor.1.send(request).await.unwrap();
+
+        let response = editor.0.next().await.unwrap().unwrap();
+        assert_eq!(Some(&Value::from(id)), response.id());
+        response.into_result().unwrap()
+    }
+
+    fn language_server_mock(method: &str, params: Option<&Value>) -> Value {
+        match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "hoverProvider": true,
+                    "completionProvider": { "resolveProvider": true },
+                },
+                "serverInfo": { "name": "language-server" },
+            }),
+            "textDocument/hover" => json!({ "contents": "hover" }),
+            "textDocument/completion" => json!([{ "label": "variable", "data": 1 }]),
+            "completionItem/resolve" => {
+                let mut item = params.unwrap().clone();
+                item["detail"] = json!(format!("resolved with data {}", item["data"]));
+                item
+            }
+            _ => Value::Null,
+        }
+    }
+
+    fn spell_checker_mock(method: &str, _params: Option<&Value>) -> Value {
+        match method {
+            "initialize" => json!({
+                "capabilities": { "completionProvider": { "triggerCharacters": ["#"] } },
+                "serverInfo": { "name": "spell-checker" },
+            }),
+            "textDocument/completion" => json!({
+                "isIncomplete": true,
+                "items": [{ "label": "spelling", "data": 2 }],
+            }),
+            "textDocument/hover" => panic!("hover should not be routed to the spell checker"),
+            _ => Value::Null,
+        }
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn combines_servers() {
+        let mut editor = spawn_proxy(vec![
+            server_mock(language_server_mock),
+            server_mock(spell_checker_mock),
+        ]);
+
+        let initialize_result = request(&mut editor, 1, "initialize", json!({})).await;
+        assert_eq!(
+            json!({
+                "capabilities": {
+                    "hoverProvider": true,
+                    "completionProvider": { "resolveProvider": true, "triggerCharacters": ["#"] },
+                },
+                "serverInfo": { "name": "language-server + spell-checker" },
+            }),
+            initialize_result
+        );
+
+        let hover = request(&mut editor, 2, "textDocument/hover", json!({})).await;
+        assert_eq!(json!({ "contents": "hover" }), hover);
+
+        let completion = request(&mut editor, 3, "textDocument/completion", json!({})).await;
+        assert_eq!(Some(true), completion["isIncomplete"].as_bool());
+        let labels = completion["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["variable", "spelling"], labels);
+
+        let completion_item = completion["items"][0].clone();
+        let resolved = request(&mut editor, 4, "completionItem/resolve", completion_item).await;
+        assert_eq!(json!("resolved with data 1"), resolved["detail"]);
+
+        // The spell checker doesn't resolve its items.
+        let completion_item = completion["items"][1].clone();
+        let resolved = request(&mut editor, 5, "completionItem/resolve", completion_item).await;
+        assert_eq!(json!({ "label": "spelling", "data": 2 }), resolved);
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn fails_requests_to_unwritable_servers() {
+        let (server_input, proxy_output) = tokio::io::duplex(4096);
+        let (proxy_input, server_output) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let mut framed_input =
+                FramedRead::new(server_input, LanguageServerCodec::<RawMessage>::default());
+            let mut framed_output =
+                FramedWrite::new(server_output, LanguageServerCodec::<RawMessage>::default());
+
+            let initialize = framed_input.next().await.unwrap().unwrap();
+            // Stops reading while leaving the output open.
+            drop(framed_input);
+            let result = language_server_mock("initialize", None);
+            let response = RawMessage::response(initialize.id().unwrap().clone(), Ok(result));
+            framed_output.send(response).await.unwrap();
+            std::future::pending::<()>().await
+        });
+
+        let mut editor = spawn_proxy(vec![(proxy_input, proxy_output)]);
+        request(&mut editor, 1, "initialize", json!({})).await;
+
+        let request = RawMessage::request(2.into(), "textDocument/hover", Some(json!({})));
+        editor.1.send(request).await.unwrap();
+        let response = editor.0.next().await.unwrap().unwrap();
+        assert_eq!(Some(&Value::from(2)), response.id());
+        assert!(response.into_result().is_err())
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn rejects_unsupported_requests() {
+        let mut editor = spawn_proxy(vec![server_mock(spell_checker_mock)]);
+        request(&mut editor, 1, "initialize", json!({})).await;
+
+        let request = RawMessage::request(2.into(), "textDocument/hover", Some(json!({})));
+        editor.1.send(request).await.unwrap();
+        assert!(editor
+            .0
+            .next()
+            .await
+            .unwrap()
+            .unwrap()
+            .into_result()
+            .is_err())
+    }
+}
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+
+use futures::SinkExt;
+use serde_json::Value;
+use tokio::io::AsyncWrite;
+use tokio_util::codec::FramedWrite;
+
+use crate::messages::{
+    codec::LanguageServerCodec,
+    core::response::response_error::{
+        ReservedResponseErrorCodes, ResponseError, ResponseErrorCode,
+    },
+    groups::raw::RawMessage,
+};
+
+use super::{
+    capabilities::{merge_capabilities, restrict_position_encodings, supports, supports_command},
+    merge::{
+        is_merged, merge_results, references_item, resolves_item, tag_result, untag_origin,
+        PublishedDiagnostics,
+    },
+    ServerInput,
+};
+
+/// Editor request awaiting the responses of the servers it was forwarded to.
+struct EditorRequest {
+    id: Value,
+    method: String,
+    forwarded_ids: Vec<(usize, i64)>,
+    results: Vec<(usize, Value)>,
+    error: Option<Value>,
+}
+
+/// Routes messages between the editor and the servers, rewriting request ids so that those
+/// chosen by different senders never collide.
+pub(super) struct Router<O: AsyncWrite + Unpin> {
+    editor_output: FramedWrite<O, LanguageServerCodec<RawMessage>>,
+    server_inputs: Vec<Option<FramedWrite<ServerInput, LanguageServerCodec<RawMessage>>>>,
+    server_capabilities: Vec<Value>,
+    next_id: i64,
+    editor_requests: HashMap<i64, EditorRequest>,
+    /// Id sent to the server, mapped to the server index and the editor request key.
+    forwarded_requests: HashMap<i64, (usize, i64)>,
+    /// Id sent to the editor, mapped to the server index and its original request id.
+    server_requests: HashMap<i64, (usize, Value)>,
+    published_diagnostics: PublishedDiagnostics,
+}
+
+impl<O: AsyncWrite + Unpin> Router<O> {
+    pub fn new(editor_output: O, server_inputs: Vec<ServerInput>) -> Self {
+        let server_count = server_inputs.len();
+        Self {
+            editor_output: FramedWrite::new(editor_output, LanguageServerCodec::default()),
+            server_inputs: server_inputs
+                .into_iter()
+                .map(|server_input| {
+                    Some(FramedWrite::new(
+                        server_input,
+                        LanguageServerCodec::default(),
+                    ))
+                })
+                .collect(),
+            server_capabilities: vec![Value::Null; server_count],
+            next_id: 0,
+            editor_requests: HashMap::new(),
+            forwarded_requests: HashMap::new(),
+            server_requests: HashMap::new(),
+            published_diagnostics: PublishedDiagnostics::new(server_count),
+        }
+    }
+
+    pub async fn handle_editor_message(&mut self, message: RawMessage) {
+        if message.is_request() {
+            self.route_editor_request(message).await
+        } else if message.is_response() {
+            self.route_editor_response(message).await
+        } else if message.method() == Some("$/cancelRequest") {
+            self.cancel_editor_request(message).await
+        } else if message.is_notification() {
+            for server_index in self.live_servers().collect::<Vec<_>>() {
+                self.write_server(server_index, message.clone()).await
+            }
+        } else {
+            tracing::warn!(?message, "Dropping invalid editor message.")
+        }
+    }
+
+    pub async fn handle_server_message(&mut self, server_index: usize, mut message: RawMessage) {
+        if message.is_response() {
+            self.handle_server_response(server_index, message).await
+        } else if message.is_request() {
+            let editor_id = self.next_id();
+            let original_id = message.id().cloned().unwrap_or_default();
+            self.server_requests
+                .insert(editor_id, (server_index, original_id));
+            message.set_id(editor_id.into());
+            self.write_editor(message).await
+        } else if message.method() == Some("textDocument/publishDiagnostics") {
+            if let Some(params) = message.params_mut() {
+                self.published_diagnostics.merge(server_index, params)
+            }
+            self.write_editor(message).await
+        } else if message.is_notification() {
+            self.write_editor(message).await
+        } else {
+            tracing::warn!(server_index, ?message, "Dropping invalid server message.")
+        }
+    }
+
+    pub async fn handle_server_closed(&mut self, server_index: usize) {
+        tracing::warn!(server_index, "Proxied server closed its output.");
+        self.abandon_server(server_index).await
+    }
+
+    /// Stops routing to the server, failing the requests still awaiting a response from it.
+    async fn abandon_server(&mut self, server_index: usize) {
+        self.server_inputs[server_index] = None;
+
+        let abandoned_ids = self
+            .forwarded_requests
+            .iter()
+            .filter(|(_, (forwarded_to, _))| *forwarded_to == server_index)
+            .map(|(forwarded_id, _)| *forwarded_id)
+            .collect::<Vec<_>>();
+
+        for forwarded_id in abandoned_ids {
+            let error = response_error(
+                ReservedResponseErrorCodes::RequestFailed,
+                "language server exited",
+            );
+            self.record_result(forwarded_id, Err(error)).await
+        }
+    }
+
+    async fn route_editor_request(&mut self, mut message: RawMessage) {
+        let method = message.method().unwrap_or_default().to_owned();
+        let editor_id = message.id().cloned().unwrap_or_default();
+
+        if method == "initialize" && self.server_inputs.len() > 1 {
+            if let Some(params) = message.params_mut() {
+                restrict_position_encodings(params)
+            }
+        }
+
+        let targets = match method.as_str() {
+            "initialize" | "shutdown" => self.live_servers().collect(),
+            method if resolves_item(method) => match message.params_mut().and_then(untag_origin) {
+                Some(server_index) if supports(&self.server_capabilities[server_index], method) => {
+                    vec![server_index]
+                }
+                // Hands the item back as is, since the server it came from can't resolve it.
+                Some(_) => {
+                    let item = message.params().cloned().unwrap_or_default();
+                    return self
+                        .write_editor(RawMessage::response(editor_id, Ok(item)))
+                        .await;
+                }
+                None => self.supporting_servers(method).take(1).collect(),
+            },
+            method if references_item(method) => message
+                .params_mut()
+                .and_then(|params| params.get_mut("item"))
+                .and_then(untag_origin)
+                .map(|server_index| vec![server_index])
+                .unwrap_or_else(|| self.supporting_servers(method).take(1).collect()),
+            "workspace/executeCommand" => {
+                let command = message
+                    .params()
+                    .and_then(|params| params.get("command"))
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                self.live_servers()
+                    .find(|server_index| {
+                        supports_command(&self.server_capabilities[*server_index], command)
+                    })
+                    .into_iter()
+                    .collect()
+            }
+            method if is_merged(method) => self.supporting_servers(method).collect(),
+            method => self.supporting_servers(method).take(1).collect::<Vec<_>>(),
+        };
+
+        if targets.is_empty() {
+            let error = response_error(
+                ReservedResponseErrorCodes::MethodNotFound,
+                "no proxied server supports the request",
+            );
+            return self
+                .write_editor(RawMessage::response(editor_id, Err(error)))
+                .await;
+        }
+
+        let request_key = self.next_id();
+        let forwarded_ids = targets
+            .into_iter()
+            .map(|server_index| (server_index, self.next_id()))
+            .collect::<Vec<_>>();
+        for (server_index, forwarded_id) in &forwarded_ids {
+            self.forwarded_requests
+                .insert(*forwarded_id, (*server_index, request_key));
+        }
+        // Tracked before writing, for failed writes to be answered.
+        self.editor_requests.insert(
+            request_key,
+            EditorRequest {
+                id: editor_id,
+                method,
+                forwarded_ids: forwarded_ids.clone(),
+                results: Vec::new(),
+                error: None,
+            },
+        );
+
+        for (server_index, forwarded_id) in forwarded_ids {
+            let mut forwarded_message = message.clone();
+            forwarded_message.set_id(forwarded_id.into());
+            self.write_server(server_index, forwarded_message).await
+        }
+    }
+
+    async fn route_editor_response(&mut self, mut message: RawMessage) {
+        let Some((server_index, original_id)) = message
+            .id()
+            .and_then(Value::as_i64)
+            .and_then(|editor_id| self.server_requests.remove(&editor_id))
+        else {
+            return tracing::warn!(?message, "Received editor response to unknown request.");
+        };
+
+        message.set_id(original_id);
+        self.write_server(server_index, message).await
+    }
+
+    async fn cancel_editor_request(&mut self, message: RawMessage) {
+        let Some(cancelled_id) = message.params().and_then(|params| params.get("id")) else {
+            return;
+        };
+
+        let Some(editor_request) = self
+            .editor_requests
+            .values()
+            .find(|editor_request| &editor_request.id == cancelled_id)
+        else {
+            return;
+        };
+
+        let cancellations = editor_request
+            .forwarded_ids
+            .iter()
+            .map(|(server_index, forwarded_id)| {
+                let params = serde_json::json!({ "id": forwarded_id });
+                (
+                    *server_index,
+                    RawMessage::notification("$/cancelRequest", params),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for (server_index, cancellation) in cancellations {
+            self.write_server(server_index, cancellation).await
+        }
+    }
+
+    async fn handle_server_response(&mut self, server_index: usize, message: RawMessage) {
+        let forwarded_id = message.id().and_then(Value::as_i64);
+        match forwarded_id.and_then(|forwarded_id| self.forwarded_requests.get(&forwarded_id)) {
+            Some((forwarded_to, _)) if *forwarded_to == server_index => {
+                let forwarded_id = forwarded_id.expect("forwarded request id should be known");
+                self.record_result(forwarded_id, message.into_result())
+                    .await
+            }
+            _ => tracing::warn!(
+                server_index,
+                ?message,
+                "Received response to unknown request."
+            ),
+        }
+    }
+
+    async fn record_result(&mut self, forwarded_id: i64, result: Result<Value, Value>) {
+        let Some((server_index, request_key)) = self.forwarded_requests.remove(&forwarded_id)
+        else {
+            return;
+        };
+        let Some(editor_request) = self.editor_requests.get_mut(&request_key) else {
+            return;
+        };
+
+        match result {
+            Ok(result) => editor_request.results.push((server_index, result)),
+            Err(error) => {
+                editor_request.error.get_or_insert(error);
+            }
+        }
+
+        if self
+            .forwarded_requests
+            .values()
+            .any(|(_, pending_key)| *pending_key == request_key)
+        {
+            return;
+        }
+
+        let editor_request = self
+            .editor_requests
+            .remove(&request_key)
+            .expect("editor request should be pending");
+        self.respond_to_editor(editor_request).await
+    }
+
+    async fn respond_to_editor(&mut self, mut editor_request: EditorRequest) {
+        if editor_request.results.is_empty() {
+            let error = editor_request.error.unwrap_or(Value::Null);
+            return self
+                .write_editor(RawMessage::response(editor_request.id, Err(error)))
+                .await;
+        }
+
+        editor_request
+            .results
+            .sort_by_key(|(server_index, _)| *server_index);
+
+        let result = match editor_request.method.as_str() {
+            "initialize" => self.merge_initialize_results(editor_request.results),
+            method => merge_results(
+                method,
+                editor_request
+                    .results
+                    .into_iter()
+                    .map(|(server_index, mut result)| {
+                        tag_result(method, &mut result, server_index);
+                        result
+                    })
+                    .collect(),
+            ),
+        };
+
+        self.write_editor(RawMessage::response(editor_request.id, Ok(result)))
+            .await
+    }
+
+    fn merge_initialize_results(&mut self, results: Vec<(usize, Value)>) -> Value {
+        let mut server_names = Vec::new();
+        for (server_index, mut result) in results {
+            if let Some(server_name) = result.pointer("/serverInfo/name").and_then(Value::as_str) {
+                server_names.push(server_name.to_owned());
+            }
+            self.server_capabilities[server_index] = result["capabilities"].take();
+        }
+
+        serde_json::json!({
+            "capabilities": merge_capabilities(&self.server_capabilities),
+            "serverInfo": { "name": server_names.join(" + ") },
+        })
+    }
+
+    fn live_servers(&self) -> impl Iterator<Item = usize> + '_ {
+        self.server_inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, server_input)| server_input.is_some())
+            .map(|(server_index, _)| server_index)
+    }
+
+    fn supporting_servers<'a>(&'a self, method: &'a str) -> impl Iterator<Item = usize> + 'a {
+        self.live_servers()
+            .filter(move |server_index| supports(&self.server_capabilities[*server_index], method))
+    }
+
+    fn next_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    async fn write_editor(&mut self, message: RawMessage) {
+        if let Err(err) = self.editor_output.send(message).await {
+            tracing::error!(?err, "Unable to write to editor.")
+        }
+    }
+
+    async fn write_server(&mut self, server_index: usize, message: RawMessage) {
+        let Some(server_input) = &mut self.server_inputs[server_index] else {
+            return;
+        };
+
+        if let Err(err) = server_input.send(message).await {
+            tracing::error!(server_index, ?err, "Unable to write to proxied server.");
+            self.abandon_server(server_index).await
+        }
+    }
+}
+
+fn response_error(code: ReservedResponseErrorCodes, message: &str) -> Value {
+    serde_json::to_value(ResponseError {
+        code: ResponseErrorCode::Reserved(code),
+        message: message.to_owned(),
+        data: None,
+    })
+    .expect("response error should serialize")
+}
@@ -1,4 +1,7 @@
-use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
+use futures::{
+    channel::mpsc::{UnboundedReceiver, UnboundedSender},
+    StreamExt,
+};
 
 use crate::service::error::BACKEND_INPUT_CLOSED;
 
@@ -7,13 +10,13 @@ use super::{
     filter::{IncomingMessage, MessageFilter, OutgoingMessage},
 };
 
-pub(crate) struct ServiceBackend<F: MessageFilter> {
+pub struct ServiceBackend<F: MessageFilter> {
     backend_rx: UnboundedReceiver<IncomingMessage<F>>,
     backend_tx: UnboundedSender<OutgoingMessage<F>>,
 }
 
 impl<F: MessageFilter> ServiceBackend<F> {
-    pub fn new(
+    pub(crate) fn new(
         backend_rx: UnboundedReceiver<IncomingMessage<F>>,
         backend_tx: UnboundedSender<OutgoingMessage<F>>,
     ) -> Self {
@@ -30,6 +33,12 @@ impl<F: MessageFilter> ServiceBackend<F> {
         }
     }
 
+    /// Waits for the next incoming message, `None` is returned once the service input has been
+    /// closed and all remaining messages have been received.
+    pub async fn next_incoming(&mut self) -> Option<IncomingMessage<F>> {
+        self.backend_rx.next().await
+    }
+
     pub fn send_outgoing(&self, message: OutgoingMessage<F>) {
         self.backend_tx
             .unbounded_send(message)
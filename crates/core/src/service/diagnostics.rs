@@ -0,0 +1,448 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use lsp_types::{notification::PublishDiagnostics, Diagnostic, PublishDiagnosticsParams, Url};
+
+use crate::{
+    messages::groups::{
+        notifications::{AllNotifications, AllServerNotifications},
+        requests::{AllRequests, AllServerRequests},
+        AllMessages,
+    },
+    outbox::Outbox,
+};
+
+use super::filter::MessageHook;
+
+const DIAGNOSTICS_POISONED: &str = "diagnostics lock poisoned";
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(200);
+
+struct DiagnosticsState {
+    debounce: Duration,
+    /// Versions of the documents open in the client.
+    versions: HashMap<Url, i32>,
+    documents: HashMap<Url, DocumentDiagnostics>,
+}
+
+#[derive(Default)]
+struct DocumentDiagnostics {
+    sources: BTreeMap<String, SourceDiagnostics>,
+    /// Bumped on each update, letting only the last of the scheduled publications through.
+    generation: u64,
+}
+
+struct SourceDiagnostics {
+    /// Document version the diagnostics were computed against, if known.
+    version: Option<i32>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Publishes the diagnostics of a [`Service`](super::Service), merging those of every source
+/// for a document into a single `textDocument/publishDiagnostics` notification.
+///
+/// Publication is debounced, so that a burst of updates results in one notification. It carries
+/// the document version the diagnostics were computed against, leaving out those of sources that
+/// have fallen behind the document in the meantime. Diagnostics are cleared when their document
+/// is closed and on shutdown.
+///
+/// Debounced publications are spawned as Tokio tasks, so [`update`](Self::update) and
+/// [`clear`](Self::clear) panic when called outside of a Tokio runtime unless the debounce is
+/// zero.
+#[derive(Clone)]
+pub struct DiagnosticsPublisher {
+    outbox: Outbox,
+    state: Arc<Mutex<DiagnosticsState>>,
+}
+
+impl DiagnosticsPublisher {
+    pub(crate) fn new(outbox: Outbox) -> Self {
+        Self {
+            outbox,
+            state: Arc::new(Mutex::new(DiagnosticsState {
+                debounce: DEFAULT_DEBOUNCE,
+                versions: HashMap::new(),
+                documents: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Applies to updates made afterwards. Updates are published right away when zero.
+    pub fn set_debounce(&self, debounce: Duration) {
+        self.state.lock().expect(DIAGNOSTICS_POISONED).debounce = debounce
+    }
+
+    /// Replaces the diagnostics of the source for the document, filling in `Diagnostic.source`
+    /// when left out.
+    ///
+    /// Diagnostics computed against a `version` older than the one currently open, or against
+    /// a document closed since, are dropped as stale. Returns whether they were kept.
+    ///
+    /// Must be called from within a Tokio runtime, see the type docs.
+    pub fn update(
+        &self,
+        uri: Url,
+        source: &str,
+        version: Option<i32>,
+        mut diagnostics: Vec<Diagnostic>,
+    ) -> bool {
+        let mut state = self.state.lock().expect(DIAGNOSTICS_POISONED);
+        if let Some(version) = version {
+            if state.versions.get(&uri) != Some(&version) {
+                tracing::debug!(%uri, version, source, "Dropping stale diagnostics.");
+                return false;
+            }
+        }
+
+        diagnostics
+            .iter_mut()
+            .filter(|diagnostic| diagnostic.source.is_none())
+            .for_each(|diagnostic| diagnostic.source = Some(source.to_string()));
+        let document = state.documents.entry(uri.clone()).or_default();
+        document.sources.insert(
+            source.to_string(),
+            SourceDiagnostics {
+                version,
+                diagnostics,
+            },
+        );
+        document.generation += 1;
+        let generation = document.generation;
+        let debounce = state.debounce;
+        drop(state);
+
+        self.schedule(uri, generation, debounce);
+        true
+    }
+
+    /// Removes the diagnostics of the source for the document. Must be called from within a
+    /// Tokio runtime, see the type docs.
+    pub fn clear(&self, uri: Url, source: &str) {
+        let mut state = self.state.lock().expect(DIAGNOSTICS_POISONED);
+        let Some(document) = state.documents.get_mut(&uri) else {
+            return;
+        };
+        if document.sources.remove(source).is_none() {
+            return;
+        }
+        document.generation += 1;
+        let generation = document.generation;
+        let debounce = state.debounce;
+        drop(state);
+
+        self.schedule(uri, generation, debounce);
+    }
+
+    /// Removes the diagnostics of every source for the document, right away.
+    pub fn clear_all(&self, uri: &Url) {
+        let removed = self
+            .state
+            .lock()
+            .expect(DIAGNOSTICS_POISONED)
+            .documents
+            .remove(uri);
+
+        if removed.is_some() {
+            self.send(uri.clone(), Vec::new(), None)
+        }
+    }
+
+    fn schedule(&self, uri: Url, generation: u64, debounce: Duration) {
+        if debounce.is_zero() {
+            self.publish(uri, generation);
+            return;
+        }
+
+        let publisher = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(debounce).await;
+            publisher.publish(uri, generation)
+        });
+    }
+
+    /// Publishes unless superseded by a later update, dropping sources computed against an
+    /// older version than the one currently open.
+    fn publish(&self, uri: Url, generation: u64) {
+        let mut state = self.state.lock().expect(DIAGNOSTICS_POISONED);
+        let current_version = state.versions.get(&uri).copied();
+        let Some(document) = state.documents.get_mut(&uri) else {
+            return;
+        };
+        if document.generation != generation {
+            return;
+        }
+
+        document.sources.retain(|source, source_diagnostics| {
+            let stale = source_diagnostics
+                .version
+                .is_some_and(|version| Some(version) != current_version);
+            if stale {
+                tracing::debug!(%uri, source, "Dropping stale diagnostics.");
+            }
+            !stale
+        });
+        let version = document
+            .sources
+            .values()
+            .find_map(|source_diagnostics| source_diagnostics.version);
+        let diagnostics = document
+            .sources
+            .values()
+            .flat_map(|source_diagnostics| source_diagnostics.diagnostics.iter().cloned())
+            .collect();
+        drop(state);
+        self.send(uri, diagnostics, version)
+    }
+
+    fn send(&self, uri: Url, diagnostics: Vec<Diagnostic>, version: Option<i32>) {
+        self.outbox
+            .send_notification::<PublishDiagnostics>(Some(PublishDiagnosticsParams {
+                uri,
+                diagnostics,
+                version,
+            }))
+    }
+}
+
+impl MessageHook for DiagnosticsPublisher {
+    fn inspect_incoming(&mut self, message: &AllMessages) {
+        match message {
+            AllMessages::Notifications(AllNotifications::Server(notification)) => {
+                match notification {
+                    AllServerNotifications::DidOpenText(notification) => {
+                        if let Some(params) = &notification.params {
+                            let text_document = &params.text_document;
+                            self.state
+                                .lock()
+                                .expect(DIAGNOSTICS_POISONED)
+                                .versions
+                                .insert(text_document.uri.clone(), text_document.version);
+                        }
+                    }
+                    AllServerNotifications::DidChangeText(notification) => {
+                        if let Some(params) = &notification.params {
+                            let text_document = &params.text_document;
+                            self.state
+                                .lock()
+                                .expect(DIAGNOSTICS_POISONED)
+                                .versions
+                                .insert(text_document.uri.clone(), text_document.version);
+                        }
+                    }
+                    AllServerNotifications::DidCloseTextDocument(notification) => {
+                        if let Some(params) = &notification.params {
+                            let uri = &params.text_document.uri;
+                            self.state
+                                .lock()
+                                .expect(DIAGNOSTICS_POISONED)
+                                .versions
+                                .remove(uri);
+                            self.clear_all(uri)
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            AllMessages::Requests(AllRequests::Server(AllServerRequests::Shutdown(_))) => {
+                let uris = self
+                    .state
+                    .lock()
+                    .expect(DIAGNOSTICS_POISONED)
+                    .documents
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                uris.iter().for_each(|uri| self.clear_all(uri))
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use futures::channel::mpsc::UnboundedReceiver;
+    use lsp_types::{
+        notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument},
+        request::Shutdown,
+        DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+        NumberOrString, Position, Range, TextDocumentIdentifier, TextDocumentItem,
+        VersionedTextDocumentIdentifier,
+    };
+
+    use crate::messages::{
+        core::{notification::NotificationMessage, request::RequestMessage},
+        groups::notifications::AllClientNotifications,
+    };
+
+    use super::*;
+
+    pub(crate) fn diagnostic_mock(message: &str) -> Diagnostic {
+        Diagnostic {
+            range: Range::new(Position::new(0, 0), Position::new(0, 1)),
+            message: message.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn publisher_mock(
+        debounce: Duration,
+    ) -> (DiagnosticsPublisher, UnboundedReceiver<AllMessages>, Url) {
+        let (outbox, outgoing_rx, _pending_responses) = Outbox::new(None);
+        let mut publisher = DiagnosticsPublisher::new(outbox);
+        publisher.set_debounce(debounce);
+
+        let uri = Url::parse("file:///a.rs").unwrap();
+        publisher.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<DidOpenTextDocument> {
+                params: Some(DidOpenTextDocumentParams {
+                    text_document: TextDocumentItem::new(
+                        uri.clone(),
+                        "rust".to_string(),
+                        0,
+                        "a".to_string(),
+                    ),
+                }),
+            }
+            .into(),
+        ));
+        (publisher, outgoing_rx, uri)
+    }
+
+    fn next_published(
+        outgoing_rx: &mut UnboundedReceiver<AllMessages>,
+    ) -> PublishDiagnosticsParams {
+        let AllMessages::Notifications(AllNotifications::Client(
+            AllClientNotifications::PublishDiagnostics(notification),
+        )) = outgoing_rx.try_next().unwrap().unwrap()
+        else {
+            panic!("expected published diagnostics")
+        };
+        notification.params.unwrap()
+    }
+
+    #[test]
+    fn merges_sources_with_document_version() {
+        let (publisher, mut outgoing_rx, uri) = publisher_mock(Duration::ZERO);
+        assert!(publisher.update(uri.clone(), "b", Some(0), vec![diagnostic_mock("b")]));
+        assert!(publisher.update(uri.clone(), "a", None, vec![diagnostic_mock("a")]));
+
+        next_published(&mut outgoing_rx);
+        let published = next_published(&mut outgoing_rx);
+        assert_eq!(Some(0), published.version);
+        assert_eq!(
+            vec![Some("a".to_string()), Some("b".to_string())],
+            published
+                .diagnostics
+                .into_iter()
+                .map(|diagnostic| diagnostic.source)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn drops_stale_diagnostics() {
+        let (mut publisher, mut outgoing_rx, uri) = publisher_mock(Duration::ZERO);
+        publisher.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<DidChangeTextDocument> {
+                params: Some(DidChangeTextDocumentParams {
+                    text_document: VersionedTextDocumentIdentifier {
+                        uri: uri.clone(),
+                        version: 1,
+                    },
+                    content_changes: Vec::new(),
+                }),
+            }
+            .into(),
+        ));
+
+        assert!(!publisher.update(uri.clone(), "a", Some(0), vec![diagnostic_mock("a")]));
+        assert!(outgoing_rx.try_next().is_err());
+        assert!(publisher.update(uri, "a", Some(1), vec![diagnostic_mock("a")]));
+        assert_eq!(Some(1), next_published(&mut outgoing_rx).version);
+    }
+
+    #[tokio::test]
+    async fn publishes_computed_version() {
+        let (mut publisher, mut outgoing_rx, uri) = publisher_mock(Duration::from_millis(10));
+        assert!(publisher.update(uri.clone(), "a", Some(0), vec![diagnostic_mock("a")]));
+        assert!(publisher.update(uri.clone(), "b", None, vec![diagnostic_mock("b")]));
+        publisher.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<DidChangeTextDocument> {
+                params: Some(DidChangeTextDocumentParams {
+                    text_document: VersionedTextDocumentIdentifier {
+                        uri: uri.clone(),
+                        version: 1,
+                    },
+                    content_changes: Vec::new(),
+                }),
+            }
+            .into(),
+        ));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let published = next_published(&mut outgoing_rx);
+        assert_eq!(None, published.version);
+        assert_eq!(
+            vec!["b".to_string()],
+            published
+                .diagnostics
+                .into_iter()
+                .map(|diagnostic| diagnostic.message)
+                .collect::<Vec<_>>()
+        );
+
+        assert!(publisher.update(uri, "a", Some(1), vec![diagnostic_mock("a")]));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(Some(1), next_published(&mut outgoing_rx).version);
+    }
+
+    #[tokio::test]
+    async fn debounces_publication() {
+        let (publisher, mut outgoing_rx, uri) = publisher_mock(Duration::from_millis(10));
+        publisher.update(uri.clone(), "a", None, vec![diagnostic_mock("1")]);
+        publisher.update(uri.clone(), "a", None, vec![diagnostic_mock("2")]);
+        assert!(outgoing_rx.try_next().is_err());
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let published = next_published(&mut outgoing_rx);
+        assert_eq!("2", published.diagnostics[0].message);
+        assert!(outgoing_rx.try_next().is_err());
+    }
+
+    #[test]
+    fn clears_on_close_and_shutdown() {
+        let (mut publisher, mut outgoing_rx, uri) = publisher_mock(Duration::ZERO);
+        let other_uri = Url::parse("file:///b.rs").unwrap();
+        publisher.update(uri.clone(), "a", None, vec![diagnostic_mock("a")]);
+        publisher.update(other_uri.clone(), "a", None, vec![diagnostic_mock("a")]);
+        next_published(&mut outgoing_rx);
+        next_published(&mut outgoing_rx);
+
+        publisher.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<DidCloseTextDocument> {
+                params: Some(DidCloseTextDocumentParams {
+                    text_document: TextDocumentIdentifier { uri: uri.clone() },
+                }),
+            }
+            .into(),
+        ));
+        let cleared = next_published(&mut outgoing_rx);
+        assert_eq!((uri.clone(), None), (cleared.uri, cleared.version));
+        assert!(cleared.diagnostics.is_empty());
+        assert!(!publisher.update(uri, "a", Some(0), vec![diagnostic_mock("a")]));
+
+        publisher.inspect_incoming(&AllMessages::Requests(
+            RequestMessage::<Shutdown> {
+                id: NumberOrString::Number(0).into(),
+                params: None,
+            }
+            .into(),
+        ));
+        let cleared = next_published(&mut outgoing_rx);
+        assert_eq!(other_uri, cleared.uri);
+        assert!(cleared.diagnostics.is_empty());
+    }
+}
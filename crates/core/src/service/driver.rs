@@ -0,0 +1,285 @@
+use std::{collections::VecDeque, future::Future, pin::Pin, time::Duration};
+
+use derive_more::{Display, From};
+use futures::StreamExt;
+use lsp_types::{notification::Notification, request::Request, NumberOrString};
+use tokio::io::{AsyncWriteExt, DuplexStream};
+use tokio_util::codec::FramedRead;
+
+use crate::messages::{
+    codec::{DecodeError, LanguageServerCodec},
+    core::{
+        notification::NotificationMessage,
+        request::{RequestId, RequestMessage},
+        response::{ResponseId, ResponseMessage},
+    },
+    groups::{notifications::AllNotifications, requests::AllRequests, AllMessages},
+    payload::Payload,
+};
+
+use super::{
+    backend::ServiceBackend,
+    error::INPUT_CLOSED,
+    filter::{IncomingMessage, MessageFilter, OutgoingMessage, ServiceMessageFilter},
+    frontend::ServiceFrontend,
+    server::Service,
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const TICK_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Display, From)]
+pub enum DriverError {
+    #[display(fmt = "timed out waiting for service output")]
+    Timeout,
+    #[display(fmt = "service output closed")]
+    OutputClosed,
+    Decode(DecodeError),
+    Deserialize(serde_json::Error),
+}
+
+enum Pipeline<F: MessageFilter> {
+    /// Stepped with [`ServiceDriver::tick`], the backend end is operated by the test.
+    Manual {
+        frontend: Box<ServiceFrontend<DuplexStream, DuplexStream>>,
+        message_filter: ServiceMessageFilter<F>,
+        backend: ServiceBackend<F>,
+    },
+    /// Polled whenever the driver awaits output, `None` once the service has finished.
+    Running(Option<Pin<Box<dyn Future<Output = ()>>>>),
+}
+
+/// Acts as the client of a [`Service`] in tests.
+///
+/// Output messages passed over while awaiting a specific response or notification are kept, and
+/// returned in order by [`ServiceDriver::next_output_message`].
+pub struct ServiceDriver<F: MessageFilter> {
+    pipeline: Pipeline<F>,
+    input_handle: DuplexStream,
+    framed_output: FramedRead<DuplexStream, LanguageServerCodec<AllMessages>>,
+    passed_over: VecDeque<AllMessages>,
+    next_request_id: i32,
+    timeout: Duration,
+}
+
+impl<F: MessageFilter> ServiceDriver<F> {
+    const MAX_BUFFERED_BYTES: usize = 1_000_000;
+
+    fn new(
+        build_pipeline: impl FnOnce(Service<F, DuplexStream, DuplexStream>) -> Pipeline<F>,
+    ) -> Self {
+        let (service_input, input_handle) = tokio::io::duplex(Self::MAX_BUFFERED_BYTES);
+        let (service_output, output_handle) = tokio::io::duplex(Self::MAX_BUFFERED_BYTES);
+
+        Self {
+            pipeline: build_pipeline(Service::new(service_input, service_output)),
+            input_handle,
+            framed_output: FramedRead::new(output_handle, LanguageServerCodec::default()),
+            passed_over: VecDeque::new(),
+            next_request_id: 0,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Runs the service with the given backend, which makes progress whenever the driver is
+    /// awaiting output.
+    pub fn with_backend<B, Fut>(backend: B) -> Self
+    where
+        F: 'static,
+        B: FnOnce(ServiceBackend<F>) -> Fut + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        Self::new(|service| Pipeline::Running(Some(Box::pin(service.run(backend)))))
+    }
+
+    /// How long any awaiting of output may take before failing with [`DriverError::Timeout`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn send_input_message(&mut self, message: &AllMessages) {
+        let payload_string = Payload::new(message).to_string();
+        self.send_raw_payload_str(&payload_string).await
+    }
+
+    pub async fn send_raw_payload_str(&mut self, payload_str: &str) {
+        self.input_handle
+            .write_all(payload_str.as_bytes())
+            .await
+            .expect(INPUT_CLOSED)
+    }
+
+    /// Returns the id of the sent request, for awaiting its response.
+    pub async fn send_request<R: Request>(&mut self, params: R::Params) -> RequestId
+    where
+        RequestMessage<R>: Into<AllRequests>,
+    {
+        let request_id = RequestId::from(NumberOrString::Number(self.next_request_id));
+        self.next_request_id += 1;
+
+        let request = RequestMessage::<R> {
+            id: request_id.clone(),
+            params: Some(params),
+        };
+        self.send_input_message(&AllMessages::Requests(request.into()))
+            .await;
+
+        request_id
+    }
+
+    pub async fn send_notification<N: Notification>(&mut self, params: N::Params)
+    where
+        NotificationMessage<N>: Into<AllNotifications>,
+    {
+        let notification = NotificationMessage::<N> {
+            params: Some(params),
+        };
+        self.send_input_message(&AllMessages::Notifications(notification.into()))
+            .await
+    }
+
+    pub async fn response<R: Request>(
+        &mut self,
+        request_id: &RequestId,
+    ) -> Result<ResponseMessage<R>, DriverError> {
+        let response_id = ResponseId::from(request_id.clone());
+        let message = self
+            .await_output(|message| {
+                matches!(message, AllMessages::UntypedResponse(response) if response.id == response_id)
+            })
+            .await?;
+
+        match message {
+            AllMessages::UntypedResponse(response) => Ok(response.try_into()?),
+            _ => unreachable!("only responses are matched"),
+        }
+    }
+
+    /// Awaits the next notification of type `N`, returning its params.
+    pub async fn notification<N: Notification>(&mut self) -> Result<N::Params, DriverError> {
+        let message = self
+            .await_output(|message| {
+                matches!(message, AllMessages::Notifications(_))
+                    && serde_json::to_value(message)
+                        .is_ok_and(|notification| notification["method"] == N::METHOD)
+            })
+            .await?;
+
+        let mut notification = serde_json::to_value(message)?;
+        Ok(serde_json::from_value(notification["params"].take())?)
+    }
+
+    pub async fn next_output_message(&mut self) -> Result<AllMessages, DriverError> {
+        self.await_output(|_| true).await
+    }
+
+    pub async fn get_output_message(&mut self) -> Option<AllMessages> {
+        self.next_output_message().await.ok()
+    }
+
+    pub fn get_incoming_at_backend(&mut self) -> Option<IncomingMessage<F>> {
+        self.manual_backend().get_incoming()
+    }
+
+    pub fn send_outgoing_at_backend(&mut self, message: OutgoingMessage<F>) {
+        self.manual_backend().send_outgoing(message)
+    }
+
+    /// Moves messages one step along a manually stepped pipeline, does nothing for a running one.
+    pub async fn tick(&mut self) {
+        if let Pipeline::Manual {
+            frontend,
+            message_filter,
+            ..
+        } = &mut self.pipeline
+        {
+            // Backend to frontend
+            message_filter.tick();
+            // Frontend to filter
+            frontend.tick().await;
+            // Filter to backend
+            message_filter.tick();
+        }
+    }
+
+    /// Closes the service input and waits for a running service to shut down.
+    pub async fn shutdown(self) -> Result<(), DriverError> {
+        drop(self.input_handle);
+        if let Pipeline::Running(Some(service)) = self.pipeline {
+            tokio::time::timeout(self.timeout, service)
+                .await
+                .map_err(|_| DriverError::Timeout)?;
+        }
+        Ok(())
+    }
+
+    async fn await_output(
+        &mut self,
+        mut is_awaited: impl FnMut(&AllMessages) -> bool,
+    ) -> Result<AllMessages, DriverError> {
+        if let Some(index) = self.passed_over.iter().position(&mut is_awaited) {
+            return Ok(self
+                .passed_over
+                .remove(index)
+                .expect("index should be in bounds"));
+        }
+
+        let timeout = self.timeout;
+        tokio::time::timeout(timeout, async {
+            loop {
+                match self.poll_output().await {
+                    Some(Some(Ok(message))) if is_awaited(&message) => return Ok(message),
+                    Some(Some(Ok(message))) => self.passed_over.push_back(message),
+                    Some(Some(Err(err))) => return Err(err.into()),
+                    Some(None) => return Err(DriverError::OutputClosed),
+                    None => continue,
+                }
+            }
+        })
+        .await
+        .map_err(|_| DriverError::Timeout)?
+    }
+
+    /// Reads the next decode attempt of the output while keeping the pipeline going, `None` when
+    /// the pipeline has yet to output anything.
+    async fn poll_output(&mut self) -> Option<Option<Result<AllMessages, DecodeError>>> {
+        match &mut self.pipeline {
+            Pipeline::Manual { .. } => {
+                match tokio::time::timeout(TICK_INTERVAL, self.framed_output.next()).await {
+                    Ok(decode_attempt) => Some(decode_attempt),
+                    Err(_) => {
+                        self.tick().await;
+                        None
+                    }
+                }
+            }
+            Pipeline::Running(Some(service)) => tokio::select! {
+                _ = service.as_mut() => {
+                    self.pipeline = Pipeline::Running(None);
+                    None
+                }
+                decode_attempt = self.framed_output.next() => Some(decode_attempt),
+            },
+            Pipeline::Running(None) => Some(self.framed_output.next().await),
+        }
+    }
+
+    fn manual_backend(&mut self) -> &mut ServiceBackend<F> {
+        match &mut self.pipeline {
+            Pipeline::Manual { backend, .. } => backend,
+            Pipeline::Running(_) => panic!("backend is operated by the running service"),
+        }
+    }
+}
+
+impl<F: MessageFilter> Default for ServiceDriver<F> {
+    /// Drives the service pipeline step by step, with the test acting as the backend.
+    fn default() -> Self {
+        Self::new(|service| Pipeline::Manual {
+            frontend: Box::new(service.frontend),
+            message_filter: service.message_filter,
+            backend: service.backend,
+        })
+    }
+}
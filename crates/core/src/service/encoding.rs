@@ -0,0 +1,199 @@
+use std::sync::{Arc, Mutex};
+
+use lsp_types::InitializeParams;
+use serde_json::{Map, Value};
+
+use crate::{documents::PositionEncoding, messages::groups::AllMessages};
+
+use super::{filter::MessageHook, initialize::InitializeTracker};
+
+const POSITION_ENCODING_KEY: &str = "positionEncoding";
+const NEGOTIATED_ENCODING_POISONED: &str = "negotiated encoding lock poisoned";
+
+/// The encoding announced by the capabilities, UTF-16 when left out.
+pub(crate) fn announced_position_encoding(capabilities: &Map<String, Value>) -> PositionEncoding {
+    capabilities
+        .get(POSITION_ENCODING_KEY)
+        .and_then(|kind| serde_json::from_value(kind.clone()).ok())
+        .and_then(|kind| PositionEncoding::from_kind(&kind))
+        .unwrap_or_default()
+}
+
+/// The position encoding announced to the client, UTF-16 until the `initialize` result has been
+/// sent.
+#[derive(Clone, Default)]
+pub struct NegotiatedPositionEncoding(Arc<Mutex<PositionEncoding>>);
+
+impl NegotiatedPositionEncoding {
+    pub fn get(&self) -> PositionEncoding {
+        *self.0.lock().expect(NEGOTIATED_ENCODING_POISONED)
+    }
+
+    fn set(&self, encoding: PositionEncoding) {
+        *self.0.lock().expect(NEGOTIATED_ENCODING_POISONED) = encoding
+    }
+}
+
+/// Picks the first of the preferred encodings supported by the client, and announces it in the
+/// `initialize` result unless the backend already chose an encoding itself. UTF-16 needs no
+/// announcement.
+pub(crate) struct PositionEncodingNegotiator {
+    preferred: Vec<PositionEncoding>,
+    negotiated: PositionEncoding,
+    announced: NegotiatedPositionEncoding,
+    initialize_tracker: InitializeTracker,
+}
+
+impl PositionEncodingNegotiator {
+    pub fn new(preferred: Vec<PositionEncoding>) -> Self {
+        Self {
+            preferred,
+            negotiated: PositionEncoding::default(),
+            announced: NegotiatedPositionEncoding::default(),
+            initialize_tracker: InitializeTracker::default(),
+        }
+    }
+
+    pub fn announced(&self) -> NegotiatedPositionEncoding {
+        self.announced.clone()
+    }
+
+    fn negotiate(&self, params: &InitializeParams) -> PositionEncoding {
+        let client_encodings = params
+            .capabilities
+            .general
+            .as_ref()
+            .and_then(|general| general.position_encodings.as_ref())
+            .map(|kinds| {
+                kinds
+                    .iter()
+                    .filter_map(PositionEncoding::from_kind)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        self.preferred
+            .iter()
+            .find(|encoding| client_encodings.contains(encoding))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl MessageHook for PositionEncodingNegotiator {
+    fn inspect_incoming(&mut self, message: &AllMessages) {
+        if let Some(params) = self.initialize_tracker.inspect_incoming(message) {
+            self.negotiated = self.negotiate(params)
+        }
+    }
+
+    fn amend_outgoing(&mut self, message: &mut AllMessages) {
+        if let Some(capabilities) = self.initialize_tracker.capabilities_mut(message) {
+            if self.negotiated != PositionEncoding::default() {
+                capabilities
+                    .entry(POSITION_ENCODING_KEY)
+                    .or_insert_with(|| Value::String(self.negotiated.kind().as_str().to_string()));
+            }
+            self.announced
+                .set(announced_position_encoding(capabilities));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{ClientCapabilities, GeneralClientCapabilities, PositionEncodingKind};
+    use serde_json::json;
+
+    use crate::{
+        messages::{
+            core::response::UntypedResponseMessage, groups::requests::tests::initialize_mock,
+        },
+        service::initialize::tests::initialize_response_mock,
+    };
+
+    use super::*;
+
+    fn negotiated_capabilities(
+        negotiator: &mut PositionEncodingNegotiator,
+        position_encodings: Option<Vec<PositionEncodingKind>>,
+        result: Value,
+    ) -> Value {
+        negotiator.inspect_incoming(&initialize_mock(ClientCapabilities {
+            general: Some(GeneralClientCapabilities {
+                position_encodings,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }));
+        let mut response = initialize_response_mock(result);
+        negotiator.amend_outgoing(&mut response);
+
+        let AllMessages::UntypedResponse(UntypedResponseMessage {
+            kind: Ok(result), ..
+        }) = response
+        else {
+            unreachable!()
+        };
+        result["capabilities"].clone()
+    }
+
+    #[test]
+    fn leaves_result_untouched_for_utf16() {
+        let mut negotiator = PositionEncodingNegotiator::new(vec![PositionEncoding::Utf16]);
+        assert_eq!(
+            json!({ "hoverProvider": true }),
+            negotiated_capabilities(
+                &mut negotiator,
+                Some(vec![
+                    PositionEncodingKind::UTF8,
+                    PositionEncodingKind::UTF16
+                ]),
+                json!({ "capabilities": { "hoverProvider": true } }),
+            )
+        );
+        assert_eq!(PositionEncoding::Utf16, negotiator.announced().get());
+    }
+
+    #[test]
+    fn negotiates_preferred_encoding() {
+        let mut negotiator =
+            PositionEncodingNegotiator::new(vec![PositionEncoding::Utf8, PositionEncoding::Utf32]);
+        assert_eq!(
+            json!({ "positionEncoding": "utf-32", "hoverProvider": true }),
+            negotiated_capabilities(
+                &mut negotiator,
+                Some(vec![
+                    PositionEncodingKind::UTF16,
+                    PositionEncodingKind::UTF32
+                ]),
+                json!({ "capabilities": { "hoverProvider": true } }),
+            )
+        );
+        assert_eq!(PositionEncoding::Utf32, negotiator.announced().get());
+    }
+
+    #[test]
+    fn falls_back_to_utf16() {
+        let mut negotiator = PositionEncodingNegotiator::new(vec![PositionEncoding::Utf8]);
+        assert_eq!(
+            json!({}),
+            negotiated_capabilities(&mut negotiator, None, json!({}))
+        );
+        assert_eq!(PositionEncoding::Utf16, negotiator.announced().get());
+    }
+
+    #[test]
+    fn keeps_encoding_chosen_by_backend() {
+        let mut negotiator = PositionEncodingNegotiator::new(vec![PositionEncoding::Utf8]);
+        assert_eq!(
+            json!({ "positionEncoding": "utf-32" }),
+            negotiated_capabilities(
+                &mut negotiator,
+                Some(vec![PositionEncodingKind::UTF8]),
+                json!({ "capabilities": { "positionEncoding": "utf-32" } }),
+            )
+        );
+        assert_eq!(PositionEncoding::Utf32, negotiator.announced().get());
+    }
+}
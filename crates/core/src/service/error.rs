@@ -1,7 +1,12 @@
+#[cfg(any(test, feature = "test-util"))]
 pub(crate) const OUTPUT_CLOSED: &str = "output sink closed";
 pub(crate) const BACKEND_INPUT_CLOSED: &str = "service backend input closed";
 pub(crate) const BACKEND_OUTPUT_CLOSED: &str = "service backend output closed";
 pub(crate) const MESSAGE_FILTER_INPUT_CLOSED: &str = "service message filter input closed";
+#[cfg(any(test, feature = "test-util"))]
 pub(crate) const MESSAGE_FILTER_OUTPUT_CLOSED: &str = "service message filter output closed";
 pub(crate) const FRONTEND_INPUT_CLOSED: &str = "service frontend input closed";
+#[cfg(any(test, feature = "test-util"))]
 pub(crate) const FRONTEND_OUTPUT_CLOSED: &str = "service frontend output closed";
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) const INPUT_CLOSED: &str = "input source closed";
@@ -1,4 +1,7 @@
-use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
+use futures::{
+    channel::mpsc::{UnboundedReceiver, UnboundedSender},
+    StreamExt,
+};
 
 use crate::{
     messages::{
@@ -18,10 +21,15 @@ use crate::{
             AllMessages,
         },
     },
-    service::error::BACKEND_OUTPUT_CLOSED,
+    outbox::PendingResponses,
+    service::error::FRONTEND_INPUT_CLOSED,
 };
 
-use super::error::{BACKEND_INPUT_CLOSED, FRONTEND_INPUT_CLOSED, FRONTEND_OUTPUT_CLOSED};
+#[cfg(any(test, feature = "test-util"))]
+use super::error::{BACKEND_OUTPUT_CLOSED, FRONTEND_OUTPUT_CLOSED};
+
+#[cfg(feature = "tracing-layers")]
+pub(crate) const TRACING_TARGET: &str = module_path!();
 
 pub(crate) struct ServiceMessageFilter<F: MessageFilter> {
     frontend_rx: UnboundedReceiver<AllMessages>,
@@ -29,6 +37,9 @@ pub(crate) struct ServiceMessageFilter<F: MessageFilter> {
     backend_rx: UnboundedReceiver<OutgoingMessage<F>>,
     backend_tx: UnboundedSender<IncomingMessage<F>>,
     type_store: F::TypeStore,
+    hooks: Vec<Box<dyn MessageHook + Send>>,
+    /// Requests sent through the service outbox rather than the backend.
+    pending_responses: PendingResponses,
 }
 
 impl<F: MessageFilter> ServiceMessageFilter<F> {
@@ -37,6 +48,7 @@ impl<F: MessageFilter> ServiceMessageFilter<F> {
         frontend_tx: UnboundedSender<AllMessages>,
         backend_rx: UnboundedReceiver<OutgoingMessage<F>>,
         backend_tx: UnboundedSender<IncomingMessage<F>>,
+        pending_responses: PendingResponses,
     ) -> Self {
         Self {
             frontend_rx,
@@ -44,42 +56,108 @@ impl<F: MessageFilter> ServiceMessageFilter<F> {
             backend_rx,
             backend_tx,
             type_store: F::TypeStore::new(),
+            hooks: Vec::new(),
+            pending_responses,
         }
     }
 
+    pub fn add_hook(&mut self, hook: impl MessageHook + Send + 'static) {
+        self.hooks.push(Box::new(hook))
+    }
+
+    /// Runs the hook ahead of those already added.
+    pub fn prepend_hook(&mut self, hook: impl MessageHook + Send + 'static) {
+        self.hooks.insert(0, Box::new(hook))
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
     pub fn tick(&mut self) {
         self.try_forward_to_backend();
         self.forward_to_frontend();
     }
 
+    pub async fn run(mut self) {
+        let mut frontend_closed = false;
+        loop {
+            tokio::select! {
+                frontend_message = self.frontend_rx.next(), if !frontend_closed => {
+                    match frontend_message {
+                        Some(message) => self.forward_message_to_backend(message),
+                        None => {
+                            // Lets the backend finish up, the filter then stops once its
+                            // output closes.
+                            frontend_closed = true;
+                            self.backend_tx.close_channel();
+                        }
+                    }
+                }
+                backend_message = self.backend_rx.next() => match backend_message {
+                    Some(message) => self.forward_message_to_frontend(message),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
     pub fn forward_to_frontend(&mut self) {
         if let Ok(message_result) = self.backend_rx.try_next() {
             let message = message_result.expect(BACKEND_OUTPUT_CLOSED);
-            if let OutgoingMessage::Request(outgoing_request) = &message {
-                self.type_store.store_request_type(outgoing_request)
-            }
-
-            self.frontend_tx
-                .unbounded_send(message.into())
-                .expect(FRONTEND_INPUT_CLOSED)
+            self.forward_message_to_frontend(message)
         }
     }
 
+    #[cfg(any(test, feature = "test-util"))]
     pub fn try_forward_to_backend(&mut self) {
         if let Ok(message_result) = self.frontend_rx.try_next() {
             let message = message_result.expect(FRONTEND_OUTPUT_CLOSED);
+            self.forward_message_to_backend(message)
+        }
+    }
+
+    fn forward_message_to_frontend(&mut self, message: OutgoingMessage<F>) {
+        if let OutgoingMessage::Request(outgoing_request) = &message {
+            self.type_store.store_request_type(outgoing_request)
+        }
+
+        let mut message = message.into();
+        for hook in self.hooks.iter_mut() {
+            hook.amend_outgoing(&mut message)
+        }
+
+        self.frontend_tx
+            .unbounded_send(message)
+            .expect(FRONTEND_INPUT_CLOSED)
+    }
 
-            match self.typeset_incoming(message) {
-                Ok(incoming_message) => self
-                    .backend_tx
-                    .unbounded_send(incoming_message)
-                    .expect(BACKEND_INPUT_CLOSED),
-                Err(try_from_err) => {
-                    self.frontend_tx
-                        .unbounded_send(try_from_err.into())
-                        .expect(FRONTEND_INPUT_CLOSED);
+    fn forward_message_to_backend(&mut self, message: AllMessages) {
+        for hook in self.hooks.iter_mut() {
+            hook.inspect_incoming(&message)
+        }
+
+        let message = match message {
+            AllMessages::UntypedResponse(response) => {
+                match self.pending_responses.resolve(response) {
+                    Ok(()) => return,
+                    Err(response) => AllMessages::UntypedResponse(response),
                 }
             }
+            message => message,
+        };
+
+        match self.typeset_incoming(message) {
+            Ok(incoming_message) => {
+                // Backend may have finished early, in which case there's nothing left to handle
+                // the message.
+                if self.backend_tx.unbounded_send(incoming_message).is_err() {
+                    tracing::warn!("Service backend closed, dropping incoming message.")
+                }
+            }
+            Err(try_from_err) => {
+                self.frontend_tx
+                    .unbounded_send(try_from_err.into())
+                    .expect(FRONTEND_INPUT_CLOSED);
+            }
         }
     }
 
@@ -128,6 +206,15 @@ impl<F: MessageFilter> ServiceMessageFilter<F> {
     }
 }
 
+/// Observes the messages passing through the service message filter, regardless of whether the
+/// [`MessageFilter`] lets them through to the backend.
+pub trait MessageHook {
+    fn inspect_incoming(&mut self, message: &AllMessages);
+
+    /// Runs on messages from the backend, in the order the hooks were added.
+    fn amend_outgoing(&mut self, _message: &mut AllMessages) {}
+}
+
 pub trait TypeStore<F: MessageFilter> {
     fn new() -> Self;
     fn store_request_type(&mut self, outgoing_request: &F::OutgoingRequests);
@@ -198,11 +285,11 @@ pub mod tests {
 
     use super::*;
 
+    type ResponseTypingFnMock =
+        fn(UntypedResponseMessage) -> Result<SomeResponsesMock, serde_json::Error>;
+
     pub struct TypeStoreMock {
-        store: HashMap<
-            RequestId,
-            fn(UntypedResponseMessage) -> Result<SomeResponsesMock, serde_json::Error>,
-        >,
+        store: HashMap<RequestId, ResponseTypingFnMock>,
     }
 
     impl TypeStore<FilterMock> for TypeStoreMock {
@@ -263,7 +350,7 @@ pub mod tests {
         AllMessages::Requests(AllRequests::Client(ShowDocument(RequestMessage {
             id: Number(1).into(),
             params: Some(lsp_types::ShowDocumentParams {
-                uri: "www.google.com".parse().unwrap(),
+                uri: "https://www.google.com".parse().unwrap(),
                 external: None,
                 take_focus: None,
                 selection: None,
@@ -2,24 +2,37 @@ use std::sync::Arc;
 
 use crate::{
     messages::{
-        codec::LanguageServerCodec,
+        codec::{Frames, LanguageServerCodec},
         groups::{responses::errors::DecodeErrorResponse, AllMessages},
+        payload::Payload,
     },
-    service::error::{MESSAGE_FILTER_INPUT_CLOSED, MESSAGE_FILTER_OUTPUT_CLOSED},
+    service::error::MESSAGE_FILTER_INPUT_CLOSED,
+    session::{Direction, SessionRecorder},
 };
 use futures::{
     channel::mpsc::{UnboundedReceiver, UnboundedSender},
-    join, FutureExt, SinkExt, StreamExt,
+    SinkExt, StreamExt,
 };
+#[cfg(any(test, feature = "test-util"))]
+use futures::{join, FutureExt};
 use tokio::{
     io::{AsyncRead, AsyncWrite},
     sync::Mutex,
 };
-use tokio_util::codec::{FramedRead, FramedWrite};
+use tokio_util::{
+    codec::{FramedRead, FramedWrite},
+    sync::CancellationToken,
+};
+
+#[cfg(any(test, feature = "test-util"))]
+use crate::service::error::{MESSAGE_FILTER_OUTPUT_CLOSED, OUTPUT_CLOSED};
+
+use super::trace::LogTracer;
 
-use crate::service::error::OUTPUT_CLOSED;
+#[cfg(feature = "tracing-layers")]
+pub(crate) const TRACING_TARGET: &str = module_path!();
 
-type FramedInput<I> = FramedRead<I, LanguageServerCodec<AllMessages>>;
+type FramedInput<I> = Frames<FramedRead<I, LanguageServerCodec<AllMessages>>>;
 type FramedOutputLock<O> = Arc<Mutex<FramedWrite<O, LanguageServerCodec<AllMessages>>>>;
 
 pub(crate) struct ServiceFrontend<I: AsyncRead + Unpin, O: AsyncWrite + Unpin> {
@@ -28,6 +41,11 @@ pub(crate) struct ServiceFrontend<I: AsyncRead + Unpin, O: AsyncWrite + Unpin> {
     framed_output_clone: FramedOutputLock<O>,
     message_filter_tx: UnboundedSender<AllMessages>,
     message_filter_rx: UnboundedReceiver<AllMessages>,
+    shutdown_token: CancellationToken,
+    recorder: Option<SessionRecorder>,
+    tracer: Option<LogTracer>,
+    /// Messages sent by the service itself rather than its backend, such as `$/logTrace`.
+    notification_rx: UnboundedReceiver<AllMessages>,
 }
 
 impl<I: AsyncRead + Unpin, O: AsyncWrite + Unpin> ServiceFrontend<I, O> {
@@ -36,6 +54,8 @@ impl<I: AsyncRead + Unpin, O: AsyncWrite + Unpin> ServiceFrontend<I, O> {
         write_output: O,
         message_filter_tx: UnboundedSender<AllMessages>,
         message_filter_rx: UnboundedReceiver<AllMessages>,
+        shutdown_token: CancellationToken,
+        notification_rx: UnboundedReceiver<AllMessages>,
     ) -> Self {
         let framed_output = Arc::new(Mutex::new(FramedWrite::new(
             write_output,
@@ -43,33 +63,228 @@ impl<I: AsyncRead + Unpin, O: AsyncWrite + Unpin> ServiceFrontend<I, O> {
         )));
 
         Self {
-            framed_input: FramedRead::new(
+            framed_input: Frames::new(FramedRead::new(
                 read_input,
                 LanguageServerCodec::<AllMessages>::default(),
-            ),
+            )),
             framed_output_clone: framed_output.clone(),
             framed_output,
             message_filter_tx,
             message_filter_rx,
+            shutdown_token,
+            recorder: None,
+            tracer: None,
+            notification_rx,
         }
     }
 
+    /// Records messages read from the input as incoming once decoded, frames failing to decode
+    /// included, and messages written to the output as outgoing once sent.
+    pub fn set_recorder(&mut self, recorder: SessionRecorder) {
+        self.recorder = Some(recorder)
+    }
+
+    /// Traces the messages read and written as `$/logTrace` notifications.
+    pub fn set_tracer(&mut self, tracer: LogTracer) {
+        self.tracer = Some(tracer)
+    }
+
+    #[cfg(feature = "tracing-layers")]
+    pub fn tracer(&self) -> Option<&LogTracer> {
+        self.tracer.as_ref()
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
     pub async fn tick(&mut self) {
         join!(
             Self::try_forward_to_message_filter(
                 &self.framed_output_clone,
                 &mut self.framed_input,
-                &self.message_filter_tx
+                &self.message_filter_tx,
+                self.tracer.as_ref()
+            ),
+            Self::forward_from_message_filter(
+                &self.framed_output,
+                &mut self.message_filter_rx,
+                &mut self.notification_rx,
+                self.tracer.as_ref()
             ),
-            Self::forward_from_message_filter(&self.framed_output, &mut self.message_filter_rx),
         );
     }
 
+    pub async fn run(self) {
+        let Self {
+            mut framed_input,
+            framed_output,
+            message_filter_tx,
+            mut message_filter_rx,
+            framed_output_clone,
+            shutdown_token,
+            recorder,
+            tracer,
+            mut notification_rx,
+        } = self;
+        drop(framed_output_clone);
+        let mut framed_output = Arc::into_inner(framed_output)
+            .expect("framed output lock shared outside of frontend")
+            .into_inner();
+
+        let mut input_closed = false;
+        let mut output_closed = false;
+
+        loop {
+            tokio::select! {
+                decode_attempt = framed_input.next(), if !input_closed => match decode_attempt {
+                    Some(Ok(message)) => {
+                        if let Some(recorder) = &recorder {
+                            let body = serde_json::to_vec(&message).unwrap_or_default();
+                            recorder.record(Direction::Incoming, &body, None)
+                        }
+                        tracing::debug!(
+                            ?message,
+                            "Forwarding message from reader to message filter."
+                        );
+                        if let Some(tracer) = &tracer {
+                            tracer.trace_message(Direction::Incoming, &message)
+                        }
+                        message_filter_tx
+                            .unbounded_send(message)
+                            .expect(MESSAGE_FILTER_INPUT_CLOSED)
+                    }
+                    Some(Err(err)) => {
+                        if let Some(recorder) = &recorder {
+                            recorder.record(Direction::Incoming, &[], Some(err.to_string()))
+                        }
+                        let error_response = DecodeErrorResponse::create(err).into();
+                        Self::write_output(
+                            &mut framed_output,
+                            &mut output_closed,
+                            recorder.as_ref(),
+                            error_response,
+                        )
+                        .await
+                    }
+                    None => {
+                        tracing::debug!("Service input closed.");
+                        input_closed = true;
+                        message_filter_tx.close_channel();
+                    }
+                },
+                _ = shutdown_token.cancelled(), if !input_closed => {
+                    tracing::debug!("Service shutdown requested, closing input.");
+                    input_closed = true;
+                    message_filter_tx.close_channel();
+                }
+                filter_message = message_filter_rx.next() => match filter_message {
+                    Some(message) => {
+                        // Partial results of a request must be written before its response.
+                        Self::write_notifications(
+                            &mut framed_output,
+                            &mut output_closed,
+                            &mut notification_rx,
+                            recorder.as_ref(),
+                            tracer.as_ref(),
+                        )
+                        .await;
+                        tracing::debug!(
+                            ?message,
+                            "Forwarding message from message_filter to writer."
+                        );
+                        if let Some(tracer) = &tracer {
+                            tracer.trace_message(Direction::Outgoing, &message)
+                        }
+                        Self::write_output(
+                            &mut framed_output,
+                            &mut output_closed,
+                            recorder.as_ref(),
+                            message,
+                        )
+                        .await;
+                        if output_closed && !input_closed {
+                            // Nobody left to respond to, so stop taking in new messages.
+                            input_closed = true;
+                            message_filter_tx.close_channel();
+                        }
+                    }
+                    None => break,
+                },
+                Some(notification) = notification_rx.next() => {
+                    if let Some(tracer) = &tracer {
+                        tracer.trace_message(Direction::Outgoing, &notification)
+                    }
+                    Self::write_output(
+                        &mut framed_output,
+                        &mut output_closed,
+                        recorder.as_ref(),
+                        notification,
+                    )
+                    .await
+                }
+            }
+        }
+
+        // Notifications such as traces of the last messages written may still be queued.
+        Self::write_notifications(
+            &mut framed_output,
+            &mut output_closed,
+            &mut notification_rx,
+            recorder.as_ref(),
+            tracer.as_ref(),
+        )
+        .await;
+    }
+
+    async fn write_notifications(
+        framed_output: &mut FramedWrite<O, LanguageServerCodec<AllMessages>>,
+        output_closed: &mut bool,
+        notification_rx: &mut UnboundedReceiver<AllMessages>,
+        recorder: Option<&SessionRecorder>,
+        tracer: Option<&LogTracer>,
+    ) {
+        while let Ok(Some(notification)) = notification_rx.try_next() {
+            if let Some(tracer) = tracer {
+                tracer.trace_message(Direction::Outgoing, &notification)
+            }
+            Self::write_output(framed_output, output_closed, recorder, notification).await
+        }
+    }
+
+    async fn write_output(
+        framed_output: &mut FramedWrite<O, LanguageServerCodec<AllMessages>>,
+        output_closed: &mut bool,
+        recorder: Option<&SessionRecorder>,
+        message: AllMessages,
+    ) {
+        if *output_closed {
+            return;
+        }
+
+        // Only rendered when recording, and kept until the write is known to have gone through.
+        let recorded_body = recorder.map(|_| Payload::new(&message).body().to_owned());
+        match framed_output.send(message).await {
+            Ok(()) => {
+                if let (Some(recorder), Some(body)) = (recorder, recorded_body) {
+                    recorder.record(Direction::Outgoing, body.as_bytes(), None)
+                }
+            }
+            Err(err) => {
+                tracing::error!(?err, "Unable to write to service output.");
+                *output_closed = true;
+            }
+        }
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
     async fn forward_from_message_filter(
         framed_write_lock: &FramedOutputLock<O>,
         from_backend_rx: &mut UnboundedReceiver<AllMessages>,
+        notification_rx: &mut UnboundedReceiver<AllMessages>,
+        tracer: Option<&LogTracer>,
     ) {
         if let Ok(channel_result) = from_backend_rx.try_next() {
+            // Partial results of a request must be written before its response.
+            Self::send_notifications(framed_write_lock, notification_rx, tracer).await;
+
             let mut output_guard = framed_write_lock.lock().await;
             let message = channel_result.expect(MESSAGE_FILTER_OUTPUT_CLOSED);
 
@@ -78,14 +293,36 @@ impl<I: AsyncRead + Unpin, O: AsyncWrite + Unpin> ServiceFrontend<I, O> {
                 "Forwarding message from message_filter to writer."
             );
 
+            if let Some(tracer) = tracer {
+                tracer.trace_message(Direction::Outgoing, &message)
+            }
             output_guard.send(message).await.expect(OUTPUT_CLOSED)
         }
+
+        Self::send_notifications(framed_write_lock, notification_rx, tracer).await;
+    }
+
+    #[cfg(any(test, feature = "test-util"))]
+    async fn send_notifications(
+        framed_write_lock: &FramedOutputLock<O>,
+        notification_rx: &mut UnboundedReceiver<AllMessages>,
+        tracer: Option<&LogTracer>,
+    ) {
+        while let Ok(Some(notification)) = notification_rx.try_next() {
+            if let Some(tracer) = tracer {
+                tracer.trace_message(Direction::Outgoing, &notification)
+            }
+            let mut output_guard = framed_write_lock.lock().await;
+            output_guard.send(notification).await.expect(OUTPUT_CLOSED)
+        }
     }
 
+    #[cfg(any(test, feature = "test-util"))]
     async fn try_forward_to_message_filter(
         framed_write_lock: &FramedOutputLock<O>,
-        framed_read_input: &mut FramedRead<I, LanguageServerCodec<AllMessages>>,
+        framed_read_input: &mut FramedInput<I>,
         backend_tx: &UnboundedSender<AllMessages>,
+        tracer: Option<&LogTracer>,
     ) {
         if let Some(Some(message_decode_attempt)) = framed_read_input.next().now_or_never() {
             match message_decode_attempt {
@@ -94,6 +331,9 @@ impl<I: AsyncRead + Unpin, O: AsyncWrite + Unpin> ServiceFrontend<I, O> {
                         ?message,
                         "Forwarding message from reader to message filter."
                     );
+                    if let Some(tracer) = tracer {
+                        tracer.trace_message(Direction::Incoming, &message)
+                    }
                     backend_tx
                         .unbounded_send(message)
                         .expect(MESSAGE_FILTER_INPUT_CLOSED)
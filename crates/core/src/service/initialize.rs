@@ -0,0 +1,91 @@
+use lsp_types::InitializeParams;
+use serde_json::{Map, Value};
+
+use crate::messages::{
+    core::response::{ResponseId, UntypedResponseMessage},
+    groups::{
+        requests::{AllRequests, AllServerRequests},
+        AllMessages,
+    },
+};
+
+const CAPABILITIES_KEY: &str = "capabilities";
+
+/// Matches the response to `initialize` up with its request.
+#[derive(Clone, Default)]
+pub(crate) struct InitializeTracker {
+    request_id: Option<ResponseId>,
+}
+
+impl InitializeTracker {
+    /// The params of the message if it is the `initialize` request.
+    pub fn inspect_incoming<'a>(
+        &mut self,
+        message: &'a AllMessages,
+    ) -> Option<&'a InitializeParams> {
+        let AllMessages::Requests(AllRequests::Server(AllServerRequests::Initialize(request))) =
+            message
+        else {
+            return None;
+        };
+        self.request_id = Some(request.id.clone().into());
+        request.params.as_ref()
+    }
+
+    /// The server capabilities of the message if it is a successful `initialize` response.
+    pub fn capabilities_mut<'a>(
+        &mut self,
+        message: &'a mut AllMessages,
+    ) -> Option<&'a mut Map<String, Value>> {
+        let AllMessages::UntypedResponse(UntypedResponseMessage {
+            id,
+            kind: Ok(Value::Object(result)),
+        }) = message
+        else {
+            return None;
+        };
+        if self.request_id.as_ref() != Some(id) {
+            return None;
+        }
+
+        self.request_id = None;
+        result
+            .entry(CAPABILITIES_KEY)
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use lsp_types::{ClientCapabilities, NumberOrString};
+    use serde_json::json;
+
+    use crate::messages::groups::requests::tests::initialize_mock;
+
+    use super::*;
+
+    /// Answers [`initialize_mock`].
+    pub(crate) fn initialize_response_mock(result: Value) -> AllMessages {
+        AllMessages::UntypedResponse(UntypedResponseMessage {
+            id: ResponseId::NumberOrString(NumberOrString::Number(0)),
+            kind: Ok(result),
+        })
+    }
+
+    #[test]
+    fn matches_response_with_request() {
+        let mut initialize_tracker = InitializeTracker::default();
+        let mut response = initialize_response_mock(json!({}));
+        assert!(initialize_tracker.capabilities_mut(&mut response).is_none());
+
+        assert!(initialize_tracker
+            .inspect_incoming(&initialize_mock(ClientCapabilities::default()))
+            .is_some());
+        assert_eq!(
+            Some(&mut Map::new()),
+            initialize_tracker.capabilities_mut(&mut response)
+        );
+        assert!(initialize_tracker.capabilities_mut(&mut response).is_none());
+    }
+}
@@ -1,123 +1,54 @@
 mod backend;
+mod diagnostics;
+#[cfg(any(test, feature = "test-util"))]
+pub mod driver;
+pub(crate) mod encoding;
 mod error;
-pub(crate) mod filter;
+pub mod filter;
 mod frontend;
+pub(crate) mod initialize;
+mod multi_client;
+mod partial;
+mod progress;
+mod pull_diagnostics;
+mod registration;
+#[cfg(any(test, feature = "test-util"))]
+pub mod script;
+pub(crate) mod server;
+mod trace;
+mod transport;
+mod watchdog;
+mod watchers;
+#[cfg(feature = "tracing-layers")]
+mod window;
+
+pub use backend::ServiceBackend;
+pub use diagnostics::DiagnosticsPublisher;
+pub use encoding::NegotiatedPositionEncoding;
+pub use multi_client::serve_tcp_clients;
+#[cfg(unix)]
+pub use multi_client::serve_unix_clients;
+pub use partial::{PartialResultRequest, PartialResults};
+pub use progress::{ProgressReporter, WorkDoneProgress};
+pub use pull_diagnostics::PullDiagnostics;
+pub use registration::{DynamicRegistrations, RegistrationError};
+pub use server::Service;
+#[cfg(feature = "tracing-layers")]
+pub use trace::LogTraceLayer;
+#[cfg(unix)]
+pub use transport::serve_unix;
+pub use transport::{serve_stdio, serve_tcp, SocketMode};
+pub use watchdog::ParentProcessWatchdog;
+pub use watchers::{FileWatch, FileWatchError, FileWatchers};
+#[cfg(feature = "tracing-layers")]
+pub use window::WindowMessageLayer;
 
-// TODO: place behind feature flag for usage in other crates
 #[cfg(test)]
-pub mod driver {
-    use bytes::BytesMut;
-    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
-    use tokio_util::codec::Decoder;
-
-    use crate::messages::{codec::LanguageServerCodec, groups::AllMessages, payload::Payload};
-
-    use super::{
-        backend::ServiceBackend,
-        error::OUTPUT_CLOSED,
-        filter::{IncomingMessage, MessageFilter, OutgoingMessage, ServiceMessageFilter},
-        frontend::ServiceFrontend,
-    };
-
-    pub struct ServiceDriver<F: MessageFilter> {
-        frontend: ServiceFrontend<DuplexStream, DuplexStream>,
-        message_filter: ServiceMessageFilter<F>,
-        backend: ServiceBackend<F>,
-        input_handle: DuplexStream,
-        output_handle: DuplexStream,
-    }
-
-    impl<F: MessageFilter> ServiceDriver<F> {
-        const MAX_PAYLOAD_BYTES: usize = 1_000_000;
-
-        pub async fn send_input_message(&mut self, message: &AllMessages) {
-            let payload_string = Payload::new(message).to_string();
-            self.send_raw_payload_str(&payload_string).await
-        }
-
-        pub async fn send_raw_payload_str(&mut self, payload_str: &str) {
-            let bytes_written = self
-                .input_handle
-                .write(payload_str.as_bytes())
-                .await
-                .unwrap();
-
-            let payload_size = payload_str.len();
-
-            if bytes_written < payload_size {
-                panic!(
-                    "unable to send payload in one write, payload size: {}, bytes_written: {}",
-                    payload_size, bytes_written
-                )
-            }
-        }
-
-        pub async fn get_output_message(&mut self) -> Option<AllMessages> {
-            let mut buffer = vec![0u8; Self::MAX_PAYLOAD_BYTES];
-            let bytes_read = self
-                .output_handle
-                .read(&mut buffer)
-                .await
-                .expect(OUTPUT_CLOSED);
-
-            LanguageServerCodec::default()
-                .decode(&mut BytesMut::from(&buffer[..bytes_read]))
-                .expect("invalid payload encoding")
-        }
-
-        pub fn get_incoming_at_backend(&mut self) -> Option<IncomingMessage<F>> {
-            self.backend.get_incoming()
-        }
-
-        pub fn send_outgoing_at_backend(&mut self, message: OutgoingMessage<F>) {
-            self.backend.send_outgoing(message)
-        }
-
-        pub async fn tick(&mut self) {
-            // Backend to frontend
-            self.message_filter.tick();
-            // Frontend to filter
-            self.frontend.tick().await;
-            // Filter to backend
-            self.message_filter.tick();
-        }
-    }
+mod tests {
+    use std::time::Duration;
 
-    impl<F: MessageFilter> Default for ServiceDriver<F> {
-        fn default() -> Self {
-            let (frontend_tx, frontend_rx) = futures::channel::mpsc::unbounded::<AllMessages>();
-            let (message_filter_tx, message_filter_rx) =
-                futures::channel::mpsc::unbounded::<AllMessages>();
-            let (incoming_tx, incoming_rx) =
-                futures::channel::mpsc::unbounded::<IncomingMessage<F>>();
-            let (outgoing_tx, outgoing_rx) =
-                futures::channel::mpsc::unbounded::<OutgoingMessage<F>>();
-            let (service_input, input_handle) = tokio::io::duplex(Self::MAX_PAYLOAD_BYTES);
-            let (service_output, output_handle) = tokio::io::duplex(Self::MAX_PAYLOAD_BYTES);
+    use lsp_types::request::Shutdown;
 
-            Self {
-                frontend: ServiceFrontend::new(
-                    service_input,
-                    service_output,
-                    frontend_tx,
-                    message_filter_rx,
-                ),
-                message_filter: ServiceMessageFilter::new(
-                    frontend_rx,
-                    message_filter_tx,
-                    outgoing_rx,
-                    incoming_tx,
-                ),
-                backend: ServiceBackend::new(incoming_rx, outgoing_tx),
-                input_handle,
-                output_handle,
-            }
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
     use crate::{
         messages::{
             core::response::{
@@ -128,10 +59,11 @@ mod tests {
             payload::tests::INVALID_PAYLOAD_STR_MOCK,
         },
         service::{
-            driver::ServiceDriver,
+            driver::{DriverError, ServiceDriver},
             filter::tests::{
                 FilterMock, INCOMING_MESSAGE_MOCK, INVALID_INCOMING_MOCK, OUTGOING_MESSAGE_MOCK,
             },
+            server::tests::shutdown_backend_mock,
         },
     };
 
@@ -206,4 +138,33 @@ mod tests {
                     })
             }))
     }
+
+    #[test_log::test(tokio::test)]
+    async fn awaits_response_from_running_service() {
+        let mut service_driver = ServiceDriver::<FilterMock>::with_backend(shutdown_backend_mock);
+        let request_id = service_driver.send_request::<Shutdown>(()).await;
+
+        let response = service_driver
+            .response::<Shutdown>(&request_id)
+            .await
+            .unwrap();
+        assert!(response.kind.is_ok());
+        service_driver.shutdown().await.unwrap()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn times_out_awaiting_missing_response() {
+        let unresponsive_backend = |backend| async move {
+            let _backend = backend;
+            futures::future::pending::<()>().await
+        };
+        let mut service_driver = ServiceDriver::<FilterMock>::with_backend(unresponsive_backend)
+            .with_timeout(Duration::from_millis(50));
+        let request_id = service_driver.send_request::<Shutdown>(()).await;
+
+        assert!(matches!(
+            service_driver.response::<Shutdown>(&request_id).await,
+            Err(DriverError::Timeout)
+        ))
+    }
 }
@@ -0,0 +1,166 @@
+use std::{future::Future, time::Duration};
+
+use futures::{Stream, StreamExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    task::JoinSet,
+};
+
+use super::{
+    backend::ServiceBackend,
+    filter::{IncomingMessage, MessageFilter, OutgoingMessage},
+    server::Service,
+};
+
+/// Accepting usually fails for reasons such as running out of file descriptors, which take a
+/// while to go away.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Serves every client connecting to the listener with its own [`Service`], each running as a
+/// task of its own.
+///
+/// Backends are created by the `backend_factory` once per connection, any state that should be
+/// shared between them is best captured by the factory itself. A disconnecting client only tears
+/// down its own service.
+pub async fn serve_tcp_clients<F, B, Fut>(listener: TcpListener, backend_factory: B)
+where
+    F: MessageFilter + 'static,
+    F::TypeStore: Send,
+    IncomingMessage<F>: Send,
+    OutgoingMessage<F>: Send,
+    B: Fn(ServiceBackend<F>) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let connections = futures::stream::unfold(listener, |listener| async move {
+        let connection = listener
+            .accept()
+            .await
+            .map(|(tcp_stream, _)| tcp_stream.into_split());
+        Some((connection, listener))
+    });
+
+    serve_connections(connections, backend_factory).await
+}
+
+/// Unix socket counterpart of [`serve_tcp_clients`].
+#[cfg(unix)]
+pub async fn serve_unix_clients<F, B, Fut>(listener: tokio::net::UnixListener, backend_factory: B)
+where
+    F: MessageFilter + 'static,
+    F::TypeStore: Send,
+    IncomingMessage<F>: Send,
+    OutgoingMessage<F>: Send,
+    B: Fn(ServiceBackend<F>) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let connections = futures::stream::unfold(listener, |listener| async move {
+        let connection = listener
+            .accept()
+            .await
+            .map(|(unix_stream, _)| unix_stream.into_split());
+        Some((connection, listener))
+    });
+
+    serve_connections(connections, backend_factory).await
+}
+
+async fn serve_connections<F, I, O, B, Fut>(
+    connections: impl Stream<Item = std::io::Result<(I, O)>>,
+    backend_factory: B,
+) where
+    F: MessageFilter + 'static,
+    F::TypeStore: Send,
+    IncomingMessage<F>: Send,
+    OutgoingMessage<F>: Send,
+    I: AsyncRead + Unpin + Send + 'static,
+    O: AsyncWrite + Unpin + Send + 'static,
+    B: Fn(ServiceBackend<F>) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut connections = std::pin::pin!(connections);
+    // Dropped along with the services still running, should serving be cancelled.
+    let mut services = JoinSet::new();
+    let mut next_connection_id: usize = 0;
+    let mut listening = true;
+
+    loop {
+        tokio::select! {
+            connection = connections.next(), if listening => match connection {
+                Some(Ok((input, output))) => {
+                    let connection_id = next_connection_id;
+                    next_connection_id += 1;
+                    tracing::info!(connection_id, "Client connected.");
+
+                    let backend_factory = backend_factory.clone();
+                    let service = Service::<F, _, _>::new(input, output);
+                    services.spawn(async move {
+                        service.run(backend_factory).await;
+                        connection_id
+                    });
+                }
+                Some(Err(err)) => {
+                    tracing::error!(?err, "Unable to accept client connection.");
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await
+                }
+                None => listening = false,
+            },
+            Some(service_result) = services.join_next(), if !services.is_empty() => {
+                match service_result {
+                    Ok(connection_id) => tracing::info!(connection_id, "Client disconnected."),
+                    Err(err) => tracing::error!(?err, "Client service failed."),
+                }
+            }
+            else => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tokio::net::TcpStream;
+
+    use crate::service::{
+        filter::tests::FilterMock,
+        server::tests::{assert_shutdown_roundtrip, shutdown_backend_mock},
+    };
+
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn serves_clients_independently() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener_addr = listener.local_addr().unwrap();
+        let backend_count = Arc::new(AtomicUsize::new(0));
+
+        let server_handle = tokio::spawn({
+            let backend_count = backend_count.clone();
+            serve_tcp_clients::<FilterMock, _, _>(listener, move |backend| {
+                backend_count.fetch_add(1, Ordering::SeqCst);
+                shutdown_backend_mock(backend)
+            })
+        });
+
+        let (first_input, first_output) = TcpStream::connect(listener_addr)
+            .await
+            .unwrap()
+            .into_split();
+        let (second_input, second_output) = TcpStream::connect(listener_addr)
+            .await
+            .unwrap()
+            .into_split();
+
+        // First client disconnects after its roundtrip without affecting the second one.
+        assert_shutdown_roundtrip(first_input, first_output).await;
+        assert_shutdown_roundtrip(second_input, second_output).await;
+
+        assert_eq!(2, backend_count.load(Ordering::SeqCst));
+        assert!(!server_handle.is_finished());
+        server_handle.abort();
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+
+use futures::{Stream, StreamExt};
+use lsp_types::{
+    request::{
+        DocumentDiagnosticRequest, References, Request, WorkspaceDiagnosticRequest,
+        WorkspaceSymbolRequest,
+    },
+    DocumentDiagnosticReport, DocumentDiagnosticReportKind, DocumentDiagnosticReportResult,
+    Location, OneOf, ProgressToken, RelatedFullDocumentDiagnosticReport, SymbolInformation, Url,
+    WorkspaceDiagnosticReport, WorkspaceDiagnosticReportPartialResult,
+    WorkspaceDiagnosticReportResult, WorkspaceSymbol, WorkspaceSymbolResponse,
+};
+use serde::Serialize;
+
+use crate::{
+    messages::groups::notifications::{PartialResultParams, PartialResultProgress},
+    outbox::Outbox,
+};
+
+/// A request whose result can be streamed to the client through its `partialResultToken`.
+pub trait PartialResultRequest: Request {
+    type Chunk: Serialize;
+
+    /// Combines the chunks into the result responded with when the client provided no token.
+    fn aggregate(chunks: Vec<Self::Chunk>) -> Self::Result;
+
+    /// Responded with once every chunk has been sent as a partial result.
+    fn empty() -> Self::Result;
+}
+
+impl PartialResultRequest for References {
+    type Chunk = Vec<Location>;
+
+    fn aggregate(chunks: Vec<Self::Chunk>) -> Self::Result {
+        Some(chunks.concat())
+    }
+
+    fn empty() -> Self::Result {
+        Some(Vec::new())
+    }
+}
+
+impl PartialResultRequest for WorkspaceSymbolRequest {
+    type Chunk = WorkspaceSymbolResponse;
+
+    /// Chunks mixing both kinds of symbols are combined into nested ones.
+    fn aggregate(chunks: Vec<Self::Chunk>) -> Self::Result {
+        let mut flat_symbols = Vec::new();
+        let mut nested_symbols = Vec::new();
+        for chunk in chunks {
+            match chunk {
+                WorkspaceSymbolResponse::Flat(symbols) => flat_symbols.extend(symbols),
+                WorkspaceSymbolResponse::Nested(symbols) => nested_symbols.extend(symbols),
+            }
+        }
+
+        if nested_symbols.is_empty() {
+            return Some(WorkspaceSymbolResponse::Flat(flat_symbols));
+        }
+        nested_symbols.extend(flat_symbols.into_iter().map(nested_symbol));
+        Some(WorkspaceSymbolResponse::Nested(nested_symbols))
+    }
+
+    fn empty() -> Self::Result {
+        Some(WorkspaceSymbolResponse::Flat(Vec::new()))
+    }
+}
+
+fn nested_symbol(symbol: SymbolInformation) -> WorkspaceSymbol {
+    let SymbolInformation {
+        name,
+        kind,
+        tags,
+        location,
+        container_name,
+        ..
+    } = symbol;
+
+    WorkspaceSymbol {
+        name,
+        kind,
+        tags,
+        container_name,
+        location: OneOf::Left(location),
+        data: None,
+    }
+}
+
+impl PartialResultRequest for DocumentDiagnosticRequest {
+    /// The first chunk sent should be the report of the document itself, with the following
+    /// ones adding related documents.
+    type Chunk = DocumentDiagnosticReportResult;
+
+    fn aggregate(chunks: Vec<Self::Chunk>) -> Self::Result {
+        let mut report = None;
+        let mut related_documents = HashMap::new();
+        for chunk in chunks {
+            match chunk {
+                DocumentDiagnosticReportResult::Report(mut chunk_report) => {
+                    related_documents.extend(
+                        related_documents_mut(&mut chunk_report)
+                            .take()
+                            .into_iter()
+                            .flatten(),
+                    );
+                    report.get_or_insert(chunk_report);
+                }
+                DocumentDiagnosticReportResult::Partial(partial_result) => {
+                    related_documents.extend(partial_result.related_documents.into_iter().flatten())
+                }
+            }
+        }
+
+        let mut report = report.unwrap_or_else(|| {
+            DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport::default())
+        });
+        if !related_documents.is_empty() {
+            *related_documents_mut(&mut report) = Some(related_documents);
+        }
+        DocumentDiagnosticReportResult::Report(report)
+    }
+
+    fn empty() -> Self::Result {
+        DocumentDiagnosticReportResult::Partial(Default::default())
+    }
+}
+
+impl PartialResultRequest for WorkspaceDiagnosticRequest {
+    type Chunk = WorkspaceDiagnosticReportPartialResult;
+
+    fn aggregate(chunks: Vec<Self::Chunk>) -> Self::Result {
+        WorkspaceDiagnosticReportResult::Report(WorkspaceDiagnosticReport {
+            items: chunks.into_iter().flat_map(|chunk| chunk.items).collect(),
+        })
+    }
+
+    fn empty() -> Self::Result {
+        WorkspaceDiagnosticReportResult::Report(WorkspaceDiagnosticReport::default())
+    }
+}
+
+fn related_documents_mut(
+    report: &mut DocumentDiagnosticReport,
+) -> &mut Option<HashMap<Url, DocumentDiagnosticReportKind>> {
+    match report {
+        DocumentDiagnosticReport::Full(report) => &mut report.related_documents,
+        DocumentDiagnosticReport::Unchanged(report) => &mut report.related_documents,
+    }
+}
+
+/// Sends the result of a request in chunks as `$/progress` notifications when the client
+/// provided a `partialResultToken`, or otherwise aggregates them into a single result.
+pub struct PartialResults<R: PartialResultRequest> {
+    outbox: Outbox,
+    partial_result_token: Option<ProgressToken>,
+    chunks: Vec<R::Chunk>,
+    streamed: bool,
+}
+
+impl<R: PartialResultRequest> PartialResults<R> {
+    pub fn new(outbox: Outbox, partial_result_token: Option<ProgressToken>) -> Self {
+        Self {
+            outbox,
+            partial_result_token,
+            chunks: Vec::new(),
+            streamed: false,
+        }
+    }
+
+    pub fn send(&mut self, chunk: R::Chunk) {
+        let Some(token) = &self.partial_result_token else {
+            self.chunks.push(chunk);
+            return;
+        };
+
+        let value = serde_json::to_value(chunk).expect("partial result not serializable");
+        self.outbox
+            .send_notification::<PartialResultProgress>(Some(PartialResultParams {
+                token: token.clone(),
+                value,
+            }));
+        self.streamed = true;
+    }
+
+    /// The result to respond with once every chunk has been sent.
+    pub fn finish(self) -> R::Result {
+        match self.streamed {
+            true => R::empty(),
+            false => R::aggregate(self.chunks),
+        }
+    }
+
+    /// Sends every chunk yielded, returning the result to respond with.
+    pub async fn collect(mut self, chunks: impl Stream<Item = R::Chunk>) -> R::Result {
+        let mut chunks = std::pin::pin!(chunks);
+        while let Some(chunk) = chunks.next().await {
+            self.send(chunk)
+        }
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{
+        DocumentDiagnosticReportPartialResult, FullDocumentDiagnosticReport, NumberOrString,
+        Position, Range, UnchangedDocumentDiagnosticReport,
+    };
+
+    use crate::messages::{
+        core::notification::NotificationMessage,
+        groups::{
+            notifications::{AllImplementationNotifications, AllNotifications},
+            AllMessages,
+        },
+    };
+
+    use super::*;
+
+    fn location(line: u32) -> Location {
+        Location {
+            uri: Url::parse("file:///a.rs").unwrap(),
+            range: Range::new(Position::new(line, 0), Position::new(line, 1)),
+        }
+    }
+
+    #[tokio::test]
+    async fn streams_chunks_through_token() {
+        let (outbox, mut outgoing_rx, _pending_responses) = Outbox::new(None);
+        let partial_results =
+            PartialResults::<References>::new(outbox.clone(), Some(NumberOrString::Number(3)));
+
+        let chunks = futures::stream::iter([vec![location(0)], vec![location(1), location(2)]]);
+        assert_eq!(Some(Vec::new()), partial_results.collect(chunks).await);
+
+        for expected_chunk in [vec![location(0)], vec![location(1), location(2)]] {
+            // Roundtrip to check that partial results aren't mistaken for work done progress.
+            let message = outgoing_rx.try_next().unwrap().unwrap();
+            let message =
+                serde_json::from_str::<AllMessages>(&serde_json::to_string(&message).unwrap())
+                    .unwrap();
+            let AllMessages::Notifications(AllNotifications::ImplementationDependent(
+                AllImplementationNotifications::PartialResult(NotificationMessage {
+                    params: Some(params),
+                }),
+            )) = message
+            else {
+                panic!("expected a partial result, got {:?}", message)
+            };
+            assert_eq!(NumberOrString::Number(3), params.token);
+            assert_eq!(serde_json::to_value(expected_chunk).unwrap(), params.value);
+        }
+        assert!(outgoing_rx.try_next().is_err())
+    }
+
+    #[tokio::test]
+    async fn aggregates_chunks_without_token() {
+        let (outbox, mut outgoing_rx, _pending_responses) = Outbox::new(None);
+        let mut partial_results = PartialResults::<References>::new(outbox.clone(), None);
+
+        partial_results.send(vec![location(0)]);
+        partial_results.send(vec![location(1)]);
+        assert_eq!(
+            Some(vec![location(0), location(1)]),
+            partial_results.finish()
+        );
+        assert!(outgoing_rx.try_next().is_err())
+    }
+
+    #[test]
+    fn aggregates_mixed_workspace_symbols() {
+        #[allow(deprecated)]
+        let flat_symbol = SymbolInformation {
+            name: "flat".to_string(),
+            kind: lsp_types::SymbolKind::FUNCTION,
+            tags: None,
+            deprecated: None,
+            location: location(0),
+            container_name: None,
+        };
+        let nested_symbol = nested_symbol(flat_symbol.clone());
+
+        let Some(WorkspaceSymbolResponse::Nested(symbols)) =
+            WorkspaceSymbolRequest::aggregate(vec![
+                WorkspaceSymbolResponse::Nested(vec![nested_symbol.clone()]),
+                WorkspaceSymbolResponse::Flat(vec![flat_symbol]),
+            ])
+        else {
+            panic!("expected nested symbols")
+        };
+        assert_eq!(vec![nested_symbol.clone(), nested_symbol], symbols);
+    }
+
+    #[test]
+    fn aggregates_related_diagnostic_documents() {
+        let related_uri = Url::parse("file:///b.rs").unwrap();
+        let related_report =
+            DocumentDiagnosticReportKind::Unchanged(UnchangedDocumentDiagnosticReport {
+                result_id: "1".to_string(),
+            });
+
+        let DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) =
+            DocumentDiagnosticRequest::aggregate(vec![
+                DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(
+                    RelatedFullDocumentDiagnosticReport {
+                        related_documents: None,
+                        full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                            result_id: Some("2".to_string()),
+                            items: Vec::new(),
+                        },
+                    },
+                )),
+                DocumentDiagnosticReportResult::Partial(DocumentDiagnosticReportPartialResult {
+                    related_documents: Some(HashMap::from([(
+                        related_uri.clone(),
+                        related_report.clone(),
+                    )])),
+                }),
+            ])
+        else {
+            panic!("expected a full report")
+        };
+        assert_eq!(
+            Some("2"),
+            report.full_document_diagnostic_report.result_id.as_deref()
+        );
+        assert_eq!(
+            Some(HashMap::from([(related_uri, related_report)])),
+            report.related_documents
+        );
+    }
+}
@@ -0,0 +1,333 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use lsp_types::{
+    notification::Progress, request::WorkDoneProgressCreate, NumberOrString, ProgressParams,
+    ProgressParamsValue, ProgressToken, WorkDoneProgressBegin, WorkDoneProgressCreateParams,
+    WorkDoneProgressEnd, WorkDoneProgressReport,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    messages::groups::{
+        notifications::{AllNotifications, AllServerNotifications},
+        requests::{AllRequests, AllServerRequests},
+        AllMessages,
+    },
+    outbox::Outbox,
+};
+
+use super::filter::MessageHook;
+
+const ACTIVE_PROGRESS_POISONED: &str = "active progress lock poisoned";
+
+#[derive(Default)]
+struct ProgressState {
+    /// Set by `InitializeParams.capabilities.window.workDoneProgress`.
+    client_creates_progress: AtomicBool,
+    next_token: AtomicU64,
+    /// Cancellation of the progress currently reported, by token.
+    active: Mutex<HashMap<ProgressToken, CancellationToken>>,
+}
+
+/// Hands out [`ProgressReporter`]s reporting to the client of a [`Service`](super::Service).
+#[derive(Clone)]
+pub struct WorkDoneProgress {
+    outbox: Outbox,
+    state: Arc<ProgressState>,
+}
+
+impl WorkDoneProgress {
+    pub(crate) fn new(outbox: Outbox) -> Self {
+        Self {
+            outbox,
+            state: Arc::default(),
+        }
+    }
+
+    /// Reports through the `workDoneToken` of a request when given, or otherwise through a token
+    /// created with `window/workDoneProgress/create` if the client supports it.
+    pub fn reporter(&self, work_done_token: Option<ProgressToken>) -> ProgressReporter {
+        ProgressReporter {
+            progress: self.clone(),
+            work_done_token,
+            begun_token: None,
+            cancellation_token: CancellationToken::new(),
+        }
+    }
+
+    fn send(&self, token: ProgressToken, value: lsp_types::WorkDoneProgress) {
+        self.outbox
+            .send_notification::<Progress>(Some(ProgressParams {
+                token,
+                value: ProgressParamsValue::WorkDone(value),
+            }))
+    }
+}
+
+impl MessageHook for WorkDoneProgress {
+    fn inspect_incoming(&mut self, message: &AllMessages) {
+        match message {
+            AllMessages::Requests(AllRequests::Server(AllServerRequests::Initialize(request))) => {
+                let client_creates_progress = request
+                    .params
+                    .as_ref()
+                    .and_then(|params| params.capabilities.window.as_ref())
+                    .and_then(|window| window.work_done_progress)
+                    .unwrap_or(false);
+                self.state
+                    .client_creates_progress
+                    .store(client_creates_progress, Ordering::Relaxed)
+            }
+            AllMessages::Notifications(AllNotifications::Server(
+                AllServerNotifications::WorkDoneProgressCancel(notification),
+            )) => {
+                let Some(params) = &notification.params else {
+                    return;
+                };
+                if let Some(cancellation_token) = self
+                    .state
+                    .active
+                    .lock()
+                    .expect(ACTIVE_PROGRESS_POISONED)
+                    .get(&params.token)
+                {
+                    cancellation_token.cancel()
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Reports the progress of a single operation, ending it when dropped.
+///
+/// Reporting does nothing until the progress has begun, which doesn't happen when the client
+/// provided no token and can't create one.
+pub struct ProgressReporter {
+    progress: WorkDoneProgress,
+    work_done_token: Option<ProgressToken>,
+    begun_token: Option<ProgressToken>,
+    cancellation_token: CancellationToken,
+}
+
+impl ProgressReporter {
+    /// Returns whether the progress has begun, awaiting the creation of a token if needed.
+    pub async fn begin(&mut self, title: impl Into<String>) -> bool {
+        if self.begun_token.is_some() {
+            return true;
+        }
+
+        let token = match self.work_done_token.take() {
+            Some(token) => token,
+            None if self
+                .progress
+                .state
+                .client_creates_progress
+                .load(Ordering::Relaxed) =>
+            {
+                let token_number = self
+                    .progress
+                    .state
+                    .next_token
+                    .fetch_add(1, Ordering::Relaxed);
+                let token = NumberOrString::String(format!("spique-progress-{}", token_number));
+                let create_params = WorkDoneProgressCreateParams {
+                    token: token.clone(),
+                };
+                if let Err(err) = self
+                    .progress
+                    .outbox
+                    .send_request::<WorkDoneProgressCreate>(Some(create_params))
+                    .await
+                {
+                    tracing::warn!(%err, "Unable to create work done progress.");
+                    return false;
+                }
+                token
+            }
+            None => return false,
+        };
+
+        self.progress
+            .state
+            .active
+            .lock()
+            .expect(ACTIVE_PROGRESS_POISONED)
+            .insert(token.clone(), self.cancellation_token.clone());
+        self.progress.send(
+            token.clone(),
+            lsp_types::WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: title.into(),
+                cancellable: Some(true),
+                message: None,
+                percentage: None,
+            }),
+        );
+        self.begun_token = Some(token);
+        true
+    }
+
+    pub fn report(&self, percentage: Option<u32>, message: Option<String>) {
+        if let Some(token) = &self.begun_token {
+            self.progress.send(
+                token.clone(),
+                lsp_types::WorkDoneProgress::Report(WorkDoneProgressReport {
+                    cancellable: None,
+                    message,
+                    percentage,
+                }),
+            )
+        }
+    }
+
+    /// Ends the progress, as does dropping the reporter.
+    pub fn finish(self) {}
+
+    /// Cancelled once the user cancels the progress from the client.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation_token.clone()
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        if let Some(token) = self.begun_token.take() {
+            self.progress
+                .state
+                .active
+                .lock()
+                .expect(ACTIVE_PROGRESS_POISONED)
+                .remove(&token);
+            self.progress.send(
+                token,
+                lsp_types::WorkDoneProgress::End(WorkDoneProgressEnd { message: None }),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use lsp_types::{
+        notification::WorkDoneProgressCancel, ClientCapabilities, WindowClientCapabilities,
+        WorkDoneProgressCancelParams,
+    };
+
+    use crate::messages::{
+        core::{notification::NotificationMessage, response::UntypedResponseMessage},
+        groups::{
+            notifications::AllImplementationNotifications,
+            requests::{tests::initialize_mock, AllClientRequests},
+        },
+    };
+
+    use super::*;
+
+    fn progress_value(message: Option<AllMessages>) -> lsp_types::WorkDoneProgress {
+        match message {
+            Some(AllMessages::Notifications(AllNotifications::ImplementationDependent(
+                AllImplementationNotifications::Progress(NotificationMessage {
+                    params: Some(params),
+                }),
+            ))) => match params.value {
+                ProgressParamsValue::WorkDone(value) => value,
+            },
+            message => panic!("expected a progress notification, got {:?}", message),
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_through_client_token() {
+        let (outbox, mut outgoing_rx, _pending_responses) = Outbox::new(None);
+        let progress = WorkDoneProgress::new(outbox);
+
+        let mut reporter = progress.reporter(Some(NumberOrString::Number(7)));
+        assert!(reporter.begin("Indexing").await);
+        reporter.report(Some(50), None);
+        reporter.finish();
+
+        assert!(matches!(
+            progress_value(outgoing_rx.try_next().unwrap()),
+            lsp_types::WorkDoneProgress::Begin(WorkDoneProgressBegin { title, .. }) if title == "Indexing"
+        ));
+        assert!(matches!(
+            progress_value(outgoing_rx.try_next().unwrap()),
+            lsp_types::WorkDoneProgress::Report(WorkDoneProgressReport {
+                percentage: Some(50),
+                ..
+            })
+        ));
+        assert!(matches!(
+            progress_value(outgoing_rx.try_next().unwrap()),
+            lsp_types::WorkDoneProgress::End(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn skips_reporting_without_token() {
+        let (outbox, mut outgoing_rx, _pending_responses) = Outbox::new(None);
+        let progress = WorkDoneProgress::new(outbox);
+
+        let mut reporter = progress.reporter(None);
+        assert!(!reporter.begin("Indexing").await);
+        reporter.report(Some(50), None);
+        drop(reporter);
+
+        assert!(outgoing_rx.try_next().is_err())
+    }
+
+    #[tokio::test]
+    async fn creates_token_and_signals_cancellation() {
+        let (outbox, mut outgoing_rx, pending_responses) = Outbox::new(None);
+        let mut progress = WorkDoneProgress::new(outbox);
+        progress.inspect_incoming(&initialize_mock(ClientCapabilities {
+            window: Some(WindowClientCapabilities {
+                work_done_progress: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }));
+
+        let mut reporter = progress.reporter(None);
+        let begin_task =
+            tokio::spawn(async move { reporter.begin("Indexing").await.then_some(reporter) });
+
+        let create_request = outgoing_rx.next().await.unwrap();
+        let AllMessages::Requests(AllRequests::Client(AllClientRequests::WorkDoneProgressCreate(
+            request,
+        ))) = create_request
+        else {
+            panic!("expected a create request, got {:?}", create_request)
+        };
+        pending_responses
+            .resolve(UntypedResponseMessage {
+                id: request.id.clone().into(),
+                kind: Ok(serde_json::Value::Null),
+            })
+            .unwrap();
+        let reporter = begin_task.await.unwrap().unwrap();
+        assert!(matches!(
+            progress_value(outgoing_rx.try_next().unwrap()),
+            lsp_types::WorkDoneProgress::Begin(_)
+        ));
+
+        progress.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<WorkDoneProgressCancel> {
+                params: Some(WorkDoneProgressCancelParams {
+                    token: request.params.unwrap().token,
+                }),
+            }
+            .into(),
+        ));
+        assert!(reporter.cancellation_token().is_cancelled());
+        drop(reporter);
+        assert!(progress.state.active.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,497 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use futures::{Stream, StreamExt};
+use lsp_types::{
+    request::{WorkspaceDiagnosticRefresh, WorkspaceDiagnosticRequest},
+    Diagnostic, DocumentDiagnosticParams, DocumentDiagnosticReport, DocumentDiagnosticReportKind,
+    DocumentDiagnosticReportResult, FullDocumentDiagnosticReport,
+    RelatedFullDocumentDiagnosticReport, RelatedUnchangedDocumentDiagnosticReport,
+    UnchangedDocumentDiagnosticReport, Url, WorkspaceDiagnosticParams,
+    WorkspaceDiagnosticReportPartialResult, WorkspaceDiagnosticReportResult,
+    WorkspaceDocumentDiagnosticReport, WorkspaceFullDocumentDiagnosticReport,
+    WorkspaceUnchangedDocumentDiagnosticReport,
+};
+
+use crate::{
+    messages::groups::{
+        notifications::{AllNotifications, AllServerNotifications},
+        requests::{AllRequests, AllServerRequests},
+        AllMessages,
+    },
+    outbox::{Outbox, RequestError},
+};
+
+use super::{filter::MessageHook, partial::PartialResults};
+
+const REPORTS_POISONED: &str = "diagnostic reports lock poisoned";
+
+/// The `identifier` of the diagnostic provider pulled from, along with the document.
+type ReportKey = (Option<String>, Url);
+
+#[derive(Default)]
+struct ReportState {
+    /// Set by `InitializeParams.capabilities.workspace.diagnostic.refreshSupport`.
+    client_refreshes: bool,
+    next_result_id: u64,
+    /// The diagnostics last reported for each document and provider. Dropped once the document
+    /// is closed, and all of them on refresh.
+    reports: HashMap<ReportKey, CachedReport>,
+}
+
+struct CachedReport {
+    result_id: String,
+    /// Document version the diagnostics were computed against, if known.
+    version: Option<i64>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Builds the reports answering `textDocument/diagnostic` and `workspace/diagnostic` requests
+/// of a [`Service`](super::Service), reporting documents as `unchanged` when the client already
+/// has their diagnostics for the same document version, or diagnostics equal to them.
+#[derive(Clone)]
+pub struct PullDiagnostics {
+    outbox: Outbox,
+    state: Arc<Mutex<ReportState>>,
+}
+
+impl PullDiagnostics {
+    pub(crate) fn new(outbox: Outbox) -> Self {
+        Self {
+            outbox,
+            state: Arc::default(),
+        }
+    }
+
+    /// Whether the client already has the diagnostics of the document at `version`, as
+    /// reported under `previous_result_id`. The backend can then skip computing them, as the
+    /// diagnostics passed in for such a document aren't looked at and can be left empty.
+    pub fn is_unchanged(
+        &self,
+        identifier: Option<&str>,
+        uri: &Url,
+        previous_result_id: Option<&str>,
+        version: i32,
+    ) -> bool {
+        self.state.lock().expect(REPORTS_POISONED).is_unchanged(
+            identifier,
+            uri,
+            previous_result_id,
+            Some(i64::from(version)),
+        )
+    }
+
+    /// The diagnostics were computed against `version` of the document, if known. Related
+    /// documents are compared against the diagnostics last reported for them, since the client
+    /// doesn't send their previous result ids.
+    pub fn document_report(
+        &self,
+        params: &DocumentDiagnosticParams,
+        version: Option<i32>,
+        diagnostics: Vec<Diagnostic>,
+        related_documents: HashMap<Url, Vec<Diagnostic>>,
+    ) -> DocumentDiagnosticReportResult {
+        let mut state = self.state.lock().expect(REPORTS_POISONED);
+        let identifier = params.identifier.as_deref();
+        let related_documents = related_documents
+            .into_iter()
+            .map(|(uri, diagnostics)| {
+                let previous_result_id = state
+                    .reports
+                    .get(&(identifier.map(str::to_string), uri.clone()))
+                    .map(|report| report.result_id.clone());
+                let report = state.report(
+                    identifier,
+                    &uri,
+                    previous_result_id.as_deref(),
+                    None,
+                    diagnostics,
+                );
+                (uri, report)
+            })
+            .collect::<HashMap<_, _>>();
+        let related_documents = (!related_documents.is_empty()).then_some(related_documents);
+
+        let report = match state.report(
+            identifier,
+            &params.text_document.uri,
+            params.previous_result_id.as_deref(),
+            version.map(i64::from),
+            diagnostics,
+        ) {
+            DocumentDiagnosticReportKind::Full(full_document_diagnostic_report) => {
+                DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+                    related_documents,
+                    full_document_diagnostic_report,
+                })
+            }
+            DocumentDiagnosticReportKind::Unchanged(unchanged_document_diagnostic_report) => {
+                DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+                    related_documents,
+                    unchanged_document_diagnostic_report,
+                })
+            }
+        };
+        DocumentDiagnosticReportResult::Report(report)
+    }
+
+    /// Reports each document yielded along with its version, streamed through the
+    /// `partialResultToken` when given. Returns the result to respond with.
+    pub async fn workspace_report(
+        &self,
+        params: &WorkspaceDiagnosticParams,
+        documents: impl Stream<Item = (Url, Option<i64>, Vec<Diagnostic>)>,
+    ) -> WorkspaceDiagnosticReportResult {
+        let previous_result_ids = params
+            .previous_result_ids
+            .iter()
+            .map(|previous| (&previous.uri, previous.value.as_str()))
+            .collect::<HashMap<_, _>>();
+
+        let chunks = documents.map(|(uri, version, diagnostics)| {
+            let report = self.state.lock().expect(REPORTS_POISONED).report(
+                params.identifier.as_deref(),
+                &uri,
+                previous_result_ids.get(&uri).copied(),
+                version,
+                diagnostics,
+            );
+            let item = match report {
+                DocumentDiagnosticReportKind::Full(full_document_diagnostic_report) => {
+                    WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+                        uri,
+                        version,
+                        full_document_diagnostic_report,
+                    })
+                }
+                DocumentDiagnosticReportKind::Unchanged(unchanged_document_diagnostic_report) => {
+                    WorkspaceDocumentDiagnosticReport::Unchanged(
+                        WorkspaceUnchangedDocumentDiagnosticReport {
+                            uri,
+                            version,
+                            unchanged_document_diagnostic_report,
+                        },
+                    )
+                }
+            };
+            WorkspaceDiagnosticReportPartialResult { items: vec![item] }
+        });
+
+        PartialResults::<WorkspaceDiagnosticRequest>::new(
+            self.outbox.clone(),
+            params.partial_result_params.partial_result_token.clone(),
+        )
+        .collect(chunks)
+        .await
+    }
+
+    /// Asks the client to pull diagnostics again after they have been invalidated, doing
+    /// nothing if it doesn't support `workspace/diagnostic/refresh`. Documents are no longer
+    /// reported as `unchanged` for their version alone afterwards.
+    pub async fn refresh(&self) -> Result<(), RequestError> {
+        let client_refreshes = {
+            let mut state = self.state.lock().expect(REPORTS_POISONED);
+            state.reports.clear();
+            state.client_refreshes
+        };
+        if !client_refreshes {
+            return Ok(());
+        }
+        self.outbox
+            .send_request::<WorkspaceDiagnosticRefresh>(None)
+            .await
+    }
+}
+
+impl ReportState {
+    fn is_unchanged(
+        &self,
+        identifier: Option<&str>,
+        uri: &Url,
+        previous_result_id: Option<&str>,
+        version: Option<i64>,
+    ) -> bool {
+        self.reports
+            .get(&(identifier.map(str::to_string), uri.clone()))
+            .is_some_and(|report| {
+                previous_result_id == Some(report.result_id.as_str())
+                    && version.is_some()
+                    && version == report.version
+            })
+    }
+
+    fn report(
+        &mut self,
+        identifier: Option<&str>,
+        uri: &Url,
+        previous_result_id: Option<&str>,
+        version: Option<i64>,
+        diagnostics: Vec<Diagnostic>,
+    ) -> DocumentDiagnosticReportKind {
+        let key = (identifier.map(str::to_string), uri.clone());
+        let unchanged = self.is_unchanged(identifier, uri, previous_result_id, version);
+        if let Some(report) = self.reports.get_mut(&key) {
+            if unchanged
+                || (previous_result_id == Some(report.result_id.as_str())
+                    && report.diagnostics == diagnostics)
+            {
+                // Equal diagnostics carry over to the new version.
+                if version.is_some() {
+                    report.version = version
+                }
+                return DocumentDiagnosticReportKind::Unchanged(
+                    UnchangedDocumentDiagnosticReport {
+                        result_id: report.result_id.clone(),
+                    },
+                );
+            }
+        }
+
+        let result_id = self.next_result_id.to_string();
+        self.next_result_id += 1;
+        self.reports.insert(
+            key,
+            CachedReport {
+                result_id: result_id.clone(),
+                version,
+                diagnostics: diagnostics.clone(),
+            },
+        );
+        DocumentDiagnosticReportKind::Full(FullDocumentDiagnosticReport {
+            result_id: Some(result_id),
+            items: diagnostics,
+        })
+    }
+}
+
+impl MessageHook for PullDiagnostics {
+    fn inspect_incoming(&mut self, message: &AllMessages) {
+        match message {
+            AllMessages::Requests(AllRequests::Server(AllServerRequests::Initialize(request))) => {
+                let client_refreshes = request
+                    .params
+                    .as_ref()
+                    .and_then(|params| params.capabilities.workspace.as_ref())
+                    .and_then(|workspace| workspace.diagnostic.as_ref())
+                    .and_then(|diagnostic| diagnostic.refresh_support)
+                    .unwrap_or(false);
+                self.state.lock().expect(REPORTS_POISONED).client_refreshes = client_refreshes
+            }
+            AllMessages::Notifications(AllNotifications::Server(
+                AllServerNotifications::DidCloseTextDocument(notification),
+            )) => {
+                if let Some(params) = &notification.params {
+                    self.state
+                        .lock()
+                        .expect(REPORTS_POISONED)
+                        .reports
+                        .retain(|(_, uri), _| *uri != params.text_document.uri)
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{
+        notification::DidCloseTextDocument, DidCloseTextDocumentParams, NumberOrString,
+        PartialResultParams, PreviousResultId, TextDocumentIdentifier, WorkDoneProgressParams,
+    };
+
+    use crate::{
+        messages::core::notification::NotificationMessage,
+        service::{diagnostics::tests::diagnostic_mock, partial::PartialResultRequest},
+    };
+
+    use super::*;
+
+    fn document_params_mock(
+        uri: &Url,
+        identifier: Option<&str>,
+        previous_result_id: Option<&str>,
+    ) -> DocumentDiagnosticParams {
+        DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            identifier: identifier.map(str::to_string),
+            previous_result_id: previous_result_id.map(str::to_string),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        }
+    }
+
+    fn full_result_id(result: DocumentDiagnosticReportResult) -> String {
+        let DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) = result
+        else {
+            panic!("expected a full report, got {:?}", result)
+        };
+        report.full_document_diagnostic_report.result_id.unwrap()
+    }
+
+    #[test]
+    fn reports_unchanged_documents() {
+        let (outbox, _outgoing_rx, _pending_responses) = Outbox::new(None);
+        let pull_diagnostics = PullDiagnostics::new(outbox);
+        let uri = Url::parse("file:///a.rs").unwrap();
+
+        let result_id = full_result_id(pull_diagnostics.document_report(
+            &document_params_mock(&uri, None, None),
+            None,
+            vec![diagnostic_mock("a")],
+            HashMap::new(),
+        ));
+        assert_eq!(
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(
+                RelatedUnchangedDocumentDiagnosticReport {
+                    related_documents: None,
+                    unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                        result_id: result_id.clone()
+                    },
+                }
+            )),
+            pull_diagnostics.document_report(
+                &document_params_mock(&uri, None, Some(&result_id)),
+                None,
+                vec![diagnostic_mock("a")],
+                HashMap::new(),
+            )
+        );
+
+        let changed_result_id = full_result_id(pull_diagnostics.document_report(
+            &document_params_mock(&uri, None, Some(&result_id)),
+            None,
+            vec![diagnostic_mock("b")],
+            HashMap::new(),
+        ));
+        assert_ne!(result_id, changed_result_id);
+    }
+
+    #[tokio::test]
+    async fn reports_unchanged_versions_before_computation() {
+        let (outbox, _outgoing_rx, _pending_responses) = Outbox::new(None);
+        let pull_diagnostics = PullDiagnostics::new(outbox);
+        let uri = Url::parse("file:///a.rs").unwrap();
+
+        let result_id = full_result_id(pull_diagnostics.document_report(
+            &document_params_mock(&uri, None, None),
+            Some(1),
+            vec![diagnostic_mock("a")],
+            HashMap::new(),
+        ));
+        assert!(pull_diagnostics.is_unchanged(None, &uri, Some(&result_id), 1));
+        assert!(!pull_diagnostics.is_unchanged(None, &uri, Some(&result_id), 2));
+        assert!(!pull_diagnostics.is_unchanged(Some("b"), &uri, Some(&result_id), 1));
+        assert!(!pull_diagnostics.is_unchanged(None, &uri, None, 1));
+        assert!(matches!(
+            pull_diagnostics.document_report(
+                &document_params_mock(&uri, None, Some(&result_id)),
+                Some(1),
+                Vec::new(),
+                HashMap::new(),
+            ),
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(_))
+        ));
+
+        pull_diagnostics.refresh().await.unwrap();
+        assert!(!pull_diagnostics.is_unchanged(None, &uri, Some(&result_id), 1));
+    }
+
+    #[test]
+    fn keeps_reports_per_identifier_until_closed() {
+        let (outbox, _outgoing_rx, _pending_responses) = Outbox::new(None);
+        let mut pull_diagnostics = PullDiagnostics::new(outbox);
+        let uri = Url::parse("file:///a.rs").unwrap();
+
+        let result_id = full_result_id(pull_diagnostics.document_report(
+            &document_params_mock(&uri, Some("a"), None),
+            None,
+            vec![diagnostic_mock("a")],
+            HashMap::new(),
+        ));
+        full_result_id(pull_diagnostics.document_report(
+            &document_params_mock(&uri, Some("b"), None),
+            None,
+            vec![diagnostic_mock("b")],
+            HashMap::new(),
+        ));
+        assert!(matches!(
+            pull_diagnostics.document_report(
+                &document_params_mock(&uri, Some("a"), Some(&result_id)),
+                None,
+                vec![diagnostic_mock("a")],
+                HashMap::new(),
+            ),
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(_))
+        ));
+
+        pull_diagnostics.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<DidCloseTextDocument> {
+                params: Some(DidCloseTextDocumentParams {
+                    text_document: TextDocumentIdentifier { uri: uri.clone() },
+                }),
+            }
+            .into(),
+        ));
+        assert!(pull_diagnostics
+            .state
+            .lock()
+            .expect(REPORTS_POISONED)
+            .reports
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn streams_workspace_reports() {
+        let (outbox, mut outgoing_rx, _pending_responses) = Outbox::new(None);
+        let pull_diagnostics = PullDiagnostics::new(outbox.clone());
+        let uri = Url::parse("file:///a.rs").unwrap();
+        let result_id = full_result_id(pull_diagnostics.document_report(
+            &document_params_mock(&uri, None, None),
+            None,
+            Vec::new(),
+            HashMap::new(),
+        ));
+
+        let params = WorkspaceDiagnosticParams {
+            identifier: None,
+            previous_result_ids: vec![PreviousResultId {
+                uri: uri.clone(),
+                value: result_id,
+            }],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams {
+                partial_result_token: Some(NumberOrString::Number(1)),
+            },
+        };
+        let documents = futures::stream::iter([
+            (uri, Some(2), Vec::new()),
+            (
+                Url::parse("file:///b.rs").unwrap(),
+                None,
+                vec![diagnostic_mock("b")],
+            ),
+        ]);
+
+        assert_eq!(
+            WorkspaceDiagnosticRequest::empty(),
+            pull_diagnostics.workspace_report(&params, documents).await
+        );
+        let mut kinds = Vec::new();
+        while let Ok(Some(AllMessages::Notifications(notification))) = outgoing_rx.try_next() {
+            let value = serde_json::to_value(notification).unwrap();
+            kinds.push(value["params"]["value"]["items"][0]["kind"].clone());
+        }
+        assert_eq!(vec!["unchanged", "full"], kinds);
+    }
+
+    #[tokio::test]
+    async fn skips_refresh_without_client_support() {
+        let (outbox, mut outgoing_rx, _pending_responses) = Outbox::new(None);
+        let pull_diagnostics = PullDiagnostics::new(outbox.clone());
+        pull_diagnostics.refresh().await.unwrap();
+        assert!(outgoing_rx.try_next().is_err());
+    }
+}
@@ -0,0 +1,406 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use derive_more::{Display, From};
+use lsp_types::{
+    notification::{DidChangeWatchedFiles, Notification},
+    request::{Formatting, RegisterCapability, Request, UnregisterCapability},
+    DidChangeWatchedFilesRegistrationOptions, DocumentSelector, FileSystemWatcher, Registration,
+    RegistrationParams, TextDocumentRegistrationOptions, Unregistration, UnregistrationParams,
+};
+use serde_json::Value;
+
+use crate::{
+    messages::groups::{
+        requests::{AllRequests, AllServerRequests},
+        AllMessages,
+    },
+    outbox::{Outbox, RequestError},
+};
+
+use super::filter::MessageHook;
+
+const REGISTRATIONS_POISONED: &str = "registrations lock poisoned";
+const DYNAMIC_REGISTRATION_KEY: &str = "dynamicRegistration";
+
+#[derive(Debug, Display, From)]
+pub enum RegistrationError {
+    #[display(fmt = "client doesn't support dynamic registration of {}", _0)]
+    #[from(ignore)]
+    Unsupported(String),
+    #[display(fmt = "no registration with id {}", _0)]
+    #[from(ignore)]
+    NotRegistered(String),
+    Request(RequestError),
+}
+
+#[derive(Default)]
+struct RegistrationState {
+    /// `InitializeParams.capabilities`, kept as JSON for looking up capabilities by method.
+    client_capabilities: Mutex<Value>,
+    next_id: AtomicU64,
+    registered: Mutex<HashMap<String, Registration>>,
+}
+
+/// Registers capabilities with the client of a [`Service`](super::Service) after
+/// initialization, unregistering whatever is left once the client asks for shutdown.
+///
+/// Unregistering on shutdown is best effort: the `shutdown` response isn't held back for it, so
+/// a client exiting right away may never answer or even receive the request.
+#[derive(Clone)]
+pub struct DynamicRegistrations {
+    outbox: Outbox,
+    state: Arc<RegistrationState>,
+}
+
+impl DynamicRegistrations {
+    pub(crate) fn new(outbox: Outbox) -> Self {
+        Self {
+            outbox,
+            state: Arc::default(),
+        }
+    }
+
+    /// Whether the client declared `dynamicRegistration` support for the capability behind the
+    /// method.
+    pub fn supports(&self, method: &str) -> bool {
+        let mut path = capability_path(method);
+        path.push(DYNAMIC_REGISTRATION_KEY);
+        self.client_flag(&path)
+    }
+
+    /// Looks up a flag of `InitializeParams.capabilities` by its path, `false` when left out.
+    pub(crate) fn client_flag(&self, path: &[&str]) -> bool {
+        let client_capabilities = self
+            .state
+            .client_capabilities
+            .lock()
+            .expect(REGISTRATIONS_POISONED);
+
+        path.iter()
+            .try_fold(&*client_capabilities, |capabilities, key| {
+                capabilities.get(key)
+            })
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    }
+
+    /// Returns the generated id of the registration, used for unregistering it.
+    pub async fn register(
+        &self,
+        method: &str,
+        register_options: Option<Value>,
+    ) -> Result<String, RegistrationError> {
+        let id = self.next_id();
+        self.register_with_id(id.clone(), method, register_options)
+            .await?;
+        Ok(id)
+    }
+
+    /// An id for [`register_with_id`](Self::register_with_id), for callers needing it before the
+    /// registration goes through.
+    pub(crate) fn next_id(&self) -> String {
+        format!(
+            "spique-registration-{}",
+            self.state.next_id.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+
+    pub(crate) async fn register_with_id(
+        &self,
+        id: String,
+        method: &str,
+        register_options: Option<Value>,
+    ) -> Result<(), RegistrationError> {
+        if !self.supports(method) {
+            return Err(RegistrationError::Unsupported(method.to_string()));
+        }
+
+        let registration = Registration {
+            id: id.clone(),
+            method: method.to_string(),
+            register_options,
+        };
+        self.outbox
+            .send_request::<RegisterCapability>(Some(RegistrationParams {
+                registrations: vec![registration.clone()],
+            }))
+            .await?;
+
+        self.state
+            .registered
+            .lock()
+            .expect(REGISTRATIONS_POISONED)
+            .insert(id, registration);
+        Ok(())
+    }
+
+    pub async fn unregister(&self, id: &str) -> Result<(), RegistrationError> {
+        let registration = self
+            .state
+            .registered
+            .lock()
+            .expect(REGISTRATIONS_POISONED)
+            .remove(id)
+            .ok_or_else(|| RegistrationError::NotRegistered(id.to_string()))?;
+
+        self.send_unregistrations(vec![registration]).await
+    }
+
+    pub async fn unregister_all(&self) -> Result<(), RegistrationError> {
+        let registrations = self
+            .state
+            .registered
+            .lock()
+            .expect(REGISTRATIONS_POISONED)
+            .drain()
+            .map(|(_, registration)| registration)
+            .collect::<Vec<_>>();
+
+        if registrations.is_empty() {
+            return Ok(());
+        }
+        self.send_unregistrations(registrations).await
+    }
+
+    /// The registrations not yet unregistered.
+    pub fn registered(&self) -> Vec<Registration> {
+        self.state
+            .registered
+            .lock()
+            .expect(REGISTRATIONS_POISONED)
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub async fn register_file_watchers(
+        &self,
+        watchers: Vec<FileSystemWatcher>,
+    ) -> Result<String, RegistrationError> {
+        let register_options = DidChangeWatchedFilesRegistrationOptions { watchers };
+        self.register(
+            DidChangeWatchedFiles::METHOD,
+            Some(serde_json::to_value(register_options).expect("serializable options")),
+        )
+        .await
+    }
+
+    pub async fn register_formatting(
+        &self,
+        document_selector: DocumentSelector,
+    ) -> Result<String, RegistrationError> {
+        let register_options = TextDocumentRegistrationOptions {
+            document_selector: Some(document_selector),
+        };
+        self.register(
+            Formatting::METHOD,
+            Some(serde_json::to_value(register_options).expect("serializable options")),
+        )
+        .await
+    }
+
+    async fn send_unregistrations(
+        &self,
+        registrations: Vec<Registration>,
+    ) -> Result<(), RegistrationError> {
+        let unregisterations = registrations
+            .into_iter()
+            .map(|registration| Unregistration {
+                id: registration.id,
+                method: registration.method,
+            })
+            .collect();
+        self.outbox
+            .send_request::<UnregisterCapability>(Some(UnregistrationParams { unregisterations }))
+            .await?;
+        Ok(())
+    }
+}
+
+impl MessageHook for DynamicRegistrations {
+    fn inspect_incoming(&mut self, message: &AllMessages) {
+        match message {
+            AllMessages::Requests(AllRequests::Server(AllServerRequests::Initialize(request))) => {
+                let client_capabilities = request
+                    .params
+                    .as_ref()
+                    .and_then(|params| serde_json::to_value(&params.capabilities).ok())
+                    .unwrap_or_default();
+                *self
+                    .state
+                    .client_capabilities
+                    .lock()
+                    .expect(REGISTRATIONS_POISONED) = client_capabilities
+            }
+            AllMessages::Requests(AllRequests::Server(AllServerRequests::Shutdown(_))) => {
+                // Not awaited by the shutdown response, see the type docs.
+                let registrations = self.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = registrations.unregister_all().await {
+                        tracing::debug!(%err, "Unable to unregister capabilities on shutdown.")
+                    }
+                });
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The client capability holding the `dynamicRegistration` flag of the method, such as
+/// `textDocument.formatting` for `textDocument/formatting`.
+fn capability_path(method: &str) -> Vec<&str> {
+    match method {
+        "textDocument/didOpen"
+        | "textDocument/didChange"
+        | "textDocument/willSave"
+        | "textDocument/willSaveWaitUntil"
+        | "textDocument/didSave"
+        | "textDocument/didClose" => vec!["textDocument", "synchronization"],
+        "notebookDocument/sync" => vec!["notebookDocument", "synchronization"],
+        "workspace/didCreateFiles"
+        | "workspace/willCreateFiles"
+        | "workspace/didRenameFiles"
+        | "workspace/willRenameFiles"
+        | "workspace/didDeleteFiles"
+        | "workspace/willDeleteFiles" => vec!["workspace", "fileOperations"],
+        method => method.split('/').collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{channel::mpsc::UnboundedReceiver, StreamExt};
+    use lsp_types::{
+        request::Shutdown, ClientCapabilities, DocumentFilter,
+        DynamicRegistrationClientCapabilities, NumberOrString, TextDocumentClientCapabilities,
+    };
+
+    use crate::{
+        messages::{
+            core::{
+                request::RequestMessage,
+                response::{ResponseId, UntypedResponseMessage},
+                LspRequest,
+            },
+            groups::requests::{tests::initialize_mock, AllClientRequests},
+        },
+        outbox::PendingResponses,
+    };
+
+    use super::*;
+
+    fn registrations_mock() -> (
+        DynamicRegistrations,
+        UnboundedReceiver<AllMessages>,
+        PendingResponses,
+    ) {
+        let (outbox, outgoing_rx, pending_responses) = Outbox::new(None);
+        let mut registrations = DynamicRegistrations::new(outbox);
+        registrations.inspect_incoming(&initialize_mock(ClientCapabilities {
+            text_document: Some(TextDocumentClientCapabilities {
+                formatting: Some(DynamicRegistrationClientCapabilities {
+                    dynamic_registration: Some(true),
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }));
+        (registrations, outgoing_rx, pending_responses)
+    }
+
+    async fn resolve_next_request(
+        outgoing_rx: &mut UnboundedReceiver<AllMessages>,
+        pending_responses: &PendingResponses,
+    ) -> AllClientRequests {
+        let AllMessages::Requests(AllRequests::Client(request)) = outgoing_rx.next().await.unwrap()
+        else {
+            panic!("expected a client request")
+        };
+        pending_responses
+            .resolve(UntypedResponseMessage {
+                id: ResponseId::from(request.request_id().clone()),
+                kind: Ok(Value::Null),
+            })
+            .unwrap();
+        request
+    }
+
+    #[test]
+    fn checks_client_support() {
+        let (registrations, _outgoing_rx, _pending_responses) = registrations_mock();
+        assert!(registrations.supports(Formatting::METHOD));
+        assert!(!registrations.supports(DidChangeWatchedFiles::METHOD));
+        assert!(!registrations.supports("textDocument/didOpen"));
+    }
+
+    #[tokio::test]
+    async fn rejects_unsupported_registrations() {
+        let (registrations, mut outgoing_rx, _pending_responses) = registrations_mock();
+        assert!(matches!(
+            registrations.register_file_watchers(Vec::new()).await,
+            Err(RegistrationError::Unsupported(_))
+        ));
+        assert!(outgoing_rx.try_next().is_err());
+    }
+
+    #[tokio::test]
+    async fn tracks_registrations_until_unregistered() {
+        let (registrations, mut outgoing_rx, pending_responses) = registrations_mock();
+        let document_selector = vec![DocumentFilter {
+            language: Some("rust".to_string()),
+            scheme: None,
+            pattern: None,
+        }];
+
+        let register_task = tokio::spawn({
+            let registrations = registrations.clone();
+            async move { registrations.register_formatting(document_selector).await }
+        });
+        let AllClientRequests::RegisterCapability(request) =
+            resolve_next_request(&mut outgoing_rx, &pending_responses).await
+        else {
+            panic!("expected a registration request")
+        };
+        let id = register_task.await.unwrap().unwrap();
+        assert_eq!(id, request.params.unwrap().registrations[0].id);
+        assert_eq!(1, registrations.registered().len());
+
+        let unregister_task = tokio::spawn({
+            let registrations = registrations.clone();
+            async move { registrations.unregister(&id).await }
+        });
+        resolve_next_request(&mut outgoing_rx, &pending_responses).await;
+        unregister_task.await.unwrap().unwrap();
+        assert!(registrations.registered().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unregisters_on_shutdown() {
+        let (mut registrations, mut outgoing_rx, pending_responses) = registrations_mock();
+        let register_task = tokio::spawn({
+            let registrations = registrations.clone();
+            async move { registrations.register_formatting(Vec::new()).await }
+        });
+        resolve_next_request(&mut outgoing_rx, &pending_responses).await;
+        register_task.await.unwrap().unwrap();
+
+        registrations.inspect_incoming(&AllMessages::Requests(
+            RequestMessage::<Shutdown> {
+                id: NumberOrString::Number(1).into(),
+                params: None,
+            }
+            .into(),
+        ));
+        assert!(matches!(
+            resolve_next_request(&mut outgoing_rx, &pending_responses).await,
+            AllClientRequests::UnregisterCapability(_)
+        ));
+        assert!(registrations.registered().is_empty());
+    }
+}
@@ -0,0 +1,268 @@
+use std::{collections::HashMap, fmt::Display};
+
+use derive_more::From;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::messages::{groups::raw::RawMessage, payload::Payload};
+
+use super::{
+    driver::{DriverError, ServiceDriver},
+    filter::MessageFilter,
+};
+
+const WILDCARD: &str = "{{_}}";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Step {
+    Send(Value),
+    Expect(Value),
+}
+
+/// A conversation of client messages and expected service output, run through a
+/// [`ServiceDriver`].
+///
+/// Expected messages are patterns: objects match when every listed field matches, so unlisted
+/// fields are ignored, while arrays and scalars must match exactly. The string `"{{_}}"` matches
+/// any value, and `"{{name}}"` captures the value on its first occurrence and must be equal on
+/// later ones. Captured values are substituted into sent messages, e.g. for answering a server
+/// request with its id.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Script {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, From)]
+pub enum ScriptError {
+    Fixture(serde_json::Error),
+    Driver(DriverError),
+    #[from(ignore)]
+    Unbound(String),
+    #[from(ignore)]
+    Mismatch {
+        step: usize,
+        expected: Value,
+        actual: Value,
+    },
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptError::Fixture(err) => write!(f, "invalid script fixture: {}", err),
+            ScriptError::Driver(err) => write!(f, "{}", err),
+            ScriptError::Unbound(name) => {
+                write!(f, "placeholder {{{{{}}}}} used before capture", name)
+            }
+            ScriptError::Mismatch {
+                step,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "step {} expected output matching {} but got {}",
+                step, expected, actual
+            ),
+        }
+    }
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a JSON array of `{ "send": message }` and `{ "expect": pattern }` steps.
+    pub fn from_fixture(fixture: &str) -> Result<Self, ScriptError> {
+        Ok(Self {
+            steps: serde_json::from_str(fixture)?,
+        })
+    }
+
+    pub fn send(mut self, message: Value) -> Self {
+        self.steps.push(Step::Send(message));
+        self
+    }
+
+    /// Expects the next output message to match the pattern.
+    pub fn expect(mut self, pattern: Value) -> Self {
+        self.steps.push(Step::Expect(pattern));
+        self
+    }
+
+    pub async fn run<F: MessageFilter>(
+        &self,
+        driver: &mut ServiceDriver<F>,
+    ) -> Result<(), ScriptError> {
+        let mut captures = HashMap::new();
+
+        for (step_index, step) in self.steps.iter().enumerate() {
+            match step {
+                Step::Send(message) => {
+                    let message = substitute(message, &captures)?;
+                    let payload = Payload::new(&RawMessage(message)).to_string();
+                    driver.send_raw_payload_str(&payload).await
+                }
+                Step::Expect(pattern) => {
+                    let actual = serde_json::to_value(driver.next_output_message().await?)?;
+                    if !matches_pattern(pattern, &actual, &mut captures) {
+                        return Err(ScriptError::Mismatch {
+                            step: step_index,
+                            expected: pattern.clone(),
+                            actual,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn placeholder_name(value: &Value) -> Option<&str> {
+    value
+        .as_str()?
+        .strip_prefix("{{")?
+        .strip_suffix("}}")
+        .filter(|name| !name.is_empty())
+}
+
+fn matches_pattern(pattern: &Value, actual: &Value, captures: &mut HashMap<String, Value>) -> bool {
+    if pattern == WILDCARD {
+        return true;
+    }
+
+    if let Some(name) = placeholder_name(pattern) {
+        return match captures.get(name) {
+            Some(captured) => captured == actual,
+            None => {
+                captures.insert(name.to_owned(), actual.clone());
+                true
+            }
+        };
+    }
+
+    match (pattern, actual) {
+        (Value::Object(pattern), Value::Object(actual)) => {
+            pattern.iter().all(|(key, pattern_value)| {
+                actual.get(key).is_some_and(|actual_value| {
+                    matches_pattern(pattern_value, actual_value, captures)
+                })
+            })
+        }
+        (Value::Array(pattern), Value::Array(actual)) => {
+            pattern.len() == actual.len()
+                && pattern
+                    .iter()
+                    .zip(actual)
+                    .all(|(pattern_value, actual_value)| {
+                        matches_pattern(pattern_value, actual_value, captures)
+                    })
+        }
+        (pattern, actual) => pattern == actual,
+    }
+}
+
+fn substitute(message: &Value, captures: &HashMap<String, Value>) -> Result<Value, ScriptError> {
+    if let Some(name) = placeholder_name(message).filter(|name| *name != "_") {
+        return captures
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ScriptError::Unbound(name.to_owned()));
+    }
+
+    Ok(match message {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), substitute(value, captures)?)))
+                .collect::<Result<_, ScriptError>>()?,
+        ),
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|value| substitute(value, captures))
+                .collect::<Result<_, _>>()?,
+        ),
+        value => value.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::service::{filter::tests::FilterMock, server::tests::shutdown_backend_mock};
+
+    use super::*;
+
+    #[test]
+    fn matches_partial_patterns() {
+        let mut captures = HashMap::new();
+        let actual = json!({ "jsonrpc": "2.0", "id": 3, "result": { "items": [1, 2] } });
+
+        assert!(matches_pattern(
+            &json!({ "id": "{{request_id}}", "result": { "items": [1, "{{_}}"] } }),
+            &actual,
+            &mut captures
+        ));
+        assert_eq!(Some(&json!(3)), captures.get("request_id"));
+        assert!(!matches_pattern(
+            &json!({ "id": "{{request_id}}", "result": { "items": [1] } }),
+            &actual,
+            &mut captures
+        ));
+        assert!(!matches_pattern(
+            &json!({ "id": "{{request_id}}", "error": "{{_}}" }),
+            &actual,
+            &mut captures
+        ));
+    }
+
+    #[test]
+    fn substitutes_captures() {
+        let captures = HashMap::from([("request_id".to_owned(), json!(3))]);
+        assert_eq!(
+            json!({ "id": 3, "result": null }),
+            substitute(
+                &json!({ "id": "{{request_id}}", "result": null }),
+                &captures
+            )
+            .unwrap()
+        );
+        assert!(matches!(
+            substitute(&json!({ "id": "{{unknown}}" }), &captures),
+            Err(ScriptError::Unbound(_))
+        ));
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn runs_fixture_against_backend() {
+        let script = Script::from_fixture(
+            r#"[
+                { "send": { "jsonrpc": "2.0", "id": 1, "method": "shutdown" } },
+                { "expect": { "id": 1, "result": null } }
+            ]"#,
+        )
+        .unwrap();
+
+        let mut driver = ServiceDriver::<FilterMock>::with_backend(shutdown_backend_mock);
+        script.run(&mut driver).await.unwrap();
+        driver.shutdown().await.unwrap()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn reports_mismatching_output() {
+        let script = Script::new()
+            .send(json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" }))
+            .expect(json!({ "id": 2 }));
+
+        let mut driver = ServiceDriver::<FilterMock>::with_backend(shutdown_backend_mock);
+        assert!(matches!(
+            script.run(&mut driver).await,
+            Err(ScriptError::Mismatch { step: 1, .. })
+        ))
+    }
+}
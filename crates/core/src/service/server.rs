@@ -0,0 +1,514 @@
+use std::{future::Future, time::Duration};
+
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    documents::{DocumentStore, NotebookStore, PositionEncoding},
+    messages::groups::AllMessages,
+    outbox::{Outbox, PendingResponses},
+    session::SessionRecorder,
+    workspace::{ConfigurationSection, Workspace},
+};
+
+use super::{
+    backend::ServiceBackend,
+    diagnostics::DiagnosticsPublisher,
+    encoding::{NegotiatedPositionEncoding, PositionEncodingNegotiator},
+    filter::{IncomingMessage, MessageFilter, MessageHook, OutgoingMessage, ServiceMessageFilter},
+    frontend::ServiceFrontend,
+    progress::WorkDoneProgress,
+    pull_diagnostics::PullDiagnostics,
+    registration::DynamicRegistrations,
+    trace::LogTracer,
+    watchdog::ParentProcessWatchdog,
+    watchers::FileWatchers,
+};
+
+#[cfg(feature = "tracing-layers")]
+use super::{trace::LogTraceLayer, window::WindowMessageLayer};
+
+const OUTBOX_REQUEST_ID_PREFIX: &str = "spique-";
+
+pub struct Service<F: MessageFilter, I: AsyncRead + Unpin, O: AsyncWrite + Unpin> {
+    pub(super) frontend: ServiceFrontend<I, O>,
+    pub(super) message_filter: ServiceMessageFilter<F>,
+    pub(super) backend: ServiceBackend<F>,
+    shutdown_token: CancellationToken,
+    notification_tx: UnboundedSender<AllMessages>,
+    outbox: Outbox,
+    pending_responses: PendingResponses,
+    work_done_progress: Option<WorkDoneProgress>,
+    dynamic_registrations: Option<DynamicRegistrations>,
+    file_watchers: Option<FileWatchers>,
+    diagnostics: Option<DiagnosticsPublisher>,
+    pull_diagnostics: Option<PullDiagnostics>,
+    position_encoding: Option<NegotiatedPositionEncoding>,
+}
+
+impl<F: MessageFilter, I: AsyncRead + Unpin, O: AsyncWrite + Unpin> Service<F, I, O> {
+    pub fn new(input: I, output: O) -> Self {
+        let (frontend_tx, frontend_rx) = unbounded::<AllMessages>();
+        let (message_filter_tx, message_filter_rx) = unbounded::<AllMessages>();
+        let (incoming_tx, incoming_rx) = unbounded::<IncomingMessage<F>>();
+        let (outgoing_tx, outgoing_rx) = unbounded::<OutgoingMessage<F>>();
+        let (notification_tx, notification_rx) = unbounded::<AllMessages>();
+        let shutdown_token = CancellationToken::new();
+        let (outbox, pending_responses) =
+            Outbox::with_sender(notification_tx.clone(), Some(OUTBOX_REQUEST_ID_PREFIX));
+
+        Self {
+            frontend: ServiceFrontend::new(
+                input,
+                output,
+                frontend_tx,
+                message_filter_rx,
+                shutdown_token.clone(),
+                notification_rx,
+            ),
+            message_filter: ServiceMessageFilter::new(
+                frontend_rx,
+                message_filter_tx,
+                outgoing_rx,
+                incoming_tx,
+                pending_responses.clone(),
+            ),
+            backend: ServiceBackend::new(incoming_rx, outgoing_tx),
+            shutdown_token,
+            notification_tx,
+            outbox,
+            pending_responses,
+            work_done_progress: None,
+            dynamic_registrations: None,
+            file_watchers: None,
+            diagnostics: None,
+            pull_diagnostics: None,
+            position_encoding: None,
+        }
+    }
+
+    pub fn with_hook(mut self, hook: impl MessageHook + Send + 'static) -> Self {
+        self.message_filter.add_hook(hook);
+        self
+    }
+
+    /// Shuts the service down once the process given by `InitializeParams.processId` has exited.
+    pub fn with_parent_process_watchdog(self, poll_interval: Duration) -> Self {
+        let watchdog = ParentProcessWatchdog::new(self.shutdown_token(), poll_interval);
+        self.with_hook(watchdog)
+    }
+
+    /// Publishes diagnostics to the client, debounced by 200 milliseconds unless changed with
+    /// [`DiagnosticsPublisher::set_debounce`].
+    pub fn with_diagnostics(mut self) -> Self {
+        let diagnostics = DiagnosticsPublisher::new(self.outbox.clone());
+        self.diagnostics = Some(diagnostics.clone());
+        self.with_hook(diagnostics)
+    }
+
+    /// Answers diagnostic pulls of the client, keeping track of the results it already has.
+    pub fn with_pull_diagnostics(mut self) -> Self {
+        let pull_diagnostics = PullDiagnostics::new(self.outbox.clone());
+        self.pull_diagnostics = Some(pull_diagnostics.clone());
+        self.with_hook(pull_diagnostics)
+    }
+
+    /// Negotiates the position encoding with the client, picking the first of the preferred
+    /// encodings it supports and UTF-16 when it supports none of them. The backend announcing an
+    /// encoding in its `initialize` result takes precedence.
+    ///
+    /// Runs ahead of the other hooks, so that they see the negotiated encoding in the
+    /// `initialize` result regardless of the order they were added in.
+    pub fn with_position_encodings(mut self, preferred: Vec<PositionEncoding>) -> Self {
+        let negotiator = PositionEncodingNegotiator::new(preferred);
+        self.position_encoding = Some(negotiator.announced());
+        self.message_filter.prepend_hook(negotiator);
+        self
+    }
+
+    /// Keeps the store in sync with the text documents opened by the client.
+    pub fn with_document_store(self, document_store: DocumentStore) -> Self {
+        self.with_hook(document_store)
+    }
+
+    /// Keeps the store in sync with the notebooks opened by the client.
+    pub fn with_notebook_store(self, notebook_store: NotebookStore) -> Self {
+        self.with_hook(notebook_store)
+    }
+
+    /// Keeps the workspace folders and configuration up to date.
+    pub fn with_workspace<C: ConfigurationSection>(self, workspace: Workspace<C>) -> Self {
+        self.with_hook(workspace)
+    }
+
+    /// Records the traffic going over the wire to a session log.
+    pub fn with_recorder(mut self, recorder: SessionRecorder) -> Self {
+        self.frontend.set_recorder(recorder);
+        self
+    }
+
+    /// Traces the messages handled by the service as `$/logTrace` notifications, at the trace
+    /// value set by the client through `InitializeParams.trace` and later `$/setTrace`.
+    pub fn with_log_trace(mut self) -> Self {
+        let tracer = LogTracer::new(self.notification_tx.clone());
+        self.frontend.set_tracer(tracer.clone());
+        self.with_hook(tracer)
+    }
+
+    /// Reports work done progress to the client, once it has advertised support for it through
+    /// `window.workDoneProgress`.
+    pub fn with_work_done_progress(mut self) -> Self {
+        let work_done_progress = WorkDoneProgress::new(self.outbox.clone());
+        self.work_done_progress = Some(work_done_progress.clone());
+        self.with_hook(work_done_progress)
+    }
+
+    /// Registers capabilities with the client after initialization, and unregisters those left
+    /// once it asks for shutdown.
+    pub fn with_dynamic_registrations(mut self) -> Self {
+        let dynamic_registrations = DynamicRegistrations::new(self.outbox.clone());
+        self.dynamic_registrations = Some(dynamic_registrations.clone());
+        self.with_hook(dynamic_registrations)
+    }
+
+    /// Watches files through the client, registering the watchers with
+    /// [`Service::with_dynamic_registrations`], which is enabled along with it.
+    pub fn with_file_watchers(mut self) -> Self {
+        if self.dynamic_registrations.is_none() {
+            self = self.with_dynamic_registrations();
+        }
+        let registrations = self.dynamic_registrations.clone().expect("enabled above");
+        let file_watchers = FileWatchers::new(registrations);
+        self.file_watchers = Some(file_watchers.clone());
+        self.with_hook(file_watchers)
+    }
+
+    /// A `tracing` layer forwarding events as `$/logTrace` notifications of this service, once
+    /// enabled with [`Service::with_log_trace`].
+    #[cfg(feature = "tracing-layers")]
+    pub fn log_trace_layer(&self) -> Option<LogTraceLayer> {
+        self.frontend.tracer().cloned().map(LogTraceLayer::new)
+    }
+
+    /// A `tracing` layer forwarding events to the client as `window/logMessage` notifications,
+    /// and optionally as `window/showMessage` ones.
+    #[cfg(feature = "tracing-layers")]
+    pub fn window_message_layer(&self) -> WindowMessageLayer {
+        WindowMessageLayer::new(self.notification_tx.clone())
+    }
+
+    /// Sends requests and notifications to the client alongside those of the backend, with
+    /// request ids that can't collide with the ones chosen by the backend.
+    pub fn outbox(&self) -> Outbox {
+        self.outbox.clone()
+    }
+
+    /// The progress reporter, once enabled with [`Service::with_work_done_progress`].
+    pub fn work_done_progress(&self) -> Option<WorkDoneProgress> {
+        self.work_done_progress.clone()
+    }
+
+    /// The registration manager, once enabled with [`Service::with_dynamic_registrations`].
+    pub fn dynamic_registrations(&self) -> Option<DynamicRegistrations> {
+        self.dynamic_registrations.clone()
+    }
+
+    /// The file watchers, once enabled with [`Service::with_file_watchers`].
+    pub fn file_watchers(&self) -> Option<FileWatchers> {
+        self.file_watchers.clone()
+    }
+
+    /// The diagnostics publisher, once enabled with [`Service::with_diagnostics`].
+    pub fn diagnostics(&self) -> Option<DiagnosticsPublisher> {
+        self.diagnostics.clone()
+    }
+
+    /// The diagnostic pull reports, once enabled with [`Service::with_pull_diagnostics`].
+    pub fn pull_diagnostics(&self) -> Option<PullDiagnostics> {
+        self.pull_diagnostics.clone()
+    }
+
+    /// The position encoding announced to the client in the `initialize` result, once
+    /// negotiated with [`Service::with_position_encodings`].
+    pub fn position_encoding(&self) -> Option<NegotiatedPositionEncoding> {
+        self.position_encoding.clone()
+    }
+
+    /// Cancelling the token stops the service from reading any further input, after which it
+    /// shuts down just as if the input had been closed.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown_token.clone()
+    }
+
+    /// Runs the service until its input closes and the backend has finished handling the
+    /// remaining incoming messages.
+    pub async fn run<B, Fut>(self, backend: B)
+    where
+        B: FnOnce(ServiceBackend<F>) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        futures::join!(
+            self.frontend.run(),
+            self.message_filter.run(),
+            backend(self.backend)
+        );
+        // Stops any remaining tasks tied to the service lifetime.
+        self.shutdown_token.cancel();
+        self.pending_responses.clear();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use futures::{SinkExt, StreamExt};
+    use lsp_types::{
+        request::{References, ShowDocument},
+        NumberOrString,
+    };
+    use serde_json::json;
+    use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    use crate::{
+        messages::{
+            codec::LanguageServerCodec,
+            core::response::{tests::SHUTDOWN_RESPONSE_MOCK, ResponseMessage},
+            groups::{
+                raw::RawMessage, requests::tests::SomeRequestsMock,
+                responses::tests::SomeResponsesMock, tests::MESSAGE_MOCK,
+            },
+            payload::tests::INVALID_PAYLOAD_STR_MOCK,
+        },
+        service::{filter::tests::FilterMock, partial::PartialResults},
+        session::{recorder::tests::SharedBuffer, Direction},
+    };
+
+    use super::*;
+
+    pub async fn shutdown_backend_mock(mut backend: ServiceBackend<FilterMock>) {
+        while let Some(incoming_message) = backend.next_incoming().await {
+            if let IncomingMessage::Request(SomeRequestsMock::ShutDown(request)) = incoming_message
+            {
+                backend.send_outgoing(OutgoingMessage::Response(SomeResponsesMock::Shutdown(
+                    ResponseMessage {
+                        id: request.id.into(),
+                        kind: Ok(()),
+                    },
+                )))
+            }
+        }
+    }
+
+    /// Sends [`MESSAGE_MOCK`] as a client would and asserts that it gets a response.
+    pub async fn assert_shutdown_roundtrip(
+        client_input: impl AsyncRead + Unpin,
+        client_output: impl AsyncWrite + Unpin,
+    ) {
+        let mut framed_output =
+            FramedWrite::new(client_output, LanguageServerCodec::<AllMessages>::default());
+        let mut framed_input =
+            FramedRead::new(client_input, LanguageServerCodec::<AllMessages>::default());
+
+        framed_output.send(MESSAGE_MOCK).await.unwrap();
+
+        assert!(framed_input
+            .next()
+            .await
+            .is_some_and(|message| message.unwrap()
+                == AllMessages::UntypedResponse(SHUTDOWN_RESPONSE_MOCK.into())))
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn runs_until_input_closes() {
+        let (service_input, client_output) = tokio::io::duplex(1024);
+        let (client_input, service_output) = tokio::io::duplex(1024);
+
+        let service_handle = tokio::spawn(
+            Service::<FilterMock, _, _>::new(service_input, service_output)
+                .run(shutdown_backend_mock),
+        );
+
+        // Client output is dropped after the roundtrip, closing the service input.
+        assert_shutdown_roundtrip(client_input, client_output).await;
+        service_handle.await.unwrap();
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn runs_until_shutdown_is_requested() {
+        let (service_input, _client_output) = tokio::io::duplex(1024);
+        let (_client_input, service_output) = tokio::io::duplex(1024);
+
+        let service = Service::<FilterMock, _, _>::new(service_input, service_output);
+        let shutdown_token = service.shutdown_token();
+        let service_handle = tokio::spawn(service.run(shutdown_backend_mock));
+
+        shutdown_token.cancel();
+        service_handle.await.unwrap();
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn records_session() {
+        let (service_input, client_output) = tokio::io::duplex(1024);
+        let (client_input, service_output) = tokio::io::duplex(1024);
+        let session_log = SharedBuffer::default();
+
+        let service_handle = tokio::spawn(
+            Service::<FilterMock, _, _>::new(service_input, service_output)
+                .with_recorder(SessionRecorder::new(session_log.clone()))
+                .run(shutdown_backend_mock),
+        );
+        assert_shutdown_roundtrip(client_input, client_output).await;
+        service_handle.await.unwrap();
+
+        let entries = session_log.entries();
+        assert_eq!(
+            vec![Direction::Incoming, Direction::Outgoing],
+            entries
+                .iter()
+                .map(|entry| entry.direction)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            serde_json::to_value(MESSAGE_MOCK).unwrap(),
+            serde_json::from_str::<serde_json::Value>(&entries[0].body).unwrap()
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn records_failed_frames() {
+        let (service_input, mut client_output) = tokio::io::duplex(1024);
+        let (client_input, service_output) = tokio::io::duplex(1024);
+        let session_log = SharedBuffer::default();
+
+        let service_handle = tokio::spawn(
+            Service::<FilterMock, _, _>::new(service_input, service_output)
+                .with_recorder(SessionRecorder::new(session_log.clone()))
+                .run(shutdown_backend_mock),
+        );
+        client_output
+            .write_all(INVALID_PAYLOAD_STR_MOCK.as_bytes())
+            .await
+            .unwrap();
+        let mut framed_input =
+            FramedRead::new(client_input, LanguageServerCodec::<AllMessages>::default());
+        framed_input.next().await.unwrap().unwrap();
+        drop(client_output);
+        service_handle.await.unwrap();
+
+        let entries = session_log.entries();
+        assert_eq!(2, entries.len());
+        assert_eq!(Direction::Incoming, entries[0].direction);
+        assert!(entries[0].error.is_some());
+        assert_eq!(Direction::Outgoing, entries[1].direction);
+        assert!(entries[1].error.is_none());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn traces_messages_once_enabled() {
+        let (service_input, client_output) = tokio::io::duplex(1024);
+        let (client_input, service_output) = tokio::io::duplex(1024);
+
+        let service_handle = tokio::spawn(
+            Service::<FilterMock, _, _>::new(service_input, service_output)
+                .with_log_trace()
+                .run(shutdown_backend_mock),
+        );
+        let mut framed_output =
+            FramedWrite::new(client_output, LanguageServerCodec::<RawMessage>::default());
+        let set_trace = RawMessage::notification("$/setTrace", json!({ "value": "messages" }));
+        framed_output.send(set_trace).await.unwrap();
+        let request = RawMessage(serde_json::to_value(MESSAGE_MOCK).unwrap());
+        framed_output.send(request).await.unwrap();
+        drop(framed_output);
+
+        let traces = FramedRead::new(client_input, LanguageServerCodec::<RawMessage>::default())
+            .filter_map(|message| async move {
+                let message = message.unwrap();
+                (message.method() == Some("$/logTrace")).then(|| message.params().cloned())
+            })
+            .collect::<Vec<_>>()
+            .await;
+        service_handle.await.unwrap();
+
+        assert!(traces.iter().flatten().any(|params| params["message"]
+            .as_str()
+            .is_some_and(|message| message.starts_with("Sending response"))));
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn resolves_outbox_requests() {
+        let (service_input, client_output) = tokio::io::duplex(1024);
+        let (client_input, service_output) = tokio::io::duplex(1024);
+
+        let service = Service::<FilterMock, _, _>::new(service_input, service_output);
+        let outbox = service.outbox();
+        let service_handle = tokio::spawn(service.run(shutdown_backend_mock));
+        let request_handle =
+            tokio::spawn(async move { outbox.send_request::<ShowDocument>(None).await });
+
+        let mut framed_output =
+            FramedWrite::new(client_output, LanguageServerCodec::<RawMessage>::default());
+        let mut framed_input =
+            FramedRead::new(client_input, LanguageServerCodec::<RawMessage>::default());
+        let request = framed_input.next().await.unwrap().unwrap();
+        assert_eq!(Some(&json!("spique-0")), request.id());
+
+        let result = json!({ "success": true });
+        framed_output
+            .send(RawMessage::response(
+                request.id().unwrap().clone(),
+                Ok(result),
+            ))
+            .await
+            .unwrap();
+        assert!(request_handle.await.unwrap().unwrap().success);
+
+        drop(framed_output);
+        service_handle.await.unwrap();
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn writes_partial_results_before_response() {
+        let (service_input, client_output) = tokio::io::duplex(1024);
+        let (client_input, service_output) = tokio::io::duplex(1024);
+
+        let service = Service::<FilterMock, _, _>::new(service_input, service_output);
+        let outbox = service.outbox();
+        let backend = |mut backend: ServiceBackend<FilterMock>| async move {
+            while let Some(incoming_message) = backend.next_incoming().await {
+                if let IncomingMessage::Request(SomeRequestsMock::ShutDown(request)) =
+                    incoming_message
+                {
+                    let mut partial_results = PartialResults::<References>::new(
+                        outbox.clone(),
+                        Some(NumberOrString::Number(0)),
+                    );
+                    partial_results.send(Vec::new());
+                    partial_results.finish();
+                    backend.send_outgoing(OutgoingMessage::Response(SomeResponsesMock::Shutdown(
+                        ResponseMessage {
+                            id: request.id.into(),
+                            kind: Ok(()),
+                        },
+                    )))
+                }
+            }
+        };
+        let service_handle = tokio::spawn(service.run(backend));
+
+        let mut framed_output =
+            FramedWrite::new(client_output, LanguageServerCodec::<AllMessages>::default());
+        let mut framed_input =
+            FramedRead::new(client_input, LanguageServerCodec::<RawMessage>::default());
+        framed_output.send(MESSAGE_MOCK).await.unwrap();
+
+        let partial_result = framed_input.next().await.unwrap().unwrap();
+        assert_eq!(None, partial_result.id());
+        let response = framed_input.next().await.unwrap().unwrap();
+        assert!(response.id().is_some());
+
+        drop(framed_output);
+        service_handle.await.unwrap();
+    }
+}
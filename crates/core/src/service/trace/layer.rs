@@ -0,0 +1,197 @@
+use std::fmt::{Debug, Write};
+
+use lsp_types::TraceValue;
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Metadata, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use crate::{
+    service::{filter, frontend},
+    session::recorder,
+};
+
+use super::LogTracer;
+
+/// Targets of the events sent while writing notifications to the client, which would otherwise
+/// produce notifications without end.
+const PIPELINE_TARGETS: [&str; 3] = [
+    frontend::TRACING_TARGET,
+    filter::TRACING_TARGET,
+    recorder::TRACING_TARGET,
+];
+
+/// Forwards `tracing` events as `$/logTrace` notifications of a [`Service`](crate::service::Service),
+/// once the client has enabled tracing.
+///
+/// The notification message is the event message with its fields, the verbose description adds
+/// the level, target and the spans the event occurred in.
+pub struct LogTraceLayer {
+    tracer: LogTracer,
+}
+
+impl LogTraceLayer {
+    pub(crate) fn new(tracer: LogTracer) -> Self {
+        Self { tracer }
+    }
+}
+
+/// Fields recorded for a span when it's created, formatted as `name{field=value}`.
+struct SpanFields(String);
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for LogTraceLayer {
+    fn on_new_span(&self, attributes: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut visitor = FieldVisitor::default();
+        attributes.record(&mut visitor);
+        let fields = match visitor.fields.is_empty() {
+            true => span.name().to_owned(),
+            false => format!("{}{{{}}}", span.name(), visitor.fields),
+        };
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if self.tracer.trace_value() == TraceValue::Off || is_pipeline_event(metadata) {
+            return;
+        }
+
+        let message = FieldVisitor::format_event(event);
+
+        self.tracer.log(message, || {
+            let mut verbose = format!("{} {}", metadata.level(), metadata.target());
+            if let Some(scope) = ctx.event_scope(event) {
+                let spans = scope
+                    .from_root()
+                    .map(|span| match span.extensions().get::<SpanFields>() {
+                        Some(SpanFields(fields)) => fields.clone(),
+                        None => span.name().to_owned(),
+                    })
+                    .collect::<Vec<_>>();
+                let _ = write!(verbose, " in {}", spans.join(" > "));
+            }
+            Some(verbose)
+        })
+    }
+}
+
+/// Whether the event comes from the service pipeline, which would emit further events while
+/// sending a notification for it.
+pub(in crate::service) fn is_pipeline_event(metadata: &Metadata<'_>) -> bool {
+    PIPELINE_TARGETS
+        .iter()
+        .any(|target| metadata.target().starts_with(target))
+}
+
+#[derive(Default)]
+pub(in crate::service) struct FieldVisitor {
+    message: String,
+    fields: String,
+}
+
+impl FieldVisitor {
+    /// The event message followed by its other fields as `name=value`.
+    pub fn format_event(event: &Event<'_>) -> String {
+        let mut visitor = Self::default();
+        event.record(&mut visitor);
+        match (visitor.message.is_empty(), visitor.fields.is_empty()) {
+            (_, true) => visitor.message,
+            (true, false) => visitor.fields,
+            (false, false) => format!("{} {}", visitor.message, visitor.fields),
+        }
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_owned(),
+            _ => self.record_debug(field, &value),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            name => {
+                if !self.fields.is_empty() {
+                    self.fields.push(' ');
+                }
+                let _ = write!(self.fields, "{}={:?}", name, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{channel::mpsc::unbounded, StreamExt};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::{
+        service::{trace::tests::log_trace_params, window::WindowMessageLayer},
+        session::{Direction, SessionRecorder},
+    };
+
+    use super::*;
+
+    struct FailingWriter;
+
+    impl std::io::Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn forwards_events_in_spans() {
+        let (trace_tx, trace_rx) = unbounded();
+        let tracer = LogTracer::new(trace_tx);
+        tracer.set_trace_value(TraceValue::Verbose);
+        let subscriber = tracing_subscriber::registry().with(LogTraceLayer::new(tracer));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("handle", uri = "file:///a").entered();
+            tracing::info!(count = 2, "Indexed.");
+            tracing::debug!(target: frontend::TRACING_TARGET, "Forwarding.");
+        });
+
+        let traces = futures::executor::block_on(trace_rx.collect::<Vec<_>>());
+        assert_eq!(1, traces.len());
+        let params = log_trace_params(traces.into_iter().next());
+        assert_eq!("Indexed. count=2", params.message);
+        assert_eq!(
+            Some(format!(
+                "INFO {} in handle{{uri=\"file:///a\"}}",
+                module_path!()
+            )),
+            params.verbose
+        );
+    }
+
+    #[test]
+    fn ignores_session_recorder_failures() {
+        let (trace_tx, trace_rx) = unbounded();
+        let tracer = LogTracer::new(trace_tx.clone());
+        tracer.set_trace_value(TraceValue::Verbose);
+        let subscriber = tracing_subscriber::registry()
+            .with(LogTraceLayer::new(tracer))
+            .with(WindowMessageLayer::new(trace_tx));
+        let recorder = SessionRecorder::new(FailingWriter);
+
+        tracing::subscriber::with_default(subscriber, || {
+            recorder.record(Direction::Outgoing, b"{}", None)
+        });
+
+        assert!(futures::executor::block_on(trace_rx.collect::<Vec<_>>()).is_empty());
+    }
+}
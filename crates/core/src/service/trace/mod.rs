@@ -0,0 +1,261 @@
+#[cfg(feature = "tracing-layers")]
+pub(super) mod layer;
+
+#[cfg(feature = "tracing-layers")]
+pub use layer::LogTraceLayer;
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use futures::channel::mpsc::UnboundedSender;
+use lsp_types::{
+    notification::{LogTrace, Notification},
+    LogTraceParams, TraceValue,
+};
+
+use crate::{
+    messages::{
+        core::notification::NotificationMessage,
+        groups::{
+            notifications::{AllNotifications, AllServerNotifications},
+            raw::RawMessage,
+            requests::{AllRequests, AllServerRequests},
+            AllMessages,
+        },
+    },
+    session::Direction,
+};
+
+use super::filter::MessageHook;
+
+const PENDING_METHODS_POISONED: &str = "pending methods lock poisoned";
+
+/// Sends `$/logTrace` notifications at the trace value set by the client, through
+/// `InitializeParams.trace` and later `$/setTrace`.
+#[derive(Clone)]
+pub(crate) struct LogTracer(Arc<LogTracerInner>);
+
+struct LogTracerInner {
+    trace_value: AtomicU8,
+    trace_tx: UnboundedSender<AllMessages>,
+    /// Methods of the requests yet to be answered, for describing their responses.
+    pending_methods: Mutex<HashMap<(Direction, String), String>>,
+}
+
+impl LogTracer {
+    pub fn new(trace_tx: UnboundedSender<AllMessages>) -> Self {
+        Self(Arc::new(LogTracerInner {
+            trace_value: AtomicU8::new(encode_trace_value(TraceValue::Off)),
+            trace_tx,
+            pending_methods: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    pub fn trace_value(&self) -> TraceValue {
+        match self.0.trace_value.load(Ordering::Relaxed) {
+            1 => TraceValue::Messages,
+            2 => TraceValue::Verbose,
+            _ => TraceValue::Off,
+        }
+    }
+
+    pub fn set_trace_value(&self, trace_value: TraceValue) {
+        self.0
+            .trace_value
+            .store(encode_trace_value(trace_value), Ordering::Relaxed)
+    }
+
+    /// The verbose description is only built when the trace value asks for it.
+    pub fn log(&self, message: String, verbose: impl FnOnce() -> Option<String>) {
+        let verbose = match self.trace_value() {
+            TraceValue::Off => return,
+            TraceValue::Messages => None,
+            TraceValue::Verbose => verbose(),
+        };
+
+        let notification = NotificationMessage::<LogTrace> {
+            params: Some(LogTraceParams { message, verbose }),
+        };
+        // The frontend may already be gone, with nobody left to read the trace.
+        let _ = self
+            .0
+            .trace_tx
+            .unbounded_send(AllMessages::Notifications(notification.into()));
+    }
+
+    /// Traces a message handled by the frontend, along with its params or result when verbose.
+    /// Traces themselves are left out.
+    pub fn trace_message(&self, direction: Direction, message: &AllMessages) {
+        if self.trace_value() == TraceValue::Off {
+            return;
+        }
+        let Ok(message) = serde_json::to_value(message).map(RawMessage) else {
+            return;
+        };
+        if message.method() == Some(LogTrace::METHOD) {
+            return;
+        }
+
+        let verb = match direction {
+            Direction::Incoming => "Received",
+            Direction::Outgoing => "Sending",
+        };
+        let id = message.display_id();
+        let id_key = message.id_key().unwrap_or_default();
+        let mut pending_methods = self
+            .0
+            .pending_methods
+            .lock()
+            .expect(PENDING_METHODS_POISONED);
+
+        let (description, label, data) = match (message.method(), id) {
+            (Some(method), Some(id)) => {
+                pending_methods.insert((direction, id_key), method.to_owned());
+                let description = format!("{} request '{} - ({})'.", verb, method, id);
+                (description, "Params", message.params().cloned())
+            }
+            (Some(method), None) => {
+                let description = format!("{} notification '{}'.", verb, method);
+                (description, "Params", message.params().cloned())
+            }
+            (None, id) => {
+                let id = id.unwrap_or_else(|| "null".to_owned());
+                let request_direction = match direction {
+                    Direction::Incoming => Direction::Outgoing,
+                    Direction::Outgoing => Direction::Incoming,
+                };
+                let method = pending_methods
+                    .remove(&(request_direction, id_key))
+                    .unwrap_or_else(|| "unknown".to_owned());
+                let description = format!("{} response '{} - ({})'.", verb, method, id);
+                match message.into_result() {
+                    Ok(result) => (description, "Result", Some(result)),
+                    Err(error) => (description, "Error", Some(error)),
+                }
+            }
+        };
+        drop(pending_methods);
+
+        self.log(description, || {
+            data.map(|data| format!("{}: {:#}", label, data))
+        })
+    }
+}
+
+impl MessageHook for LogTracer {
+    fn inspect_incoming(&mut self, message: &AllMessages) {
+        match message {
+            AllMessages::Requests(AllRequests::Server(AllServerRequests::Initialize(request))) => {
+                if let Some(trace_value) = request.params.as_ref().and_then(|params| params.trace) {
+                    self.set_trace_value(trace_value)
+                }
+            }
+            AllMessages::Notifications(AllNotifications::Server(
+                AllServerNotifications::SetTrace(notification),
+            )) => {
+                if let Some(params) = &notification.params {
+                    self.set_trace_value(params.value)
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn encode_trace_value(trace_value: TraceValue) -> u8 {
+    match trace_value {
+        TraceValue::Off => 0,
+        TraceValue::Messages => 1,
+        TraceValue::Verbose => 2,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use futures::channel::mpsc::unbounded;
+    use lsp_types::{notification::SetTrace, SetTraceParams};
+
+    use crate::messages::groups::notifications::AllClientNotifications;
+
+    use super::*;
+
+    pub fn log_trace_params(message: Option<AllMessages>) -> LogTraceParams {
+        match message {
+            Some(AllMessages::Notifications(AllNotifications::Client(
+                AllClientNotifications::LogTrace(notification),
+            ))) => notification.params.unwrap(),
+            message => panic!("expected a log trace notification, got {:?}", message),
+        }
+    }
+
+    #[test]
+    fn follows_set_trace() {
+        let (trace_tx, mut trace_rx) = unbounded();
+        let mut tracer = LogTracer::new(trace_tx);
+        tracer.log("ignored".to_owned(), || None);
+
+        let set_trace = |value| {
+            AllMessages::Notifications(
+                NotificationMessage::<SetTrace> {
+                    params: Some(SetTraceParams { value }),
+                }
+                .into(),
+            )
+        };
+        tracer.inspect_incoming(&set_trace(TraceValue::Messages));
+        tracer.log("traced".to_owned(), || Some("verbose".to_owned()));
+        tracer.inspect_incoming(&set_trace(TraceValue::Verbose));
+        tracer.log("traced".to_owned(), || Some("verbose".to_owned()));
+
+        assert_eq!(
+            LogTraceParams {
+                message: "traced".to_owned(),
+                verbose: None
+            },
+            log_trace_params(trace_rx.try_next().unwrap())
+        );
+        assert_eq!(
+            Some("verbose".to_owned()),
+            log_trace_params(trace_rx.try_next().unwrap()).verbose
+        );
+        assert!(trace_rx.try_next().is_err());
+    }
+
+    #[test]
+    fn traces_message_exchanges() {
+        let (trace_tx, mut trace_rx) = unbounded();
+        let tracer = LogTracer::new(trace_tx);
+        tracer.set_trace_value(TraceValue::Verbose);
+
+        let request = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" });
+        let response = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": null });
+        tracer.trace_message(
+            Direction::Incoming,
+            &serde_json::from_value(request).unwrap(),
+        );
+        tracer.trace_message(
+            Direction::Outgoing,
+            &serde_json::from_value(response).unwrap(),
+        );
+
+        assert_eq!(
+            LogTraceParams {
+                message: "Received request 'shutdown - (1)'.".to_owned(),
+                verbose: None
+            },
+            log_trace_params(trace_rx.try_next().unwrap())
+        );
+        assert_eq!(
+            LogTraceParams {
+                message: "Sending response 'shutdown - (1)'.".to_owned(),
+                verbose: Some("Result: null".to_owned())
+            },
+            log_trace_params(trace_rx.try_next().unwrap())
+        );
+    }
+}
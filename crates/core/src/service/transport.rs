@@ -0,0 +1,149 @@
+use std::future::Future;
+
+use tokio::{
+    io::{Stdin, Stdout},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream, ToSocketAddrs,
+    },
+};
+
+use super::{backend::ServiceBackend, filter::MessageFilter, server::Service};
+
+/// Whether the service should establish the connection itself or wait for the client to do so.
+///
+/// Editors passing a `--port` argument usually expect the server to connect back to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketMode {
+    Connect,
+    Listen,
+}
+
+/// `configure` is handed the service before it runs, pass [`std::convert::identity`] to run it
+/// as is.
+pub async fn serve_stdio<F, C, B, Fut>(configure: C, backend: B)
+where
+    F: MessageFilter,
+    C: FnOnce(Service<F, Stdin, Stdout>) -> Service<F, Stdin, Stdout>,
+    B: FnOnce(ServiceBackend<F>) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    configure(Service::new(tokio::io::stdin(), tokio::io::stdout()))
+        .run(backend)
+        .await
+}
+
+/// `configure` is handed the service once connected, before it runs.
+pub async fn serve_tcp<F, C, B, Fut>(
+    addr: impl ToSocketAddrs,
+    socket_mode: SocketMode,
+    configure: C,
+    backend: B,
+) -> std::io::Result<()>
+where
+    F: MessageFilter,
+    C: FnOnce(
+        Service<F, OwnedReadHalf, OwnedWriteHalf>,
+    ) -> Service<F, OwnedReadHalf, OwnedWriteHalf>,
+    B: FnOnce(ServiceBackend<F>) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let tcp_stream = match socket_mode {
+        SocketMode::Connect => TcpStream::connect(addr).await?,
+        SocketMode::Listen => TcpListener::bind(addr).await?.accept().await?.0,
+    };
+
+    let (read_half, write_half) = tcp_stream.into_split();
+    configure(Service::new(read_half, write_half))
+        .run(backend)
+        .await;
+
+    Ok(())
+}
+
+/// `configure` is handed the service once connected, before it runs.
+#[cfg(unix)]
+pub async fn serve_unix<F, C, B, Fut>(
+    path: impl AsRef<std::path::Path>,
+    socket_mode: SocketMode,
+    configure: C,
+    backend: B,
+) -> std::io::Result<()>
+where
+    F: MessageFilter,
+    C: FnOnce(
+        Service<F, tokio::net::unix::OwnedReadHalf, tokio::net::unix::OwnedWriteHalf>,
+    ) -> Service<F, tokio::net::unix::OwnedReadHalf, tokio::net::unix::OwnedWriteHalf>,
+    B: FnOnce(ServiceBackend<F>) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    use tokio::net::{UnixListener, UnixStream};
+
+    let unix_stream = match socket_mode {
+        SocketMode::Connect => UnixStream::connect(path).await?,
+        SocketMode::Listen => UnixListener::bind(path)?.accept().await?.0,
+    };
+
+    let (read_half, write_half) = unix_stream.into_split();
+    configure(Service::new(read_half, write_half))
+        .run(backend)
+        .await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use crate::service::{
+        filter::tests::FilterMock,
+        server::tests::{assert_shutdown_roundtrip, shutdown_backend_mock},
+    };
+
+    use super::*;
+
+    #[test_log::test(tokio::test)]
+    async fn connects_over_tcp() {
+        let client_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client_addr = client_listener.local_addr().unwrap();
+
+        let service_handle = tokio::spawn(serve_tcp::<FilterMock, _, _, _>(
+            client_addr,
+            SocketMode::Connect,
+            std::convert::identity,
+            shutdown_backend_mock,
+        ));
+
+        let (client_stream, _) = client_listener.accept().await.unwrap();
+        let (client_input, client_output) = client_stream.into_split();
+        assert_shutdown_roundtrip(client_input, client_output).await;
+        service_handle.await.unwrap().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test_log::test(tokio::test)]
+    async fn listens_on_unix_socket() {
+        let socket_path =
+            std::env::temp_dir().join(format!("spique-serve-unix-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket_path);
+
+        let service_handle = tokio::spawn(serve_unix::<FilterMock, _, _, _>(
+            socket_path.clone(),
+            SocketMode::Listen,
+            std::convert::identity,
+            shutdown_backend_mock,
+        ));
+
+        let client_stream = loop {
+            match tokio::net::UnixStream::connect(&socket_path).await {
+                Ok(client_stream) => break client_stream,
+                Err(_) => tokio::task::yield_now().await,
+            }
+        };
+        let (client_input, client_output) = client_stream.into_split();
+        assert_shutdown_roundtrip(client_input, client_output).await;
+        service_handle.await.unwrap().unwrap();
+        std::fs::remove_file(socket_path).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::messages::groups::{
+    requests::{AllRequests, AllServerRequests},
+    AllMessages,
+};
+
+use super::filter::MessageHook;
+
+/// Cancels the service shutdown token once the process given by `InitializeParams.processId`
+/// has exited.
+///
+/// Only supported on Linux, where `/proc` is polled, elsewhere a warning is logged instead.
+pub struct ParentProcessWatchdog {
+    shutdown_token: CancellationToken,
+    poll_interval: Duration,
+    watching: bool,
+}
+
+impl ParentProcessWatchdog {
+    pub fn new(shutdown_token: CancellationToken, poll_interval: Duration) -> Self {
+        Self {
+            shutdown_token,
+            poll_interval,
+            watching: false,
+        }
+    }
+
+    fn watch(&mut self, process_id: u32) {
+        self.watching = true;
+
+        #[cfg(target_os = "linux")]
+        tokio::spawn(watch_process(
+            process_id,
+            self.poll_interval,
+            self.shutdown_token.clone(),
+        ));
+
+        #[cfg(not(target_os = "linux"))]
+        tracing::warn!(
+            process_id,
+            "Parent process monitoring is not supported on this platform."
+        );
+    }
+}
+
+impl MessageHook for ParentProcessWatchdog {
+    fn inspect_incoming(&mut self, message: &AllMessages) {
+        if self.watching {
+            return;
+        }
+
+        if let AllMessages::Requests(AllRequests::Server(AllServerRequests::Initialize(request))) =
+            message
+        {
+            if let Some(process_id) = request.params.as_ref().and_then(|params| params.process_id) {
+                self.watch(process_id)
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+async fn watch_process(
+    process_id: u32,
+    poll_interval: Duration,
+    shutdown_token: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(poll_interval);
+    loop {
+        tokio::select! {
+            _ = shutdown_token.cancelled() => return,
+            _ = interval.tick() => {
+                if !process_is_running(process_id) {
+                    tracing::info!(process_id, "Parent process exited, shutting down service.");
+                    shutdown_token.cancel();
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn process_is_running(process_id: u32) -> bool {
+    let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", process_id)) else {
+        return false;
+    };
+
+    // Process state follows the parenthesized executable name, which may itself contain spaces
+    // and parentheses. Zombies are considered exited since only reaping remains.
+    match stat
+        .rfind(')')
+        .and_then(|name_end| stat[name_end + 1..].trim_start().chars().next())
+    {
+        Some(state) => !matches!(state, 'Z' | 'X' | 'x'),
+        None => false,
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use crate::messages::core::request::RequestMessage;
+
+    use super::*;
+
+    fn initialize_message(process_id: u32) -> AllMessages {
+        AllMessages::Requests(AllRequests::Server(AllServerRequests::Initialize(
+            RequestMessage {
+                id: lsp_types::NumberOrString::Number(0).into(),
+                params: Some(lsp_types::InitializeParams {
+                    process_id: Some(process_id),
+                    ..Default::default()
+                }),
+            },
+        )))
+    }
+
+    #[test]
+    fn detects_running_process() {
+        assert!(process_is_running(std::process::id()))
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn cancels_shutdown_token_when_parent_exits() {
+        let mut exited_process = std::process::Command::new("true").spawn().unwrap();
+        exited_process.wait().unwrap();
+
+        let shutdown_token = CancellationToken::new();
+        let mut watchdog =
+            ParentProcessWatchdog::new(shutdown_token.clone(), Duration::from_millis(10));
+        watchdog.inspect_incoming(&initialize_message(exited_process.id()));
+
+        tokio::time::timeout(Duration::from_secs(5), shutdown_token.cancelled())
+            .await
+            .expect("watchdog should have cancelled the shutdown token")
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn keeps_running_while_parent_is_alive() {
+        let shutdown_token = CancellationToken::new();
+        let mut watchdog =
+            ParentProcessWatchdog::new(shutdown_token.clone(), Duration::from_millis(10));
+        watchdog.inspect_incoming(&initialize_message(std::process::id()));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!shutdown_token.is_cancelled());
+        shutdown_token.cancel();
+    }
+}
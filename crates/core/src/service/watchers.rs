@@ -0,0 +1,513 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use derive_more::{Display, From};
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    StreamExt,
+};
+use lsp_types::{
+    notification::{DidChangeWatchedFiles, Notification},
+    DidChangeWatchedFilesRegistrationOptions, FileChangeType, FileEvent, FileSystemWatcher,
+    GlobPattern, OneOf, RelativePattern, Url, WatchKind, WorkspaceFolder,
+};
+use percent_encoding::percent_decode_str;
+
+use crate::{
+    glob::{Glob, GlobError},
+    messages::groups::{
+        notifications::{AllNotifications, AllServerNotifications},
+        AllMessages,
+    },
+};
+
+use super::{
+    filter::MessageHook,
+    registration::{DynamicRegistrations, RegistrationError},
+};
+
+const FILE_WATCHES_POISONED: &str = "file watches lock poisoned";
+const RELATIVE_PATTERN_SUPPORT_PATH: [&str; 3] = [
+    "workspace",
+    "didChangeWatchedFiles",
+    "relativePatternSupport",
+];
+
+#[derive(Debug, Display, From)]
+pub enum FileWatchError {
+    Glob(GlobError),
+    Registration(RegistrationError),
+}
+
+/// Registers `workspace/didChangeWatchedFiles` watchers with the client of a
+/// [`Service`](super::Service), handing each file event to the watches with a matching pattern.
+#[derive(Clone)]
+pub struct FileWatchers {
+    registrations: DynamicRegistrations,
+    watches: Arc<Mutex<HashMap<String, WatchHandle>>>,
+}
+
+struct WatchHandle {
+    matchers: Vec<WatcherMatcher>,
+    events_tx: UnboundedSender<FileEvent>,
+}
+
+/// A compiled [`FileSystemWatcher`].
+struct WatcherMatcher {
+    /// Decoded path of the base uri of relative patterns, without a trailing separator.
+    base_path: Option<String>,
+    glob: Glob,
+    kind: WatchKind,
+}
+
+/// The file events matching the watchers of a single registration.
+pub struct FileWatch {
+    id: String,
+    events_rx: UnboundedReceiver<FileEvent>,
+}
+
+impl FileWatchers {
+    pub(crate) fn new(registrations: DynamicRegistrations) -> Self {
+        Self {
+            registrations,
+            watches: Arc::default(),
+        }
+    }
+
+    /// Fails without registering anything if any of the patterns is invalid, or if the client
+    /// doesn't support dynamic registration of file watchers.
+    pub async fn watch(
+        &self,
+        watchers: Vec<FileSystemWatcher>,
+    ) -> Result<FileWatch, FileWatchError> {
+        let matchers = watchers
+            .iter()
+            .map(WatcherMatcher::new)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Watching ahead of the registration response, as the client may send events right
+        // after acknowledging it.
+        let id = self.registrations.next_id();
+        let (events_tx, events_rx) = unbounded();
+        self.watches.lock().expect(FILE_WATCHES_POISONED).insert(
+            id.clone(),
+            WatchHandle {
+                matchers,
+                events_tx,
+            },
+        );
+
+        let register_options = DidChangeWatchedFilesRegistrationOptions { watchers };
+        let registration = self
+            .registrations
+            .register_with_id(
+                id.clone(),
+                DidChangeWatchedFiles::METHOD,
+                Some(serde_json::to_value(register_options).expect("serializable options")),
+            )
+            .await;
+        if let Err(err) = registration {
+            self.watches
+                .lock()
+                .expect(FILE_WATCHES_POISONED)
+                .remove(&id);
+            return Err(err.into());
+        }
+
+        Ok(FileWatch { id, events_rx })
+    }
+
+    /// Watches the pattern relative to each of the workspace folders, prefixing it with the
+    /// escaped folder path when the client lacks support for relative patterns.
+    pub async fn watch_relative(
+        &self,
+        folders: &[WorkspaceFolder],
+        pattern: &str,
+        kind: Option<WatchKind>,
+    ) -> Result<FileWatch, FileWatchError> {
+        let relative_pattern_support = self
+            .registrations
+            .client_flag(&RELATIVE_PATTERN_SUPPORT_PATH);
+
+        let watchers = folders
+            .iter()
+            .map(|folder| FileSystemWatcher {
+                glob_pattern: match relative_pattern_support {
+                    true => GlobPattern::Relative(RelativePattern {
+                        base_uri: OneOf::Left(folder.clone()),
+                        pattern: pattern.to_string(),
+                    }),
+                    false => GlobPattern::String(format!(
+                        "{}/{}",
+                        Glob::escape(decoded_path(&folder.uri).trim_end_matches('/')),
+                        pattern
+                    )),
+                },
+                kind,
+            })
+            .collect();
+        self.watch(watchers).await
+    }
+
+    pub async fn unwatch(&self, id: &str) -> Result<(), RegistrationError> {
+        self.watches.lock().expect(FILE_WATCHES_POISONED).remove(id);
+        self.registrations.unregister(id).await
+    }
+
+    fn dispatch(&self, event: &FileEvent) {
+        self.watches
+            .lock()
+            .expect(FILE_WATCHES_POISONED)
+            .values()
+            .filter(|watch| watch.matchers.iter().any(|matcher| matcher.is_match(event)))
+            .for_each(|watch| {
+                // The watch having been dropped only means nobody cares about the event.
+                let _ = watch.events_tx.unbounded_send(event.clone());
+            })
+    }
+}
+
+impl MessageHook for FileWatchers {
+    fn inspect_incoming(&mut self, message: &AllMessages) {
+        if let AllMessages::Notifications(AllNotifications::Server(
+            AllServerNotifications::DidChangeWatcheFiles(notification),
+        )) = message
+        {
+            if let Some(params) = &notification.params {
+                params.changes.iter().for_each(|event| self.dispatch(event))
+            }
+        }
+    }
+}
+
+impl FileWatch {
+    /// The registration id, for use with [`FileWatchers::unwatch`].
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns `None` once the watch has been unwatched.
+    pub async fn next_event(&mut self) -> Option<FileEvent> {
+        self.events_rx.next().await
+    }
+}
+
+impl WatcherMatcher {
+    fn new(watcher: &FileSystemWatcher) -> Result<Self, GlobError> {
+        let (base_path, pattern) = match &watcher.glob_pattern {
+            GlobPattern::String(pattern) => (None, pattern),
+            GlobPattern::Relative(RelativePattern { base_uri, pattern }) => {
+                let base_uri = match base_uri {
+                    OneOf::Left(folder) => &folder.uri,
+                    OneOf::Right(uri) => uri,
+                };
+                (
+                    Some(decoded_path(base_uri).trim_end_matches('/').to_string()),
+                    pattern,
+                )
+            }
+        };
+
+        Ok(Self {
+            base_path,
+            glob: Glob::new(pattern)?,
+            kind: watcher.kind.unwrap_or(WatchKind::all()),
+        })
+    }
+
+    fn is_match(&self, event: &FileEvent) -> bool {
+        let kind = match event.typ {
+            FileChangeType::CREATED => WatchKind::Create,
+            FileChangeType::CHANGED => WatchKind::Change,
+            FileChangeType::DELETED => WatchKind::Delete,
+            _ => return false,
+        };
+        self.kind.contains(kind) && self.is_path_match(&event.uri)
+    }
+
+    fn is_path_match(&self, uri: &Url) -> bool {
+        let path = decoded_path(uri);
+        match &self.base_path {
+            Some(base_path) => path
+                .strip_prefix(base_path.as_str())
+                .and_then(|relative_path| relative_path.strip_prefix('/'))
+                .is_some_and(|relative_path| self.glob.is_match(relative_path)),
+            None => self.glob.is_match(&path),
+        }
+    }
+}
+
+/// Patterns are matched against paths as written, not as percent-encoded in uris.
+fn decoded_path(uri: &Url) -> String {
+    percent_decode_str(uri.path())
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{
+        ClientCapabilities, DidChangeWatchedFilesClientCapabilities, DidChangeWatchedFilesParams,
+        WorkspaceClientCapabilities,
+    };
+    use serde_json::Value;
+
+    use crate::{
+        messages::{
+            core::{
+                notification::NotificationMessage,
+                response::{ResponseId, UntypedResponseMessage},
+            },
+            groups::{
+                requests::{tests::initialize_mock, AllClientRequests, AllRequests},
+                AllMessages,
+            },
+        },
+        outbox::{Outbox, PendingResponses},
+        workspace::tests::folder_mock,
+    };
+
+    use super::*;
+
+    fn event_mock(path: &str, typ: FileChangeType) -> FileEvent {
+        FileEvent {
+            uri: Url::parse(&format!("file://{}", path)).unwrap(),
+            typ,
+        }
+    }
+
+    #[test]
+    fn matches_events_against_watchers() {
+        let absolute = WatcherMatcher::new(&FileSystemWatcher {
+            glob_pattern: GlobPattern::String("**/*.rs".to_string()),
+            kind: Some(WatchKind::Create | WatchKind::Delete),
+        })
+        .unwrap();
+        assert!(absolute.is_match(&event_mock("/a/lib.rs", FileChangeType::CREATED)));
+        assert!(!absolute.is_match(&event_mock("/a/lib.rs", FileChangeType::CHANGED)));
+        assert!(!absolute.is_match(&event_mock("/a/Cargo.toml", FileChangeType::DELETED)));
+
+        let relative = WatcherMatcher::new(&FileSystemWatcher {
+            glob_pattern: GlobPattern::Relative(RelativePattern {
+                base_uri: OneOf::Left(folder_mock("a/")),
+                pattern: "src/*.rs".to_string(),
+            }),
+            kind: None,
+        })
+        .unwrap();
+        assert!(relative.is_match(&event_mock("/a/src/lib.rs", FileChangeType::CHANGED)));
+        assert!(!relative.is_match(&event_mock("/b/src/lib.rs", FileChangeType::CHANGED)));
+        assert!(!relative.is_match(&event_mock("/ab/src/lib.rs", FileChangeType::CHANGED)));
+    }
+
+    #[test]
+    fn matches_decoded_paths() {
+        let matcher = WatcherMatcher::new(&FileSystemWatcher {
+            glob_pattern: GlobPattern::Relative(RelativePattern {
+                base_uri: OneOf::Left(folder_mock("my%20folder")),
+                pattern: "**/my file.rs".to_string(),
+            }),
+            kind: None,
+        })
+        .unwrap();
+        assert!(matcher.is_match(&event_mock(
+            "/my%20folder/src/my%20file.rs",
+            FileChangeType::CHANGED
+        )));
+    }
+
+    fn file_watchers_mock(
+        relative_pattern_support: bool,
+    ) -> (
+        FileWatchers,
+        UnboundedReceiver<AllMessages>,
+        PendingResponses,
+    ) {
+        let (outbox, outgoing_rx, pending_responses) = Outbox::new(None);
+        let mut registrations = DynamicRegistrations::new(outbox);
+        registrations.inspect_incoming(&initialize_mock(ClientCapabilities {
+            workspace: Some(WorkspaceClientCapabilities {
+                did_change_watched_files: Some(DidChangeWatchedFilesClientCapabilities {
+                    dynamic_registration: Some(true),
+                    relative_pattern_support: Some(relative_pattern_support),
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }));
+        (
+            FileWatchers::new(registrations),
+            outgoing_rx,
+            pending_responses,
+        )
+    }
+
+    fn did_change_watched_files_mock(changes: Vec<FileEvent>) -> AllMessages {
+        AllMessages::Notifications(
+            NotificationMessage::<DidChangeWatchedFiles> {
+                params: Some(DidChangeWatchedFilesParams { changes }),
+            }
+            .into(),
+        )
+    }
+
+    /// Acknowledges the next registration request, returning its watchers.
+    async fn acknowledge_registration(
+        outgoing_rx: &mut UnboundedReceiver<AllMessages>,
+        pending_responses: &PendingResponses,
+    ) -> Vec<FileSystemWatcher> {
+        let AllMessages::Requests(AllRequests::Client(AllClientRequests::RegisterCapability(
+            request,
+        ))) = outgoing_rx.next().await.unwrap()
+        else {
+            panic!("expected a registration request")
+        };
+        pending_responses
+            .resolve(UntypedResponseMessage {
+                id: ResponseId::from(request.id.clone()),
+                kind: Ok(Value::Null),
+            })
+            .unwrap();
+
+        let register_options = request.params.unwrap().registrations[0]
+            .register_options
+            .clone()
+            .unwrap();
+        serde_json::from_value::<DidChangeWatchedFilesRegistrationOptions>(register_options)
+            .unwrap()
+            .watchers
+    }
+
+    /// Watches the pattern in `/{folder}`, returning the registered watchers.
+    async fn watch_relative_mock(
+        file_watchers: &FileWatchers,
+        outgoing_rx: &mut UnboundedReceiver<AllMessages>,
+        pending_responses: &PendingResponses,
+        folder: &'static str,
+        pattern: &'static str,
+    ) -> (FileWatch, Vec<FileSystemWatcher>) {
+        let watch_task = tokio::spawn({
+            let file_watchers = file_watchers.clone();
+            async move {
+                file_watchers
+                    .watch_relative(&[folder_mock(folder)], pattern, None)
+                    .await
+            }
+        });
+        let watchers = acknowledge_registration(outgoing_rx, pending_responses).await;
+        (watch_task.await.unwrap().unwrap(), watchers)
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_absolute_patterns() {
+        let (file_watchers, mut outgoing_rx, pending_responses) = file_watchers_mock(false);
+        let (_watch, watchers) = watch_relative_mock(
+            &file_watchers,
+            &mut outgoing_rx,
+            &pending_responses,
+            "a",
+            "**/*.toml",
+        )
+        .await;
+        assert_eq!(
+            GlobPattern::String("/a/**/*.toml".to_string()),
+            watchers[0].glob_pattern
+        );
+    }
+
+    #[tokio::test]
+    async fn escapes_folder_paths_of_absolute_patterns() {
+        let (file_watchers, mut outgoing_rx, pending_responses) = file_watchers_mock(false);
+        let (_watch, watchers) = watch_relative_mock(
+            &file_watchers,
+            &mut outgoing_rx,
+            &pending_responses,
+            "a[1]",
+            "*.toml",
+        )
+        .await;
+        assert_eq!(
+            GlobPattern::String("/a[[]1[]]/*.toml".to_string()),
+            watchers[0].glob_pattern
+        );
+
+        let matcher = WatcherMatcher::new(&watchers[0]).unwrap();
+        assert!(matcher.is_match(&event_mock("/a[1]/Cargo.toml", FileChangeType::CHANGED)));
+        assert!(!matcher.is_match(&event_mock("/a1/Cargo.toml", FileChangeType::CHANGED)));
+    }
+
+    #[tokio::test]
+    async fn dispatches_events_to_matching_watches() {
+        let (mut file_watchers, mut outgoing_rx, pending_responses) = file_watchers_mock(true);
+        let (mut watch, watchers) = watch_relative_mock(
+            &file_watchers,
+            &mut outgoing_rx,
+            &pending_responses,
+            "a",
+            "**/*.toml",
+        )
+        .await;
+        assert!(matches!(watchers[0].glob_pattern, GlobPattern::Relative(_)));
+
+        let matching_event = event_mock("/a/crates/b/Cargo.toml", FileChangeType::CHANGED);
+        file_watchers.inspect_incoming(&did_change_watched_files_mock(vec![
+            event_mock("/a/src/lib.rs", FileChangeType::CHANGED),
+            matching_event.clone(),
+        ]));
+        assert_eq!(Some(matching_event), watch.next_event().await);
+        assert!(watch.events_rx.try_next().is_err());
+    }
+
+    #[tokio::test]
+    async fn dispatches_events_sent_along_with_acknowledgement() {
+        let (mut file_watchers, mut outgoing_rx, pending_responses) = file_watchers_mock(true);
+        let watch_task = tokio::spawn({
+            let file_watchers = file_watchers.clone();
+            async move {
+                file_watchers
+                    .watch(vec![FileSystemWatcher {
+                        glob_pattern: GlobPattern::String("**/*.toml".to_string()),
+                        kind: None,
+                    }])
+                    .await
+            }
+        });
+
+        let event = event_mock("/a/Cargo.toml", FileChangeType::CREATED);
+        let AllMessages::Requests(AllRequests::Client(AllClientRequests::RegisterCapability(
+            request,
+        ))) = outgoing_rx.next().await.unwrap()
+        else {
+            panic!("expected a registration request")
+        };
+        file_watchers.inspect_incoming(&did_change_watched_files_mock(vec![event.clone()]));
+        pending_responses
+            .resolve(UntypedResponseMessage {
+                id: ResponseId::from(request.id),
+                kind: Ok(Value::Null),
+            })
+            .unwrap();
+
+        let mut watch = watch_task.await.unwrap().unwrap();
+        assert_eq!(Some(event), watch.next_event().await);
+    }
+
+    #[tokio::test]
+    async fn forgets_watches_failing_to_register() {
+        let (outbox, _outgoing_rx, _pending_responses) = Outbox::new(None);
+        let file_watchers = FileWatchers::new(DynamicRegistrations::new(outbox));
+        assert!(matches!(
+            file_watchers.watch(Vec::new()).await,
+            Err(FileWatchError::Registration(
+                RegistrationError::Unsupported(_)
+            ))
+        ));
+        assert!(file_watchers
+            .watches
+            .lock()
+            .expect(FILE_WATCHES_POISONED)
+            .is_empty());
+    }
+}
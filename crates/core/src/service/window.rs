@@ -0,0 +1,240 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use futures::channel::mpsc::UnboundedSender;
+use lsp_types::{
+    notification::{LogMessage, ShowMessage},
+    LogMessageParams, MessageType, ShowMessageParams,
+};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::{layer::Context, Layer};
+
+use crate::messages::{core::notification::NotificationMessage, groups::AllMessages};
+
+use super::trace::layer::{is_pipeline_event, FieldVisitor};
+
+const DEFAULT_MAX_MESSAGES: usize = 20;
+const DEFAULT_RATE_LIMIT_PERIOD: Duration = Duration::from_secs(1);
+
+/// Forwards `tracing` events to the client of a [`Service`](super::Service) as
+/// `window/logMessage` notifications, and escalates the most severe ones to
+/// `window/showMessage`.
+///
+/// Events are rate limited, so that a burst of them doesn't flood the editor. The count of
+/// events suppressed is logged along with the next event let through.
+pub struct WindowMessageLayer {
+    notification_tx: UnboundedSender<AllMessages>,
+    log_level: Level,
+    show_level: Option<Level>,
+    rate_limit: Mutex<RateLimit>,
+}
+
+impl WindowMessageLayer {
+    pub(crate) fn new(notification_tx: UnboundedSender<AllMessages>) -> Self {
+        Self {
+            notification_tx,
+            log_level: Level::INFO,
+            show_level: None,
+            rate_limit: Mutex::new(RateLimit::new(
+                DEFAULT_MAX_MESSAGES,
+                DEFAULT_RATE_LIMIT_PERIOD,
+            )),
+        }
+    }
+
+    /// Logs events at the level or more severe ones, `INFO` by default.
+    pub fn with_log_level(mut self, level: Level) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// Also shows events at the level or more severe ones to the user, none by default.
+    pub fn with_show_level(mut self, level: Level) -> Self {
+        self.show_level = Some(level);
+        self
+    }
+
+    /// Lets through at most `max_messages` events per `period`, 20 per second by default.
+    pub fn with_rate_limit(mut self, max_messages: usize, period: Duration) -> Self {
+        self.rate_limit = Mutex::new(RateLimit::new(max_messages, period));
+        self
+    }
+
+    fn send(&self, message: AllMessages) {
+        // Events may outlive the service, with nobody left to read them.
+        let _ = self.notification_tx.unbounded_send(message);
+    }
+
+    fn log_message(&self, typ: MessageType, message: String) {
+        let notification = NotificationMessage::<LogMessage> {
+            params: Some(LogMessageParams { typ, message }),
+        };
+        self.send(AllMessages::Notifications(notification.into()))
+    }
+
+    fn show_message(&self, typ: MessageType, message: String) {
+        let notification = NotificationMessage::<ShowMessage> {
+            params: Some(ShowMessageParams { typ, message }),
+        };
+        self.send(AllMessages::Notifications(notification.into()))
+    }
+}
+
+impl<S: Subscriber> Layer<S> for WindowMessageLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        // Levels compare as more verbose being greater.
+        let logged = *metadata.level() <= self.log_level;
+        let shown = self
+            .show_level
+            .is_some_and(|show_level| *metadata.level() <= show_level);
+        if !logged && !shown || is_pipeline_event(metadata) {
+            return;
+        }
+
+        let suppressed_count = match self
+            .rate_limit
+            .lock()
+            .expect("lock poisoned")
+            .admit(Instant::now())
+        {
+            Some(suppressed_count) => suppressed_count,
+            None => return,
+        };
+        if suppressed_count > 0 {
+            self.log_message(
+                MessageType::WARNING,
+                format!("{} messages suppressed by rate limiting.", suppressed_count),
+            )
+        }
+
+        let typ = message_type(metadata.level());
+        let message = FieldVisitor::format_event(event);
+        if shown {
+            self.show_message(typ, message.clone())
+        }
+        if logged {
+            self.log_message(typ, message)
+        }
+    }
+}
+
+fn message_type(level: &Level) -> MessageType {
+    match *level {
+        Level::ERROR => MessageType::ERROR,
+        Level::WARN => MessageType::WARNING,
+        Level::INFO => MessageType::INFO,
+        _ => MessageType::LOG,
+    }
+}
+
+/// Admits a fixed number of messages per period, counting those suppressed beyond that.
+struct RateLimit {
+    max_messages: usize,
+    period: Duration,
+    period_start: Option<Instant>,
+    admitted_count: usize,
+    suppressed_count: usize,
+}
+
+impl RateLimit {
+    fn new(max_messages: usize, period: Duration) -> Self {
+        Self {
+            max_messages,
+            period,
+            period_start: None,
+            admitted_count: 0,
+            suppressed_count: 0,
+        }
+    }
+
+    /// Returns the count of messages suppressed since the last admitted one, or `None` when the
+    /// message is to be suppressed.
+    fn admit(&mut self, now: Instant) -> Option<usize> {
+        if self
+            .period_start
+            .is_none_or(|period_start| now.duration_since(period_start) >= self.period)
+        {
+            self.period_start = Some(now);
+            self.admitted_count = 0;
+        }
+
+        if self.admitted_count >= self.max_messages {
+            self.suppressed_count += 1;
+            return None;
+        }
+        self.admitted_count += 1;
+        Some(std::mem::take(&mut self.suppressed_count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{channel::mpsc::unbounded, StreamExt};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::messages::groups::notifications::{AllClientNotifications, AllNotifications};
+
+    use super::*;
+
+    #[test]
+    fn limits_message_rate() {
+        let mut rate_limit = RateLimit::new(2, Duration::from_secs(1));
+        let start = Instant::now();
+
+        assert_eq!(Some(0), rate_limit.admit(start));
+        assert_eq!(Some(0), rate_limit.admit(start));
+        assert_eq!(None, rate_limit.admit(start + Duration::from_millis(500)));
+        assert_eq!(None, rate_limit.admit(start + Duration::from_millis(999)));
+        assert_eq!(Some(2), rate_limit.admit(start + Duration::from_secs(1)));
+        assert_eq!(Some(0), rate_limit.admit(start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn forwards_events_by_level() {
+        let (notification_tx, notification_rx) = unbounded();
+        let layer = WindowMessageLayer::new(notification_tx)
+            .with_show_level(Level::ERROR)
+            .with_rate_limit(2, Duration::from_secs(60));
+
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            tracing::debug!("Not forwarded.");
+            tracing::info!(path = "a.rs", "Indexed.");
+            tracing::error!("Index corrupted.");
+            tracing::error!("Rate limited.");
+        });
+
+        let notifications = futures::executor::block_on(notification_rx.collect::<Vec<_>>())
+            .into_iter()
+            .map(|notification| match notification {
+                AllMessages::Notifications(AllNotifications::Client(notification)) => notification,
+                notification => panic!("unexpected notification {:?}", notification),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(3, notifications.len());
+        assert!(matches!(
+            &notifications[0],
+            AllClientNotifications::LogMessage(NotificationMessage {
+                params: Some(LogMessageParams { typ: MessageType::INFO, message })
+            }) if message == "Indexed. path=\"a.rs\""
+        ));
+        assert!(matches!(
+            &notifications[1],
+            AllClientNotifications::ShowMessage(NotificationMessage {
+                params: Some(ShowMessageParams {
+                    typ: MessageType::ERROR,
+                    ..
+                })
+            })
+        ));
+        assert!(matches!(
+            &notifications[2],
+            AllClientNotifications::LogMessage(NotificationMessage {
+                params: Some(LogMessageParams { typ: MessageType::ERROR, message })
+            }) if message == "Index corrupted."
+        ));
+    }
+}
@@ -0,0 +1,47 @@
+pub(crate) mod recorder;
+mod replay;
+mod trace;
+
+pub use recorder::SessionRecorder;
+pub use replay::{replay, ReplayMismatch, ReplayReport, ReplayRules};
+pub use trace::{
+    parse_inspector_log, parse_vscode_trace, write_inspector_log, write_vscode_trace,
+    TraceParseError,
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Client to server.
+    Incoming,
+    /// Server to client.
+    Outgoing,
+}
+
+/// A single line of a JSONL session log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionEntry {
+    pub direction: Direction,
+    /// Microseconds since the recording started.
+    pub timestamp_us: u64,
+    /// Message content without its headers.
+    pub body: String,
+    /// Set when the frame failed to decode, the body is left empty then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Reads a JSONL session log as written by the [`SessionRecorder`], skipping empty lines.
+pub fn read_session_log(path: impl AsRef<std::path::Path>) -> std::io::Result<Vec<SessionEntry>> {
+    parse_session_log(&std::fs::read_to_string(path)?)
+}
+
+pub fn parse_session_log(session_log: &str) -> std::io::Result<Vec<SessionEntry>> {
+    session_log
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(std::io::Error::from))
+        .collect()
+}
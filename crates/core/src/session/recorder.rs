@@ -0,0 +1,101 @@
+use std::{
+    io::{LineWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use super::{Direction, SessionEntry};
+
+#[cfg(feature = "tracing-layers")]
+pub(crate) const TRACING_TARGET: &str = module_path!();
+
+const RECORDER_LOCK_POISONED: &str = "session recorder lock poisoned";
+
+/// Appends every message a service reads or writes to a JSONL session log.
+///
+/// Writes are blocking but line buffered, the recorder is meant for debugging sessions rather
+/// than production traffic.
+#[derive(Clone)]
+pub struct SessionRecorder(Arc<RecorderInner>);
+
+struct RecorderInner {
+    started_at: Instant,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl SessionRecorder {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self(Arc::new(RecorderInner {
+            started_at: Instant::now(),
+            writer: Mutex::new(Box::new(writer)),
+        }))
+    }
+
+    /// Truncates any existing log at `path`.
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        std::fs::File::create(path).map(|file| Self::new(LineWriter::new(file)))
+    }
+
+    pub(crate) fn record(&self, direction: Direction, body: &[u8], error: Option<String>) {
+        let entry = SessionEntry {
+            direction,
+            timestamp_us: self.0.started_at.elapsed().as_micros() as u64,
+            body: String::from_utf8_lossy(body).into_owned(),
+            error,
+        };
+
+        let mut writer = self.0.writer.lock().expect(RECORDER_LOCK_POISONED);
+        let write_result = serde_json::to_writer(&mut *writer, &entry)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"))
+            .and_then(|_| writer.flush());
+
+        if let Err(err) = write_result {
+            tracing::warn!(?err, "Unable to write session log entry.")
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedBuffer {
+        pub fn entries(&self) -> Vec<SessionEntry> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_entries_as_json_lines() {
+        let buffer = SharedBuffer::default();
+        let recorder = SessionRecorder::new(buffer.clone());
+        recorder.record(Direction::Incoming, br#"{"jsonrpc":"2.0"}"#, None);
+        recorder.record(Direction::Outgoing, b"{", Some("EOF".to_owned()));
+
+        let entries = buffer.entries();
+        assert_eq!(2, entries.len());
+        assert_eq!(r#"{"jsonrpc":"2.0"}"#, entries[0].body);
+        assert_eq!(None, entries[0].error);
+        assert_eq!(Direction::Outgoing, entries[1].direction);
+        assert_eq!(Some("EOF".to_owned()), entries[1].error);
+    }
+}
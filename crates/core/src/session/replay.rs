@@ -0,0 +1,303 @@
+use std::{fmt::Display, future::Future, time::Duration};
+
+use futures::{future::Either, StreamExt};
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncWriteExt};
+use tokio_util::codec::FramedRead;
+
+use crate::{
+    messages::{
+        codec::LanguageServerCodec, groups::raw::RawMessage, payload::headers::JsonRpcHeaders,
+    },
+    service::{filter::MessageFilter, Service, ServiceBackend},
+};
+
+use super::{Direction, SessionEntry};
+
+/// What to disregard when comparing replayed output with the recording. Entry timestamps are
+/// never compared.
+#[derive(Debug, Clone)]
+pub struct ReplayRules {
+    /// Ignores the `id` of outgoing messages.
+    pub ignore_request_ids: bool,
+    /// Compares outgoing notifications regardless of their order.
+    pub ignore_notification_order: bool,
+    /// JSON pointers to fields left out of the comparison, such as timestamps within messages.
+    pub ignored_pointers: Vec<String>,
+    /// How long to wait for the outgoing messages recorded before each incoming one.
+    pub wait_timeout: Duration,
+}
+
+impl Default for ReplayRules {
+    fn default() -> Self {
+        Self {
+            ignore_request_ids: false,
+            ignore_notification_order: false,
+            ignored_pointers: Vec::new(),
+            wait_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ReplayRules {
+    fn normalize(&self, mut message: Value) -> Value {
+        if self.ignore_request_ids {
+            if let Some(message) = message.as_object_mut() {
+                message.remove("id");
+            }
+        }
+
+        for ignored_pointer in &self.ignored_pointers {
+            let Some((parent_pointer, key)) = ignored_pointer.rsplit_once('/') else {
+                continue;
+            };
+            if let Some(parent) = message
+                .pointer_mut(parent_pointer)
+                .and_then(Value::as_object_mut)
+            {
+                parent.remove(key);
+            }
+        }
+
+        message
+    }
+}
+
+/// An outgoing message differing from the recording, or missing on either side.
+#[derive(Debug, PartialEq)]
+pub struct ReplayMismatch {
+    pub expected: Option<Value>,
+    pub actual: Option<Value>,
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+impl ReplayReport {
+    pub fn is_match(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl Display for ReplayReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for mismatch in &self.mismatches {
+            let format_message = |message: &Option<Value>| match message {
+                Some(message) => message.to_string(),
+                None => "<none>".to_owned(),
+            };
+            writeln!(f, "expected: {}", format_message(&mismatch.expected))?;
+            writeln!(f, "  actual: {}", format_message(&mismatch.actual))?;
+        }
+        Ok(())
+    }
+}
+
+/// Feeds the recorded incoming messages through a [`Service`] running the given backend, and
+/// compares what it sends back with the recorded outgoing messages.
+///
+/// Before each incoming message, the replay waits for the backend to have sent as many messages
+/// as were recorded up to that point, so that responses to server requests aren't sent ahead of
+/// the requests themselves.
+pub async fn replay<F, B, Fut>(
+    session: &[SessionEntry],
+    rules: &ReplayRules,
+    backend: B,
+) -> ReplayReport
+where
+    F: MessageFilter,
+    B: FnOnce(ServiceBackend<F>) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let (service_input, mut client_output) = tokio::io::duplex(64 * 1024);
+    let (client_input, service_output) = tokio::io::duplex(64 * 1024);
+
+    let client = async move {
+        let mut framed_input =
+            FramedRead::new(client_input, LanguageServerCodec::<RawMessage>::default());
+        let mut expected = Vec::new();
+        let mut actual = Vec::new();
+
+        for entry in session {
+            match entry.direction {
+                Direction::Outgoing => expected.push(
+                    serde_json::from_str(&entry.body)
+                        .unwrap_or_else(|_| Value::String(entry.body.clone())),
+                ),
+                Direction::Incoming => {
+                    receive(&mut framed_input, &mut actual, expected.len(), rules).await;
+                    // Failed frames are replayed with valid headers, header errors therefore
+                    // resurface as content errors.
+                    let headers = JsonRpcHeaders {
+                        content_length: entry.body.len(),
+                    };
+                    let frame = format!("{}\r\n{}", headers, entry.body);
+                    if client_output.write_all(frame.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Lets the service shut down as if the client had disconnected.
+        drop(client_output);
+        receive(&mut framed_input, &mut actual, usize::MAX, rules).await;
+
+        (expected, actual)
+    };
+
+    let service = Service::<F, _, _>::new(service_input, service_output).run(backend);
+    let (expected, actual) =
+        match futures::future::select(std::pin::pin!(client), std::pin::pin!(service)).await {
+            Either::Left((messages, _)) => messages,
+            Either::Right(((), client)) => client.await,
+        };
+
+    compare(expected, actual, rules)
+}
+
+async fn receive(
+    framed_input: &mut FramedRead<impl AsyncRead + Unpin, LanguageServerCodec<RawMessage>>,
+    received: &mut Vec<Value>,
+    count: usize,
+    rules: &ReplayRules,
+) {
+    while received.len() < count {
+        match tokio::time::timeout(rules.wait_timeout, framed_input.next()).await {
+            Ok(Some(Ok(message))) => received.push(message.0),
+            Ok(Some(Err(err))) => tracing::warn!(%err, "Unable to decode replayed message."),
+            Ok(None) | Err(_) => break,
+        }
+    }
+}
+
+fn compare(expected: Vec<Value>, actual: Vec<Value>, rules: &ReplayRules) -> ReplayReport {
+    let is_notification = |message: &Value| {
+        rules.ignore_notification_order
+            && message.get("method").is_some()
+            && message.get("id").is_none()
+    };
+    let (expected_notifications, expected): (Vec<_>, Vec<_>) =
+        expected.into_iter().partition(is_notification);
+    let (mut actual_notifications, actual): (Vec<_>, Vec<_>) =
+        actual.into_iter().partition(is_notification);
+
+    let mut report = ReplayReport::default();
+    let mut expected = expected.into_iter().map(|message| rules.normalize(message));
+    let mut actual = actual.into_iter().map(|message| rules.normalize(message));
+    loop {
+        match (expected.next(), actual.next()) {
+            (None, None) => break,
+            (expected, actual) if expected == actual => continue,
+            (expected, actual) => report.mismatches.push(ReplayMismatch { expected, actual }),
+        }
+    }
+
+    actual_notifications = actual_notifications
+        .into_iter()
+        .map(|message| rules.normalize(message))
+        .collect();
+    for expected in expected_notifications {
+        let expected = rules.normalize(expected);
+        match actual_notifications
+            .iter()
+            .position(|actual| actual == &expected)
+        {
+            Some(actual_index) => {
+                actual_notifications.remove(actual_index);
+            }
+            None => report.mismatches.push(ReplayMismatch {
+                expected: Some(expected),
+                actual: None,
+            }),
+        }
+    }
+    report.mismatches.extend(
+        actual_notifications
+            .into_iter()
+            .map(|actual| ReplayMismatch {
+                expected: None,
+                actual: Some(actual),
+            }),
+    );
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        service::{
+            filter::tests::FilterMock,
+            server::tests::{assert_shutdown_roundtrip, shutdown_backend_mock},
+        },
+        session::{recorder::tests::SharedBuffer, SessionRecorder},
+    };
+
+    use super::*;
+
+    async fn record_shutdown_session() -> Vec<SessionEntry> {
+        let (service_input, client_output) = tokio::io::duplex(1024);
+        let (client_input, service_output) = tokio::io::duplex(1024);
+        let session_log = SharedBuffer::default();
+
+        let service_handle = tokio::spawn(
+            Service::<FilterMock, _, _>::new(service_input, service_output)
+                .with_recorder(SessionRecorder::new(session_log.clone()))
+                .run(shutdown_backend_mock),
+        );
+        assert_shutdown_roundtrip(client_input, client_output).await;
+        service_handle.await.unwrap();
+
+        session_log.entries()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn replays_recorded_session() {
+        let session = record_shutdown_session().await;
+        let report =
+            replay::<FilterMock, _, _>(&session, &ReplayRules::default(), shutdown_backend_mock)
+                .await;
+
+        assert!(report.is_match(), "{}", report)
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn reports_missing_messages() {
+        let session = record_shutdown_session().await;
+        let rules = ReplayRules {
+            wait_timeout: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let report = replay::<FilterMock, _, _>(&session, &rules, |_| async {}).await;
+
+        assert_eq!(1, report.mismatches.len());
+        assert!(report.mismatches[0].actual.is_none())
+    }
+
+    #[test]
+    fn applies_ignore_rules() {
+        let rules = ReplayRules {
+            ignore_request_ids: true,
+            ignore_notification_order: true,
+            ignored_pointers: vec!["/params/timestamp".to_owned()],
+            ..Default::default()
+        };
+        let expected = vec![
+            json!({ "method": "a", "params": { "timestamp": 1 } }),
+            json!({ "method": "b" }),
+            json!({ "id": 1, "result": null }),
+        ];
+        let actual = vec![
+            json!({ "id": 2, "result": null }),
+            json!({ "method": "b" }),
+            json!({ "method": "a", "params": { "timestamp": 2 } }),
+        ];
+
+        assert!(compare(expected, actual, &rules).is_match())
+    }
+}
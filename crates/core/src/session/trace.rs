@@ -0,0 +1,504 @@
+//! VS Code `"trace.server": "verbose"` output and the JSON log of the LSP Inspector, both
+//! written from the perspective of the client.
+
+use std::collections::HashMap;
+
+use derive_more::Display;
+use serde_json::{json, ser::PrettyFormatter, Serializer, Value};
+
+use crate::messages::groups::{raw::RawMessage, AllMessages};
+
+use super::{Direction, SessionEntry};
+
+const TRACE_PREFIX: &str = "[Trace - ";
+const INSPECTOR_PREFIX: &str = "[LSP   - ";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Display)]
+#[display(fmt = "line {}: {}", line, reason)]
+pub struct TraceParseError {
+    pub line: usize,
+    pub reason: String,
+}
+
+/// Parses the verbose text trace of the VS Code language client. Timestamps are relative to the
+/// first traced message, and only have the precision of the trace apart from responses, which are
+/// placed after their request by the latency that VS Code reports.
+///
+/// Messages not decoding as [`AllMessages`] are kept with the decode error, like frames failing
+/// to decode in a recorded session.
+pub fn parse_vscode_trace(trace: &str) -> Result<Vec<SessionEntry>, TraceParseError> {
+    let mut entries = Vec::new();
+    let mut clock = RelativeClock::default();
+    let mut request_timestamps = HashMap::new();
+    let lines = trace.lines().collect::<Vec<_>>();
+
+    let mut line_index = 0;
+    while line_index < lines.len() {
+        let header_index = line_index;
+        line_index += 1;
+        let Some(header) = lines[header_index].strip_prefix(TRACE_PREFIX) else {
+            continue;
+        };
+
+        let data_end = lines[line_index..]
+            .iter()
+            .position(|line| line.starts_with('['))
+            .map_or(lines.len(), |offset| line_index + offset);
+        let data = lines[line_index..data_end].join("\n");
+        line_index = data_end;
+
+        let error = |reason: &str| TraceParseError {
+            line: header_index + 1,
+            reason: reason.to_owned(),
+        };
+        let (time, header) = header
+            .split_once("] ")
+            .ok_or_else(|| error("malformed header"))?;
+        let mut timestamp_us =
+            clock.timestamp_us(parse_time(time).ok_or_else(|| error("malformed time"))?);
+        let traced = parse_header(header).ok_or_else(|| error("unrecognized trace header"))?;
+        let data = parse_data(&data).map_err(|err| error(&err.to_string()))?;
+
+        let message = match traced.kind {
+            "request" => {
+                let id = traced.id.ok_or_else(|| error("request without id"))?;
+                request_timestamps.insert((traced.direction, id.clone()), timestamp_us);
+                RawMessage::request(id, traced.name, data)
+            }
+            "notification" => RawMessage::notification(traced.name, data.unwrap_or(Value::Null)),
+            _ => {
+                let id = traced.id.ok_or_else(|| error("response without id"))?;
+                let request_direction = opposite(traced.direction);
+                if let (Some(request_timestamp_us), Some(latency_ms)) = (
+                    request_timestamps.remove(&(request_direction, id.clone())),
+                    traced.latency_ms,
+                ) {
+                    timestamp_us = request_timestamp_us + latency_ms * 1000;
+                }
+                let result = match traced.error {
+                    Some((message, code)) => {
+                        let mut error = json!({ "code": code, "message": message });
+                        if let Some(data) = data {
+                            error["data"] = data;
+                        }
+                        Err(error)
+                    }
+                    None => Ok(data.unwrap_or(Value::Null)),
+                };
+                RawMessage::response(id, result)
+            }
+        };
+
+        entries.push(session_entry(traced.direction, timestamp_us, message));
+    }
+
+    Ok(entries)
+}
+
+/// Writes entries as the verbose text trace of the VS Code language client, leaving out those
+/// whose body isn't JSON. Timestamps are written as times of day counted from midnight.
+pub fn write_vscode_trace(entries: &[SessionEntry]) -> String {
+    let mut trace = String::new();
+    let mut requests = HashMap::new();
+
+    for (entry, message) in json_entries(entries) {
+        let (verb, response_latency) = match entry.direction {
+            Direction::Incoming => ("Sending", "Processing request took"),
+            Direction::Outgoing => ("Received", "in"),
+        };
+        let id = message.display_id().unwrap_or_default();
+        let id_key = message.id_key();
+
+        let (header, data) = match message.method() {
+            Some(method) if message.is_request() => {
+                requests.insert(
+                    (entry.direction, id_key),
+                    (method.to_owned(), entry.timestamp_us),
+                );
+                (
+                    format!("{} request '{} - ({})'.", verb, method, id),
+                    format_data("Params", message.params(), "No parameters provided."),
+                )
+            }
+            Some(method) => (
+                format!("{} notification '{}'.", verb, method),
+                format_data("Params", message.params(), "No parameters provided."),
+            ),
+            None => {
+                let (method, request_timestamp_us) = requests
+                    .remove(&(opposite(entry.direction), id_key))
+                    .unwrap_or(("unknown".to_owned(), entry.timestamp_us));
+                let latency_ms = entry.timestamp_us.saturating_sub(request_timestamp_us) / 1000;
+                let mut header = format!(
+                    "{} response '{} - ({})' {} {}ms.",
+                    verb, method, id, response_latency, latency_ms
+                );
+
+                let data = match message.clone().into_result() {
+                    Ok(result) => format_data("Result", Some(&result), "No result returned."),
+                    Err(error) => {
+                        header.push_str(&format!(
+                            " Request failed: {} ({}).",
+                            error["message"].as_str().unwrap_or_default(),
+                            error["code"]
+                        ));
+                        format_data("Error data", error.get("data"), "No result returned.")
+                    }
+                };
+                (header, data)
+            }
+        };
+
+        trace.push_str(&format!(
+            "{}{}] {}\n{}\n\n\n",
+            TRACE_PREFIX,
+            format_time(entry.timestamp_us),
+            header,
+            data
+        ));
+    }
+
+    trace
+}
+
+/// Parses the JSON log of the LSP Inspector, one `[LSP - time] { ... }` record per line.
+/// Timestamps are relative to the first record.
+pub fn parse_inspector_log(log: &str) -> Result<Vec<SessionEntry>, TraceParseError> {
+    let mut entries = Vec::new();
+    let mut first_timestamp_ms = None;
+
+    for (line_index, line) in log.lines().enumerate() {
+        let Some(record) = line
+            .find('{')
+            .filter(|_| line.starts_with("[LSP"))
+            .map(|start| &line[start..])
+        else {
+            continue;
+        };
+
+        let error = |reason: String| TraceParseError {
+            line: line_index + 1,
+            reason,
+        };
+        let mut record: Value =
+            serde_json::from_str(record).map_err(|err| error(err.to_string()))?;
+        let direction = match record["type"].as_str() {
+            Some(kind) if kind.starts_with("send-") => Direction::Incoming,
+            Some(kind) if kind.starts_with("receive-") => Direction::Outgoing,
+            _ => return Err(error(format!("unknown record type {}", record["type"]))),
+        };
+        let timestamp_ms = record["timestamp"].as_u64().unwrap_or_default();
+        let first_timestamp_ms = *first_timestamp_ms.get_or_insert(timestamp_ms);
+
+        entries.push(session_entry(
+            direction,
+            timestamp_ms.saturating_sub(first_timestamp_ms) * 1000,
+            RawMessage(record["message"].take()),
+        ));
+    }
+
+    Ok(entries)
+}
+
+/// Writes entries as the JSON log of the LSP Inspector, leaving out those whose body isn't JSON.
+/// Record timestamps are the milliseconds since the start of the session.
+pub fn write_inspector_log(entries: &[SessionEntry]) -> String {
+    let mut log = String::new();
+
+    for (entry, message) in json_entries(entries) {
+        let action = match entry.direction {
+            Direction::Incoming => "send",
+            Direction::Outgoing => "receive",
+        };
+        let kind = match (message.is_request(), message.is_notification()) {
+            (true, _) => "request",
+            (_, true) => "notification",
+            _ => "response",
+        };
+        let record = json!({
+            "isLSPMessage": true,
+            "type": format!("{}-{}", action, kind),
+            "message": message.0,
+            "timestamp": entry.timestamp_us / 1000,
+        });
+
+        log.push_str(&format!(
+            "{}{}] {}\n",
+            INSPECTOR_PREFIX,
+            format_time(entry.timestamp_us),
+            record
+        ));
+    }
+
+    log
+}
+
+/// The parts of a trace header such as `Received response 'shutdown - (1)' in 3ms.`
+struct TracedHeader<'a> {
+    direction: Direction,
+    kind: &'a str,
+    name: &'a str,
+    id: Option<Value>,
+    latency_ms: Option<u64>,
+    error: Option<(&'a str, i64)>,
+}
+
+fn parse_header(header: &str) -> Option<TracedHeader<'_>> {
+    let (direction, header) = match header.split_once(' ')? {
+        ("Sending", header) => (Direction::Incoming, header),
+        ("Received", header) => (Direction::Outgoing, header),
+        _ => return None,
+    };
+    let (kind, header) = header.split_once(" '")?;
+    if !matches!(kind, "request" | "notification" | "response") {
+        return None;
+    }
+    let (name, details) = header.split_once('\'')?;
+
+    let (name, id) = match kind {
+        "notification" => (name, None),
+        _ => {
+            let (name, id) = name.rsplit_once(" - (")?;
+            (name, Some(parse_id(id.strip_suffix(')')?)))
+        }
+    };
+
+    let latency_ms = details
+        .split_once("ms")
+        .and_then(|(before, _)| before.rsplit(' ').next()?.parse().ok());
+
+    let error = details
+        .split_once("Request failed: ")
+        .and_then(|(_, failure)| {
+            let (message, code) = failure.trim_end_matches('.').rsplit_once(" (")?;
+            Some((message, code.strip_suffix(')')?.parse().ok()?))
+        });
+
+    Some(TracedHeader {
+        direction,
+        kind,
+        name,
+        id,
+        latency_ms,
+        error,
+    })
+}
+
+/// Reads the `Params: ...`, `Result: ...` or `Error data: ...` following a trace header.
+fn parse_data(data: &str) -> serde_json::Result<Option<Value>> {
+    let data = data.trim();
+    ["Params: ", "Result: ", "Error data: "]
+        .iter()
+        .find_map(|label| data.strip_prefix(label))
+        .map(serde_json::from_str)
+        .transpose()
+}
+
+fn format_data(label: &str, data: Option<&Value>, absent: &str) -> String {
+    match data {
+        Some(data) => {
+            let mut pretty = Vec::new();
+            let mut serializer =
+                Serializer::with_formatter(&mut pretty, PrettyFormatter::with_indent(b"    "));
+            serde::Serialize::serialize(data, &mut serializer).expect("values should serialize");
+            format!("{}: {}", label, String::from_utf8_lossy(&pretty))
+        }
+        None => absent.to_owned(),
+    }
+}
+
+fn parse_id(id: &str) -> Value {
+    id.parse::<i64>()
+        .map_or_else(|_| Value::from(id), Value::from)
+}
+
+/// Seconds since midnight of times such as `10:31:24 AM` or `22:31:24`.
+fn parse_time(time: &str) -> Option<u64> {
+    let (clock, meridiem) = match time.split_once(' ') {
+        Some((clock, meridiem)) => (clock, Some(meridiem)),
+        None => (time, None),
+    };
+    let mut fields = clock.split(':').map(|field| field.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (fields.next()??, fields.next()??, fields.next()??);
+
+    let hours = match meridiem {
+        Some("AM") => hours % 12,
+        Some("PM") => hours % 12 + 12,
+        Some(_) => return None,
+        None => hours,
+    };
+    Some(hours * 60 * 60 + minutes * 60 + seconds)
+}
+
+fn format_time(timestamp_us: u64) -> String {
+    let seconds = timestamp_us / 1_000_000 % SECONDS_PER_DAY;
+    let hours = seconds / (60 * 60);
+    let meridiem = if hours < 12 { "AM" } else { "PM" };
+    let twelve_hours = match hours % 12 {
+        0 => 12,
+        hours => hours,
+    };
+    format!(
+        "{}:{:02}:{:02} {}",
+        twelve_hours,
+        seconds / 60 % 60,
+        seconds % 60,
+        meridiem
+    )
+}
+
+/// Turns times of day into microseconds since the first one, assuming that a time earlier than
+/// the previous one is on the next day.
+#[derive(Default)]
+struct RelativeClock {
+    first_seconds: Option<u64>,
+    previous_seconds: u64,
+    elapsed_days: u64,
+}
+
+impl RelativeClock {
+    fn timestamp_us(&mut self, seconds: u64) -> u64 {
+        let first_seconds = *self.first_seconds.get_or_insert(seconds);
+        if seconds < self.previous_seconds {
+            self.elapsed_days += 1;
+        }
+        self.previous_seconds = seconds;
+
+        (self.elapsed_days * SECONDS_PER_DAY + seconds).saturating_sub(first_seconds) * 1_000_000
+    }
+}
+
+fn opposite(direction: Direction) -> Direction {
+    match direction {
+        Direction::Incoming => Direction::Outgoing,
+        Direction::Outgoing => Direction::Incoming,
+    }
+}
+
+fn session_entry(direction: Direction, timestamp_us: u64, message: RawMessage) -> SessionEntry {
+    let error = serde_json::from_value::<AllMessages>(message.0.clone())
+        .err()
+        .map(|err| err.to_string());
+
+    SessionEntry {
+        direction,
+        timestamp_us,
+        body: message.0.to_string(),
+        error,
+    }
+}
+
+fn json_entries(entries: &[SessionEntry]) -> impl Iterator<Item = (&SessionEntry, RawMessage)> {
+    entries.iter().filter_map(|entry| {
+        serde_json::from_str(&entry.body)
+            .ok()
+            .map(|message| (entry, message))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    const VSCODE_TRACE_MOCK: &str = indoc! {r#"
+        [Trace - 11:59:58 PM] Sending request 'shutdown - (1)'.
+        No parameters provided.
+
+
+        [Info  - 11:59:59 PM] Unrelated output
+        [Trace - 11:59:59 PM] Received request 'workspace/configuration - (abc)'.
+        Params: {
+            "items": []
+        }
+
+
+        [Trace - 12:00:00 AM] Sending response 'workspace/configuration - (abc)'. Processing request took 1ms
+        Result: []
+
+
+        [Trace - 12:00:01 AM] Received response 'shutdown - (1)' in 2500ms. Request failed: Busy (-32803).
+        No result returned.
+
+
+    "#};
+
+    #[test]
+    fn parses_vscode_traces() {
+        let entries = parse_vscode_trace(VSCODE_TRACE_MOCK).unwrap();
+
+        let messages = entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.direction,
+                    entry.timestamp_us,
+                    entry.body.parse::<Value>().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (
+                    Direction::Incoming,
+                    0,
+                    json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" })
+                ),
+                (
+                    Direction::Outgoing,
+                    1_000_000,
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": "abc",
+                        "method": "workspace/configuration",
+                        "params": { "items": [] }
+                    })
+                ),
+                (
+                    Direction::Incoming,
+                    1_001_000,
+                    json!({ "jsonrpc": "2.0", "id": "abc", "result": [] })
+                ),
+                (
+                    Direction::Outgoing,
+                    2_500_000,
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "error": { "code": -32803, "message": "Busy" }
+                    })
+                ),
+            ],
+            messages
+        );
+        assert!(entries.iter().all(|entry| entry.error.is_none()));
+    }
+
+    #[test]
+    fn rejects_unrecognized_trace_headers() {
+        let error = parse_vscode_trace("[Trace - 10:00:00 AM] Something else\n").unwrap_err();
+        assert_eq!(1, error.line)
+    }
+
+    #[test]
+    fn round_trips_vscode_traces() {
+        let entries = parse_vscode_trace(VSCODE_TRACE_MOCK).unwrap();
+        assert_eq!(
+            entries,
+            parse_vscode_trace(&write_vscode_trace(&entries)).unwrap()
+        );
+    }
+
+    #[test]
+    fn round_trips_inspector_logs() {
+        let entries = parse_vscode_trace(VSCODE_TRACE_MOCK).unwrap();
+        let log = write_inspector_log(&entries);
+
+        assert!(log.starts_with(
+            r#"[LSP   - 12:00:00 AM] {"isLSPMessage":true,"message":{"id":1,"jsonrpc":"2.0","method":"shutdown"},"timestamp":0,"type":"send-request"}"#
+        ));
+        assert_eq!(entries, parse_inspector_log(&log).unwrap());
+    }
+}
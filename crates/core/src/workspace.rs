@@ -0,0 +1,329 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+use lsp_types::{
+    request::WorkspaceConfiguration, ConfigurationItem, ConfigurationParams, InitializeParams,
+    WorkspaceFolder, WorkspaceFoldersChangeEvent,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::{
+    messages::groups::{
+        notifications::{AllNotifications, AllServerNotifications},
+        requests::{AllRequests, AllServerRequests},
+        AllMessages,
+    },
+    outbox::{Outbox, RequestError},
+    service::filter::MessageHook,
+};
+
+/// Settings deserialized from a single configuration section of the client.
+pub trait ConfigurationSection: DeserializeOwned + Default + Clone + Send + Sync + 'static {
+    /// Asked for in `workspace/configuration` requests, such as `"rust-analyzer"`.
+    const SECTION: &'static str;
+}
+
+/// The workspace folders and configuration of the client, kept up to date when added to a
+/// [`Service`](crate::service::Service) with
+/// [`with_workspace`](crate::service::Service::with_workspace).
+///
+/// Configuration is pulled with `workspace/configuration` once the client is initialized and
+/// again on each `workspace/didChangeConfiguration`. Clients unable to handle the request get
+/// the section read from the pushed settings instead.
+#[derive(Clone)]
+pub struct Workspace<C: ConfigurationSection> {
+    outbox: Outbox,
+    folders_tx: Arc<watch::Sender<Vec<WorkspaceFolder>>>,
+    configuration_tx: Arc<watch::Sender<C>>,
+    /// Set by `InitializeParams.capabilities.workspace.configuration`.
+    client_pulls_configuration: Arc<AtomicBool>,
+    /// Lets only the latest of overlapping pulls through.
+    pull_generation: Arc<AtomicU64>,
+}
+
+impl<C: ConfigurationSection> Workspace<C> {
+    /// Pulls configuration through the outbox of the service, see
+    /// [`Service::outbox`](crate::service::Service::outbox).
+    pub fn new(outbox: Outbox) -> Self {
+        Self {
+            outbox,
+            folders_tx: Arc::new(watch::channel(Vec::new()).0),
+            configuration_tx: Arc::new(watch::channel(C::default()).0),
+            client_pulls_configuration: Arc::default(),
+            pull_generation: Arc::default(),
+        }
+    }
+
+    pub fn folders(&self) -> Vec<WorkspaceFolder> {
+        self.folders_tx.borrow().clone()
+    }
+
+    pub fn configuration(&self) -> C {
+        self.configuration_tx.borrow().clone()
+    }
+
+    /// Marked as changed whenever folders are added or removed.
+    pub fn watch_folders(&self) -> watch::Receiver<Vec<WorkspaceFolder>> {
+        self.folders_tx.subscribe()
+    }
+
+    /// Marked as changed whenever the configuration is updated, even if left the same.
+    pub fn watch_configuration(&self) -> watch::Receiver<C> {
+        self.configuration_tx.subscribe()
+    }
+
+    /// Unlike the pulls done on configuration changes, doesn't check whether the client
+    /// supports `workspace/configuration`.
+    pub async fn pull_configuration(&self) -> Result<(), RequestError> {
+        let generation = self.pull_generation.fetch_add(1, Ordering::Relaxed) + 1;
+        let params = ConfigurationParams {
+            items: vec![ConfigurationItem {
+                scope_uri: None,
+                section: Some(C::SECTION.to_string()),
+            }],
+        };
+        let section = self
+            .outbox
+            .send_request::<WorkspaceConfiguration>(Some(params))
+            .await?
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+
+        if self.pull_generation.load(Ordering::Relaxed) == generation {
+            self.update_configuration(section)
+        }
+        Ok(())
+    }
+
+    fn update_configuration(&self, section: Value) {
+        let configuration = match section {
+            Value::Null => C::default(),
+            section => match serde_json::from_value(section) {
+                Ok(configuration) => configuration,
+                Err(err) => {
+                    tracing::warn!(%err, section = C::SECTION, "Invalid configuration section.");
+                    return;
+                }
+            },
+        };
+        self.configuration_tx.send_replace(configuration);
+    }
+
+    fn spawn_pull(&self) {
+        let workspace = self.clone();
+        tokio::spawn(async move {
+            if let Err(err) = workspace.pull_configuration().await {
+                tracing::warn!(%err, "Unable to pull configuration.")
+            }
+        });
+    }
+
+    fn initialize(&self, params: &InitializeParams) {
+        let client_pulls_configuration = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.configuration)
+            .unwrap_or(false);
+        self.client_pulls_configuration
+            .store(client_pulls_configuration, Ordering::Relaxed);
+
+        #[allow(deprecated)]
+        let folders = params.workspace_folders.clone().unwrap_or_else(|| {
+            params
+                .root_uri
+                .iter()
+                .map(|uri| WorkspaceFolder {
+                    name: uri
+                        .path()
+                        .rsplit('/')
+                        .find(|segment| !segment.is_empty())
+                        .unwrap_or_default()
+                        .to_string(),
+                    uri: uri.clone(),
+                })
+                .collect()
+        });
+        self.folders_tx.send_replace(folders);
+    }
+
+    fn change_folders(&self, event: &WorkspaceFoldersChangeEvent) {
+        self.folders_tx.send_modify(|folders| {
+            folders.retain(|folder| {
+                !event
+                    .removed
+                    .iter()
+                    .any(|removed| removed.uri == folder.uri)
+            });
+            folders.extend(event.added.iter().cloned());
+        })
+    }
+}
+
+impl<C: ConfigurationSection> MessageHook for Workspace<C> {
+    fn inspect_incoming(&mut self, message: &AllMessages) {
+        match message {
+            AllMessages::Requests(AllRequests::Server(AllServerRequests::Initialize(request))) => {
+                if let Some(params) = &request.params {
+                    self.initialize(params)
+                }
+            }
+            AllMessages::Notifications(AllNotifications::Server(notification)) => {
+                match notification {
+                    AllServerNotifications::Initialized(_)
+                        if self.client_pulls_configuration.load(Ordering::Relaxed) =>
+                    {
+                        self.spawn_pull()
+                    }
+                    AllServerNotifications::DidChangeNotification(notification) => {
+                        if self.client_pulls_configuration.load(Ordering::Relaxed) {
+                            self.spawn_pull()
+                        } else if let Some(params) = &notification.params {
+                            let section = params.settings.get(C::SECTION).cloned();
+                            self.update_configuration(section.unwrap_or_default())
+                        }
+                    }
+                    AllServerNotifications::DidChangeWorkspaceFolders(notification) => {
+                        if let Some(params) = &notification.params {
+                            self.change_folders(&params.event)
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use futures::StreamExt;
+    use lsp_types::{
+        notification::{DidChangeConfiguration, DidChangeWorkspaceFolders, Initialized},
+        ClientCapabilities, DidChangeConfigurationParams, DidChangeWorkspaceFoldersParams,
+        InitializedParams, Url, WorkspaceClientCapabilities,
+    };
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use crate::messages::{
+        core::{
+            notification::NotificationMessage,
+            response::{ResponseId, UntypedResponseMessage},
+        },
+        groups::requests::tests::initialize_params_mock,
+    };
+
+    use super::*;
+
+    #[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+    struct SettingsMock {
+        check_on_save: bool,
+    }
+
+    impl ConfigurationSection for SettingsMock {
+        const SECTION: &'static str = "mock";
+    }
+
+    /// A folder at `/{name}`.
+    pub(crate) fn folder_mock(name: &str) -> WorkspaceFolder {
+        WorkspaceFolder {
+            uri: Url::parse(&format!("file:///{}", name)).unwrap(),
+            name: name.to_string(),
+        }
+    }
+
+    /// Opens folders `/a` and `/b`.
+    fn initialize_params(client_pulls_configuration: bool) -> InitializeParams {
+        InitializeParams {
+            workspace_folders: Some(vec![folder_mock("a"), folder_mock("b")]),
+            capabilities: ClientCapabilities {
+                workspace: Some(WorkspaceClientCapabilities {
+                    configuration: Some(client_pulls_configuration),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tracks_workspace_folders() {
+        let (outbox, _outgoing_rx, _pending_responses) = Outbox::new(None);
+        let mut workspace = Workspace::<SettingsMock>::new(outbox);
+        workspace.inspect_incoming(&initialize_params_mock(initialize_params(false)));
+        let mut folders_rx = workspace.watch_folders();
+
+        workspace.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<DidChangeWorkspaceFolders> {
+                params: Some(DidChangeWorkspaceFoldersParams {
+                    event: WorkspaceFoldersChangeEvent {
+                        added: vec![folder_mock("c")],
+                        removed: vec![folder_mock("a")],
+                    },
+                }),
+            }
+            .into(),
+        ));
+        assert!(folders_rx.has_changed().unwrap());
+        assert_eq!(
+            vec![folder_mock("b"), folder_mock("c")],
+            *folders_rx.borrow_and_update()
+        );
+    }
+
+    #[tokio::test]
+    async fn pulls_configuration_on_change() {
+        let (outbox, mut outgoing_rx, pending_responses) = Outbox::new(None);
+        let mut workspace = Workspace::<SettingsMock>::new(outbox);
+        let mut configuration_rx = workspace.watch_configuration();
+        workspace.inspect_incoming(&initialize_params_mock(initialize_params(true)));
+
+        workspace.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<Initialized> {
+                params: Some(InitializedParams {}),
+            }
+            .into(),
+        ));
+        let request = outgoing_rx.next().await.unwrap();
+        pending_responses
+            .resolve(UntypedResponseMessage {
+                id: ResponseId::from(request.request_id().unwrap().clone()),
+                kind: Ok(json!([{ "check_on_save": true }])),
+            })
+            .unwrap();
+
+        configuration_rx.changed().await.unwrap();
+        assert_eq!(
+            SettingsMock {
+                check_on_save: true
+            },
+            workspace.configuration()
+        );
+    }
+
+    #[test]
+    fn reads_pushed_settings_without_pull_support() {
+        let (outbox, mut outgoing_rx, _pending_responses) = Outbox::new(None);
+        let mut workspace = Workspace::<SettingsMock>::new(outbox.clone());
+        workspace.inspect_incoming(&initialize_params_mock(initialize_params(false)));
+
+        workspace.inspect_incoming(&AllMessages::Notifications(
+            NotificationMessage::<DidChangeConfiguration> {
+                params: Some(DidChangeConfigurationParams {
+                    settings: json!({ "mock": { "check_on_save": true } }),
+                }),
+            }
+            .into(),
+        ));
+        assert!(workspace.configuration().check_on_save);
+        assert!(outgoing_rx.try_next().is_err());
+    }
+}
@@ -0,0 +1,280 @@
+mod stats;
+
+pub use stats::{method_stats, pair_exchanges, Exchange, MethodStats};
+
+use std::{collections::HashMap, fmt::Write};
+
+use bytes::BytesMut;
+use serde_json::Value;
+use spique_core::{
+    messages::{
+        codec::{DecodeError, LanguageServerCodec},
+        groups::{raw::RawMessage, AllMessages},
+    },
+    session::{parse_session_log, Direction},
+};
+use tokio_util::codec::Decoder;
+
+/// A message read from a session log or a raw captured stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Unknown for raw streams unless given when loading.
+    pub direction: Option<Direction>,
+    /// Microseconds since the recording started, raw streams carry no timing.
+    pub timestamp_us: Option<u64>,
+    /// `None` when the frame isn't JSON at all.
+    pub message: Option<RawMessage>,
+    /// Why the entry doesn't decode as any known message.
+    pub malformed: Option<String>,
+}
+
+impl Entry {
+    fn new(
+        direction: Option<Direction>,
+        timestamp_us: Option<u64>,
+        message: Option<RawMessage>,
+        error: Option<String>,
+    ) -> Self {
+        let malformed = error.or_else(|| {
+            let value = message
+                .as_ref()
+                .map_or(Value::Null, |message| message.0.clone());
+            serde_json::from_value::<AllMessages>(value)
+                .err()
+                .map(|err| err.to_string())
+        });
+
+        Self {
+            direction,
+            timestamp_us,
+            message,
+            malformed,
+        }
+    }
+
+    pub fn method(&self) -> Option<&str> {
+        self.message.as_ref()?.method()
+    }
+
+    pub fn id(&self) -> Option<String> {
+        self.message.as_ref()?.id_key()
+    }
+}
+
+/// Loads either a JSONL session log or a raw stream of `Content-Length` framed messages, the
+/// latter being detected by its leading header. Every raw message is given `raw_direction`.
+pub fn load(contents: &[u8], raw_direction: Option<Direction>) -> std::io::Result<Vec<Entry>> {
+    let leading = contents.trim_ascii_start();
+    if leading.len() >= 7 && leading[..7].eq_ignore_ascii_case(b"content") {
+        return Ok(load_raw_stream(leading, raw_direction));
+    }
+
+    let session_log = std::str::from_utf8(contents)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    Ok(parse_session_log(session_log)?
+        .into_iter()
+        .map(|entry| {
+            let message = serde_json::from_str(&entry.body).ok();
+            Entry::new(
+                Some(entry.direction),
+                Some(entry.timestamp_us),
+                message,
+                entry.error,
+            )
+        })
+        .collect())
+}
+
+fn load_raw_stream(contents: &[u8], direction: Option<Direction>) -> Vec<Entry> {
+    let mut codec = LanguageServerCodec::<RawMessage>::default();
+    let mut src = BytesMut::from(contents);
+    let mut entries = Vec::new();
+
+    loop {
+        let frame_start = contents.len() - src.len();
+        match codec.decode(&mut src) {
+            Ok(Some(message)) => entries.push(Entry::new(direction, None, Some(message), None)),
+            Ok(None) => break,
+            Err(err) => {
+                let headers_unparsable = matches!(err, DecodeError::Httparse(_));
+                entries.push(Entry::new(direction, None, None, Some(err.to_string())));
+                // The codec discards its whole buffer when unable to parse headers, so pick up
+                // again from the next frame in the stream.
+                if headers_unparsable {
+                    match find_next_frame(&contents[frame_start + 1..]) {
+                        Some(offset) => src = BytesMut::from(&contents[frame_start + 1 + offset..]),
+                        None => break,
+                    }
+                }
+            }
+        }
+    }
+
+    if !src.trim_ascii().is_empty() {
+        let error = Some("stream ends with an incomplete frame".to_owned());
+        entries.push(Entry::new(direction, None, None, error));
+    }
+
+    entries
+}
+
+fn find_next_frame(contents: &[u8]) -> Option<usize> {
+    const FRAME_START: &[u8] = b"Content-Length:";
+    contents
+        .windows(FRAME_START.len())
+        .position(|window| window == FRAME_START)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Matches responses through the method of their request, any method when empty.
+    pub methods: Vec<String>,
+    pub direction: Option<Direction>,
+    pub id: Option<String>,
+}
+
+impl Filter {
+    fn matches(&self, entry: &Entry, method: Option<&str>) -> bool {
+        (self.methods.is_empty()
+            || method.is_some_and(|method| self.methods.iter().any(|wanted| wanted == method)))
+            && (self.direction.is_none() || entry.direction == self.direction)
+            && (self.id.is_none() || entry.id() == self.id)
+    }
+}
+
+/// Describes the entries passing the filter one per line, with responses annotated by the
+/// method and latency of their request.
+pub fn render_timeline(entries: &[Entry], filter: &Filter) -> String {
+    let exchanges = pair_exchanges(entries);
+    let exchanges_by_response = exchanges
+        .iter()
+        .filter_map(|exchange| Some((exchange.response?, exchange)))
+        .collect::<HashMap<_, _>>();
+    let mut timeline = String::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let exchange = exchanges_by_response.get(&index).copied();
+        let method = entry
+            .method()
+            .or(exchange.map(|exchange| exchange.method.as_str()));
+        if !filter.matches(entry, method) {
+            continue;
+        }
+
+        let timestamp = match entry.timestamp_us {
+            Some(timestamp_us) => format!("{:>10.3} ms", timestamp_us as f64 / 1000.0),
+            None => format!("{:>13}", "-"),
+        };
+        let arrow = match entry.direction {
+            Some(Direction::Incoming) => "-->",
+            Some(Direction::Outgoing) => "<--",
+            None => "---",
+        };
+        let description = match (&entry.message, entry.id()) {
+            (Some(message), Some(id)) if message.is_request() => {
+                format!("request #{} {}", id, method.unwrap_or_default())
+            }
+            (Some(message), Some(id)) if message.is_response() => {
+                let mut description = format!("response #{}", id);
+                if let Some(exchange) = exchange {
+                    let _ = write!(description, " {}", exchange.method);
+                    if let Some(latency_us) = exchange.latency_us {
+                        let _ = write!(description, " ({:.1} ms)", latency_us as f64 / 1000.0);
+                    }
+                }
+                if message.0.get("error").is_some() {
+                    description.push_str(" error");
+                }
+                description
+            }
+            (Some(_), _) if method.is_some() => {
+                format!("notification {}", method.unwrap_or_default())
+            }
+            _ => "unrecognized message".to_owned(),
+        };
+
+        let _ = writeln!(timeline, "{}  {} {}", timestamp, arrow, description);
+        if let Some(malformed) = &entry.malformed {
+            let _ = writeln!(timeline, "{:>13}  !! malformed: {}", "", malformed);
+        }
+    }
+
+    timeline
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use spique_core::messages::payload::Payload;
+
+    use super::*;
+
+    pub const SESSION_LOG_MOCK: &str = r#"
+{"direction":"incoming","timestamp_us":1000,"body":"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"shutdown\"}"}
+{"direction":"incoming","timestamp_us":1500,"body":"{\"jsonrpc\":\"2.0\",\"method\":\"spique/unknown\"}"}
+{"direction":"outgoing","timestamp_us":3000,"body":"{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":null}"}
+{"direction":"incoming","timestamp_us":4000,"body":"{","error":"EOF while parsing an object"}
+"#;
+
+    #[test]
+    fn loads_session_logs() {
+        let entries = load(SESSION_LOG_MOCK.as_bytes(), None).unwrap();
+
+        assert_eq!(4, entries.len());
+        assert_eq!(Some("shutdown"), entries[0].method());
+        assert!(entries[0].malformed.is_none());
+        assert!(entries[1].malformed.is_some());
+        assert!(entries[2].malformed.is_none());
+        assert_eq!(
+            Some("EOF while parsing an object"),
+            entries[3].malformed.as_deref()
+        );
+    }
+
+    #[test]
+    fn loads_raw_streams() {
+        let request = RawMessage::request(1.into(), "shutdown", None);
+        let raw_stream = format!(
+            "{}Content-Length: 1\r\n\r\n{{{}",
+            Payload::new(&request),
+            Payload::new(&request)
+        );
+
+        let entries = load(raw_stream.as_bytes(), Some(Direction::Incoming)).unwrap();
+        assert_eq!(3, entries.len());
+        assert_eq!(Some(&request), entries[0].message.as_ref());
+        assert!(entries[1].malformed.is_some());
+        assert_eq!(Some(&request), entries[2].message.as_ref());
+        assert!(entries
+            .iter()
+            .all(|entry| entry.direction == Some(Direction::Incoming)));
+    }
+
+    #[test]
+    fn renders_filtered_timeline() {
+        let entries = load(SESSION_LOG_MOCK.as_bytes(), None).unwrap();
+        let filter = Filter {
+            methods: vec!["shutdown".to_owned()],
+            ..Default::default()
+        };
+
+        let timeline = render_timeline(&entries, &filter);
+        let lines = timeline.lines().collect::<Vec<_>>();
+        assert_eq!(2, lines.len());
+        assert_eq!("     1.000 ms  --> request #1 shutdown", lines[0]);
+        assert_eq!("     3.000 ms  <-- response #1 shutdown (2.0 ms)", lines[1]);
+    }
+
+    #[test]
+    fn resyncs_raw_streams_after_unparsable_headers() {
+        let request = RawMessage::request(1.into(), "shutdown", None);
+        let raw_stream = format!(
+            "Content-Length: 1\r\nbroken header\r\n\r\n{}",
+            Payload::new(&request)
+        );
+
+        let entries = load(raw_stream.as_bytes(), None).unwrap();
+        assert_eq!(2, entries.len());
+        assert!(entries[0].malformed.is_some());
+        assert_eq!(Some(&request), entries[1].message.as_ref());
+    }
+}
@@ -0,0 +1,109 @@
+use std::process::ExitCode;
+
+use serde_json::Value;
+use spique_core::session::Direction;
+use spique_inspect::{load, method_stats, pair_exchanges, render_timeline, Filter};
+
+const USAGE: &str = "usage: spique-inspect [options] <session log or raw stream>\n\
+     \n\
+     --method <method>        only show the method, may be repeated\n\
+     --direction <direction>  only show incoming or outgoing messages\n\
+     --id <id>                only show messages with the id\n\
+     --raw-direction <dir>    direction of every message in a raw stream\n\
+     --pairs                  list requests with their responses\n\
+     --stats                  show latency statistics per method";
+
+enum View {
+    Timeline,
+    Pairs,
+    Stats,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run() -> Result<ExitCode, String> {
+    let mut args = std::env::args().skip(1);
+    let mut filter = Filter::default();
+    let mut raw_direction = None;
+    let mut view = View::Timeline;
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| USAGE.to_owned());
+        match arg.as_str() {
+            "--method" => filter.methods.push(value()?),
+            "--direction" => filter.direction = Some(parse_direction(value()?)?),
+            "--id" => filter.id = Some(value()?),
+            "--raw-direction" => raw_direction = Some(parse_direction(value()?)?),
+            "--pairs" => view = View::Pairs,
+            "--stats" => view = View::Stats,
+            _ if arg.starts_with("--") || path.is_some() => return Err(USAGE.to_owned()),
+            _ => path = Some(arg),
+        }
+    }
+
+    let path = path.ok_or_else(|| USAGE.to_owned())?;
+    let contents =
+        std::fs::read(&path).map_err(|err| format!("unable to read {}: {}", path, err))?;
+    let entries = load(&contents, raw_direction)
+        .map_err(|err| format!("unable to load {}: {}", path, err))?;
+
+    match view {
+        View::Timeline => print!("{}", render_timeline(&entries, &filter)),
+        View::Pairs => {
+            for exchange in pair_exchanges(&entries) {
+                let request = &entries[exchange.request];
+                if !filter.methods.is_empty() && !filter.methods.contains(&exchange.method)
+                    || filter.id.is_some() && request.id() != filter.id
+                {
+                    continue;
+                }
+                let outcome = match (exchange.response, exchange.latency_us) {
+                    (Some(_), Some(latency_us)) => format!("{:.1} ms", latency_us as f64 / 1000.0),
+                    (Some(_), None) => "answered".to_owned(),
+                    (None, _) => "unanswered".to_owned(),
+                };
+                println!(
+                    "#{} {} {}",
+                    request.id().unwrap_or_default(),
+                    exchange.method,
+                    outcome
+                );
+            }
+        }
+        View::Stats => {
+            println!(
+                "{:<40} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+                "method", "requests", "unanswered", "min ms", "median ms", "mean ms", "max ms"
+            );
+            for stats in method_stats(&pair_exchanges(&entries)) {
+                if filter.methods.is_empty() || filter.methods.contains(&stats.method) {
+                    println!("{}", stats);
+                }
+            }
+        }
+    }
+
+    let malformed_count = entries
+        .iter()
+        .filter(|entry| entry.malformed.is_some())
+        .count();
+    if malformed_count > 0 {
+        eprintln!("{} malformed entries", malformed_count);
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn parse_direction(direction: String) -> Result<Direction, String> {
+    serde_json::from_value(Value::String(direction))
+        .map_err(|_| "direction must be incoming or outgoing".to_owned())
+}
@@ -0,0 +1,178 @@
+use std::{collections::HashMap, fmt::Display};
+
+use spique_core::session::Direction;
+
+use crate::Entry;
+
+/// A request paired with its response, by index into the entries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exchange {
+    pub method: String,
+    pub request: usize,
+    pub response: Option<usize>,
+    /// Only known when both entries carry timestamps.
+    pub latency_us: Option<u64>,
+}
+
+/// Pairs requests with responses carrying the same id in the opposite direction, or with any
+/// response carrying the same id when directions are unknown.
+pub fn pair_exchanges(entries: &[Entry]) -> Vec<Exchange> {
+    let mut exchanges = Vec::new();
+    let mut pending = HashMap::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let (Some(message), Some(id)) = (&entry.message, entry.id()) else {
+            continue;
+        };
+
+        if let Some(method) = message.method().filter(|_| message.is_request()) {
+            pending.insert((entry.direction, id), exchanges.len());
+            exchanges.push(Exchange {
+                method: method.to_owned(),
+                request: index,
+                response: None,
+                latency_us: None,
+            });
+        } else if message.is_response() {
+            let request_direction = entry.direction.map(|direction| match direction {
+                Direction::Incoming => Direction::Outgoing,
+                Direction::Outgoing => Direction::Incoming,
+            });
+            if let Some(exchange_index) = pending.remove(&(request_direction, id)) {
+                let exchange = &mut exchanges[exchange_index];
+                exchange.response = Some(index);
+                exchange.latency_us = entry
+                    .timestamp_us
+                    .zip(entries[exchange.request].timestamp_us)
+                    .map(|(response_us, request_us)| response_us.saturating_sub(request_us));
+            }
+        }
+    }
+
+    exchanges
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodStats {
+    pub method: String,
+    pub requests: usize,
+    pub unanswered: usize,
+    /// Sorted latencies of the answered requests with known timing.
+    pub latencies_us: Vec<u64>,
+}
+
+impl MethodStats {
+    pub fn min_us(&self) -> Option<u64> {
+        self.latencies_us.first().copied()
+    }
+
+    pub fn median_us(&self) -> Option<u64> {
+        self.latencies_us.get(self.latencies_us.len() / 2).copied()
+    }
+
+    pub fn max_us(&self) -> Option<u64> {
+        self.latencies_us.last().copied()
+    }
+
+    pub fn mean_us(&self) -> Option<u64> {
+        let count = self.latencies_us.len() as u64;
+        (count > 0).then(|| self.latencies_us.iter().sum::<u64>() / count)
+    }
+}
+
+impl Display for MethodStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format_ms = |latency_us: Option<u64>| match latency_us {
+            Some(latency_us) => format!("{:.1}", latency_us as f64 / 1000.0),
+            None => "-".to_owned(),
+        };
+        write!(
+            f,
+            "{:<40} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            self.method,
+            self.requests,
+            self.unanswered,
+            format_ms(self.min_us()),
+            format_ms(self.median_us()),
+            format_ms(self.mean_us()),
+            format_ms(self.max_us())
+        )
+    }
+}
+
+/// Latency statistics per method, ordered by method.
+pub fn method_stats(exchanges: &[Exchange]) -> Vec<MethodStats> {
+    let mut stats_by_method = HashMap::<&str, MethodStats>::new();
+    for exchange in exchanges {
+        let stats = stats_by_method
+            .entry(&exchange.method)
+            .or_insert_with(|| MethodStats {
+                method: exchange.method.clone(),
+                requests: 0,
+                unanswered: 0,
+                latencies_us: Vec::new(),
+            });
+        stats.requests += 1;
+        if exchange.response.is_none() {
+            stats.unanswered += 1;
+        }
+        stats.latencies_us.extend(exchange.latency_us);
+    }
+
+    let mut stats = stats_by_method.into_values().collect::<Vec<_>>();
+    for method_stats in &mut stats {
+        method_stats.latencies_us.sort_unstable();
+    }
+    stats.sort_by(|a, b| a.method.cmp(&b.method));
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{load, tests::SESSION_LOG_MOCK};
+
+    use super::*;
+
+    #[test]
+    fn pairs_requests_with_responses() {
+        let entries = load(SESSION_LOG_MOCK.as_bytes(), None).unwrap();
+        assert_eq!(
+            vec![Exchange {
+                method: "shutdown".to_owned(),
+                request: 0,
+                response: Some(2),
+                latency_us: Some(2000),
+            }],
+            pair_exchanges(&entries)
+        );
+    }
+
+    #[test]
+    fn computes_method_stats() {
+        let exchange = |method: &str, latency_us: Option<u64>| Exchange {
+            method: method.to_owned(),
+            request: 0,
+            response: latency_us.map(|_| 1),
+            latency_us,
+        };
+        let exchanges = [
+            exchange("textDocument/hover", Some(3000)),
+            exchange("shutdown", None),
+            exchange("textDocument/hover", Some(1000)),
+            exchange("textDocument/hover", Some(2000)),
+        ];
+
+        let stats = method_stats(&exchanges);
+        assert_eq!(vec!["shutdown", "textDocument/hover"], {
+            stats
+                .iter()
+                .map(|stats| stats.method.as_str())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(1, stats[0].unanswered);
+        assert_eq!(Some(1000), stats[1].min_us());
+        assert_eq!(Some(2000), stats[1].median_us());
+        assert_eq!(Some(2000), stats[1].mean_us());
+        assert_eq!(Some(3000), stats[1].max_us());
+    }
+}
@@ -0,0 +1,219 @@
+mod monitor;
+
+pub use monitor::TrafficMonitor;
+
+use std::io::Write;
+
+use bytes::BytesMut;
+use futures::{SinkExt, StreamExt};
+use spique_core::{
+    messages::{
+        codec::{DecodeError, Frames, LanguageServerCodec},
+        groups::raw::RawMessage,
+    },
+    session::Direction,
+};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::{Decoder, Encoder, FramedRead, FramedWrite};
+
+/// Passes frame contents along untouched, so that the tap never alters the traffic it observes,
+/// not even the frames it fails to decode.
+#[derive(Default)]
+struct FrameCodec(LanguageServerCodec<RawMessage>);
+
+impl Decoder for FrameCodec {
+    type Item = BytesMut;
+    type Error = DecodeError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.0.decode_frame(src)
+    }
+}
+
+impl Encoder<BytesMut> for FrameCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: BytesMut, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.encode_frame(&item, dst);
+        Ok(())
+    }
+}
+
+/// Forwards messages between the editor and the server until the server closes its output,
+/// writing a description of each one to the log.
+pub async fn tap(
+    editor_output: impl AsyncRead + Unpin,
+    editor_input: impl AsyncWrite + Unpin,
+    server_output: impl AsyncRead + Unpin,
+    server_input: impl AsyncWrite + Unpin,
+    mut monitor: TrafficMonitor,
+    mut log: impl Write,
+) {
+    let mut from_editor = Frames::new(FramedRead::new(editor_output, FrameCodec::default()));
+    let mut to_editor = FramedWrite::new(editor_input, FrameCodec::default());
+    let mut from_server = Frames::new(FramedRead::new(server_output, FrameCodec::default()));
+    let mut to_server = Some(FramedWrite::new(server_input, FrameCodec::default()));
+    let mut write_log = |lines: Vec<String>| {
+        for line in lines {
+            let _ = writeln!(log, "{}", line);
+        }
+        let _ = log.flush();
+    };
+
+    loop {
+        tokio::select! {
+            decode_attempt = from_editor.next(), if to_server.is_some() => match decode_attempt {
+                Some(Ok(frame)) => {
+                    write_log(describe(&mut monitor, Direction::Incoming, &frame));
+                    if let Some(framed_input) = &mut to_server {
+                        if let Err(err) = framed_input.send(frame).await {
+                            write_log(vec![format!("!! unable to write to server: {:?}", err)]);
+                            to_server = None;
+                        }
+                    }
+                }
+                Some(Err(err)) => {
+                    write_log(vec![format!("!! unreadable editor frame dropped: {}", err)]);
+                }
+                // Closes the server input, letting the server exit on its own.
+                None => to_server = None,
+            },
+            decode_attempt = from_server.next() => match decode_attempt {
+                Some(Ok(frame)) => {
+                    write_log(describe(&mut monitor, Direction::Outgoing, &frame));
+                    if let Err(err) = to_editor.send(frame).await {
+                        write_log(vec![format!("!! unable to write to editor: {:?}", err)]);
+                    }
+                }
+                Some(Err(err)) => {
+                    write_log(vec![format!("!! unreadable server frame dropped: {}", err)]);
+                }
+                None => break,
+            }
+        }
+    }
+
+    write_log(monitor.unanswered());
+}
+
+fn describe(monitor: &mut TrafficMonitor, direction: Direction, frame: &[u8]) -> Vec<String> {
+    match serde_json::from_slice::<RawMessage>(frame) {
+        Ok(message) => monitor.observe(direction, &message),
+        Err(err) => {
+            let sender = match direction {
+                Direction::Incoming => "editor",
+                Direction::Outgoing => "server",
+            };
+            vec![
+                format!("!! undecodable {} frame forwarded as is: {}", sender, err),
+                String::from_utf8_lossy(frame).into_owned(),
+            ]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::Value;
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedLog(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedLog {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Write::write(&mut *self.0.lock().unwrap(), buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn forwards_and_logs_traffic() {
+        let (editor_output, tap_input) = tokio::io::duplex(4096);
+        let (tap_output, editor_input) = tokio::io::duplex(4096);
+        let (server_input, tap_server_input) = tokio::io::duplex(4096);
+        let (tap_server_output, server_output) = tokio::io::duplex(4096);
+        let log = SharedLog::default();
+
+        let tap_handle = tokio::spawn(tap(
+            tap_input,
+            tap_output,
+            tap_server_output,
+            tap_server_input,
+            TrafficMonitor::new(true),
+            log.clone(),
+        ));
+
+        // Server answers every request with a null result.
+        tokio::spawn(async move {
+            let mut from_tap =
+                FramedRead::new(server_input, LanguageServerCodec::<RawMessage>::default());
+            let mut to_tap =
+                FramedWrite::new(server_output, LanguageServerCodec::<RawMessage>::default());
+            while let Some(Ok(message)) = from_tap.next().await {
+                if let Some(id) = message.id() {
+                    let response = RawMessage::response(id.clone(), Ok(Value::Null));
+                    to_tap.send(response).await.unwrap();
+                }
+            }
+        });
+
+        let mut to_tap =
+            FramedWrite::new(editor_output, LanguageServerCodec::<RawMessage>::default());
+        let mut from_tap =
+            FramedRead::new(editor_input, LanguageServerCodec::<RawMessage>::default());
+        let request = RawMessage::request(1.into(), "shutdown", None);
+        to_tap.send(request).await.unwrap();
+        assert_eq!(
+            Some(&Value::from(1)),
+            from_tap.next().await.unwrap().unwrap().id()
+        );
+
+        drop(to_tap);
+        tap_handle.await.unwrap();
+
+        let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        assert!(log.contains("--> request #1 shutdown"));
+        assert!(log.contains("<-- response #1 shutdown ("));
+        assert!(!log.contains("!!"));
+    }
+
+    #[tokio::test]
+    async fn forwards_undecodable_frames_as_is() {
+        let (mut editor_output, tap_input) = tokio::io::duplex(4096);
+        let (tap_output, _editor_input) = tokio::io::duplex(4096);
+        let (server_input, tap_server_input) = tokio::io::duplex(4096);
+        let (tap_server_output, server_output) = tokio::io::duplex(4096);
+        let log = SharedLog::default();
+
+        let tap_handle = tokio::spawn(tap(
+            tap_input,
+            tap_output,
+            tap_server_output,
+            tap_server_input,
+            TrafficMonitor::new(true),
+            log.clone(),
+        ));
+
+        editor_output
+            .write_all(b"Content-Length: 5\r\n\r\n{oops")
+            .await
+            .unwrap();
+        let mut from_tap = FramedRead::new(server_input, FrameCodec::default());
+        assert_eq!(b"{oops", &from_tap.next().await.unwrap().unwrap()[..]);
+
+        drop(editor_output);
+        drop(server_output);
+        tap_handle.await.unwrap();
+
+        let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        assert!(log.contains("!! undecodable editor frame forwarded as is"));
+    }
+}
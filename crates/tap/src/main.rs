@@ -0,0 +1,75 @@
+use std::{
+    fs::File,
+    io::{LineWriter, Write},
+    process::{ExitCode, Stdio},
+};
+
+use spique_tap::{tap, TrafficMonitor};
+use tokio::process::Command;
+
+const USAGE: &str = "usage: spique-tap [--log <file>] [--check] <server> [server args...]\n\
+     Messages are logged to stderr unless a log file is given.";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    let mut log: Box<dyn Write + Send> = Box::new(std::io::stderr());
+    let mut check_violations = false;
+
+    while let Some(flag) = args.next_if(|arg| arg.starts_with("--")) {
+        match flag.as_str() {
+            "--check" => check_violations = true,
+            "--log" => match args.next().map(File::create) {
+                Some(Ok(file)) => log = Box::new(LineWriter::new(file)),
+                Some(Err(err)) => {
+                    eprintln!("unable to create log file: {}", err);
+                    return ExitCode::from(2);
+                }
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::from(2);
+                }
+            },
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let Some(program) = args.next() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+
+    let mut child = match Command::new(&program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) => {
+            eprintln!("unable to launch {}: {}", program, err);
+            return ExitCode::from(2);
+        }
+    };
+
+    let server_input = child.stdin.take().expect("stdin should be piped");
+    let server_output = child.stdout.take().expect("stdout should be piped");
+    tap(
+        tokio::io::stdin(),
+        tokio::io::stdout(),
+        server_output,
+        server_input,
+        TrafficMonitor::new(check_violations),
+        log,
+    )
+    .await;
+
+    // Mirrors the server exit code so that editors see the same outcome as without the tap.
+    match child.wait().await.ok().and_then(|status| status.code()) {
+        Some(code) => ExitCode::from(code as u8),
+        None => ExitCode::FAILURE,
+    }
+}
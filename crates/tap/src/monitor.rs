@@ -0,0 +1,148 @@
+use std::{collections::HashMap, time::Instant};
+
+use spique_core::{messages::groups::raw::RawMessage, session::Direction};
+
+struct PendingRequest {
+    method: String,
+    sent_at: Instant,
+}
+
+/// Matches requests with their responses as traffic passes the tap, describing each message and
+/// optionally the protocol violations found along the way.
+pub struct TrafficMonitor {
+    check_violations: bool,
+    /// Keyed by the direction of the request, since each side picks its own ids.
+    pending_requests: HashMap<(Direction, String), PendingRequest>,
+}
+
+impl TrafficMonitor {
+    pub fn new(check_violations: bool) -> Self {
+        Self {
+            check_violations,
+            pending_requests: HashMap::new(),
+        }
+    }
+
+    /// Returns the log lines describing the message.
+    pub fn observe(&mut self, direction: Direction, message: &RawMessage) -> Vec<String> {
+        let arrow = match direction {
+            Direction::Incoming => "-->",
+            Direction::Outgoing => "<--",
+        };
+        let id = message.id_key();
+        let mut lines = Vec::new();
+
+        let summary = match (message.method(), id) {
+            (Some(method), Some(id)) => {
+                let pending_request = PendingRequest {
+                    method: method.to_owned(),
+                    sent_at: Instant::now(),
+                };
+                let previous = self
+                    .pending_requests
+                    .insert((direction, id.clone()), pending_request);
+                if previous.is_some() && self.check_violations {
+                    lines.push(format!(
+                        "!! duplicate request id {} while still pending",
+                        id
+                    ));
+                }
+                format!("{} request #{} {}", arrow, id, method)
+            }
+            (Some(method), None) => format!("{} notification {}", arrow, method),
+            (None, Some(id)) => {
+                let request_direction = match direction {
+                    Direction::Incoming => Direction::Outgoing,
+                    Direction::Outgoing => Direction::Incoming,
+                };
+                match self
+                    .pending_requests
+                    .remove(&(request_direction, id.clone()))
+                {
+                    Some(request) => format!(
+                        "{} response #{} {} ({:.1} ms)",
+                        arrow,
+                        id,
+                        request.method,
+                        request.sent_at.elapsed().as_secs_f64() * 1000.0
+                    ),
+                    None => {
+                        if self.check_violations {
+                            lines.push(format!("!! response to unknown request id {}", id));
+                        }
+                        format!("{} response #{}", arrow, id)
+                    }
+                }
+            }
+            (None, None) => {
+                if self.check_violations {
+                    lines.push(
+                        "!! message is neither a request, response nor notification".to_owned(),
+                    );
+                }
+                format!("{} message", arrow)
+            }
+        };
+
+        lines.insert(0, summary);
+        lines.push(serde_json::to_string_pretty(&message.0).unwrap_or_default());
+        lines
+    }
+
+    /// Describes the requests left without a response, meant for when the session has ended.
+    pub fn unanswered(&self) -> Vec<String> {
+        if !self.check_violations {
+            return Vec::new();
+        }
+
+        self.pending_requests
+            .iter()
+            .map(|((direction, id), request)| {
+                let sender = match direction {
+                    Direction::Incoming => "editor",
+                    Direction::Outgoing => "server",
+                };
+                format!(
+                    "!! unanswered {} request #{} {}",
+                    sender, id, request.method
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn matches_responses_with_requests() {
+        let mut monitor = TrafficMonitor::new(true);
+        let request = RawMessage::request(1.into(), "textDocument/hover", None);
+        let response = RawMessage::response(1.into(), Ok(Value::Null));
+
+        assert_eq!(
+            "--> request #1 textDocument/hover",
+            monitor.observe(Direction::Incoming, &request)[0]
+        );
+        let response_lines = monitor.observe(Direction::Outgoing, &response);
+        assert!(response_lines[0].starts_with("<-- response #1 textDocument/hover ("));
+        assert!(monitor.unanswered().is_empty())
+    }
+
+    #[test]
+    fn reports_violations() {
+        let mut monitor = TrafficMonitor::new(true);
+        let request = RawMessage::request(1.into(), "textDocument/hover", None);
+        monitor.observe(Direction::Incoming, &request);
+
+        assert!(monitor.observe(Direction::Incoming, &request)[1].starts_with("!! duplicate"));
+        let unknown_response = RawMessage::response(2.into(), Ok(Value::Null));
+        assert!(
+            monitor.observe(Direction::Outgoing, &unknown_response)[1].starts_with("!! response")
+        );
+        assert_eq!(1, monitor.unanswered().len());
+    }
+}